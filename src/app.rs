@@ -0,0 +1,191 @@
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::body::{self, Bytes};
+use hyper::{Body, Method, Request, Uri};
+use log::debug;
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::config::AppConfig;
+use crate::errors::Error;
+use crate::github::{self, GitHub};
+use crate::Result;
+
+/// Installation tokens are minted again this long before they expire, so
+/// that no request is sent with a token expiring on its way
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Authentication as a GitHub App instead of with a personal token: the
+/// app signs a JWT with its private key to mint installation tokens,
+/// which last an hour and are minted again as they expire during long
+/// runs
+pub struct App {
+    app_id: u64,
+    key: PKey<Private>,
+    /// Installation to mint tokens of, none to use the only one
+    installation_id: Option<u64>,
+    /// REST API URL of the instance the app is registered on
+    api_url: String,
+    token: Mutex<Option<InstallationToken>>,
+}
+
+struct InstallationToken {
+    token: String,
+    expires_at: SystemTime,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+    expires_at: String,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+    account: Account,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    login: String,
+}
+
+impl App {
+    pub fn new(config: &AppConfig, api_url: String) -> Result<App> {
+        let pem = fs::read(&config.private_key)
+            .map_err(|source| Error::Read { path: config.private_key.clone(), source })?;
+        let key = PKey::private_key_from_pem(&pem)
+            .map_err(|e| format!("{} is not a PEM private key: {}", config.private_key, e))?;
+        Ok(App { app_id: config.app_id, key, installation_id: config.installation_id, api_url, token: Mutex::new(None) })
+    }
+
+    /// Token of the installation, minted when there's none yet or it's
+    /// about to expire
+    pub async fn token(&self, github: &GitHub) -> Result<String> {
+        // Concurrent requests wait for a single token to be minted
+        let mut token = self.token.lock().await;
+        let now = SystemTime::now();
+        if let Some(current) = token.as_ref().filter(|current| current.expires_at > now + REFRESH_MARGIN) {
+            return Ok(current.token.clone());
+        }
+        let minted = self.mint(github).await?;
+        let value = minted.token.clone();
+        *token = Some(minted);
+        Ok(value)
+    }
+
+    async fn mint(&self, github: &GitHub) -> Result<InstallationToken> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let jwt = self.jwt(now)?;
+        let installation_id = match self.installation_id {
+            Some(installation_id) => installation_id,
+            None => self.installation(github, &jwt).await?,
+        };
+
+        let uri = format!("{}/app/installations/{}/access_tokens", self.api_url, installation_id);
+        let access: AccessToken = serde_json::from_slice(&self.send(github, Method::POST, &uri, &jwt).await?)?;
+        let expires_at = chrono::DateTime::parse_from_rfc3339(&access.expires_at)
+            .map_err(|e| format!("Invalid expiry {:?} of the installation token: {}", access.expires_at, e))?;
+        debug!("Minted a token of installation {} of GitHub App {}, expiring at {}", installation_id, self.app_id, access.expires_at);
+        Ok(InstallationToken {
+            token: access.token,
+            expires_at: UNIX_EPOCH + Duration::from_secs(expires_at.timestamp().max(0) as u64),
+        })
+    }
+
+    /// Installation of the app when it's installed on a single account
+    async fn installation(&self, github: &GitHub, jwt: &str) -> Result<u64> {
+        let uri = format!("{}/app/installations", self.api_url);
+        let installations: Vec<Installation> = serde_json::from_slice(&self.send(github, Method::GET, &uri, jwt).await?)?;
+        match installations.as_slice() {
+            [installation] => Ok(installation.id),
+            [] => Err(format!("GitHub App {} isn't installed on any account", self.app_id).into()),
+            _ => {
+                let accounts: Vec<String> = installations
+                    .iter()
+                    .map(|installation| format!("{} ({})", installation.account.login, installation.id))
+                    .collect();
+                Err(format!("GitHub App {} is installed on {}, pick one with installation_id", self.app_id, accounts.join(", ")).into())
+            }
+        }
+    }
+
+    /// Sends a request authenticated as the app. It doesn't go through the
+    /// client's retries, which would ask for an installation token again.
+    async fn send(&self, github: &GitHub, method: Method, uri: &str, jwt: &str) -> Result<Bytes> {
+        let user_agent = github.config().http.user_agent.as_deref().unwrap_or(github::USER_AGENT);
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Accept", "application/vnd.github+json")
+            .header("Authorization", format!("Bearer {}", jwt))
+            .header("User-Agent", user_agent);
+        let api_host = uri.parse::<Uri>().ok().and_then(|uri| uri.host().map(str::to_string)).unwrap_or_default();
+        if let Some(version) = github.api_version(&api_host) {
+            builder = builder.header("X-Github-Api-Version", version);
+        }
+        let request = builder.body(Body::empty())?;
+        let res = github.client.request(request).await?;
+        let status = res.status();
+        let body_bytes = body::to_bytes(res).await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(body_bytes)
+    }
+
+    /// JSON Web Token identifying the app, valid for the 10 minutes GitHub
+    /// accepts at most
+    fn jwt(&self, now: u64) -> Result<String> {
+        let header = base64url(br#"{"alg":"RS256","typ":"JWT"}"#);
+        // Issued a minute earlier in case the clock is ahead of GitHub's
+        let claims = serde_json::json!({ "iat": now - 60, "exp": now + 9 * 60, "iss": self.app_id.to_string() });
+        let signed = format!("{}.{}", header, base64url(&serde_json::to_vec(&claims)?));
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(signed.as_bytes())?;
+        Ok(format!("{}.{}", signed, base64url(&signer.sign_to_vec()?)))
+    }
+}
+
+/// Base64 with the URL-safe alphabet and without padding, as JWTs encode
+/// their segments
+fn base64url(data: &[u8]) -> String {
+    base64::encode_block(data).trim_end_matches('=').replace('+', "-").replace('/', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+    use openssl::sign::Verifier;
+
+    fn decode(segment: &str) -> Vec<u8> {
+        let mut segment = segment.replace('-', "+").replace('_', "/");
+        while !segment.len().is_multiple_of(4) {
+            segment.push('=');
+        }
+        base64::decode_block(&segment).unwrap()
+    }
+
+    #[test]
+    fn signs_jwts_with_the_private_key() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let app = App { app_id: 42, key: key.clone(), installation_id: None, api_url: github::API_URL.to_string(), token: Mutex::new(None) };
+
+        let jwt = app.jwt(1_700_000_000).unwrap();
+        let segments: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(segments.len(), 3);
+        let claims: serde_json::Value = serde_json::from_slice(&decode(segments[1])).unwrap();
+        assert_eq!(claims, serde_json::json!({ "iat": 1_699_999_940u64, "exp": 1_700_000_540u64, "iss": "42" }));
+
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.update(format!("{}.{}", segments[0], segments[1]).as_bytes()).unwrap();
+        assert!(verifier.verify(&decode(segments[2])).unwrap());
+    }
+}
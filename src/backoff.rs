@@ -0,0 +1,144 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::header::HeaderMap;
+use hyper::{Method, StatusCode};
+
+/// Retries of a request after a transient failure, unless configured with
+/// max_retries
+pub const MAX_RETRIES: u32 = 3;
+
+/// Longest wait before retrying, unless configured with
+/// max_retry_wait_secs; requests rate limited for longer fail right away
+pub const MAX_WAIT_SECS: u64 = 60;
+
+/// First delay of the exponential backoff, doubled on every retry
+const BASE_DELAY_MILLIS: u64 = 500;
+
+tokio::task_local! {
+    static RETRIES: Cell<u32>;
+}
+
+/// Runs a future and counts the requests it retried
+pub async fn counted<F: Future>(future: F) -> (F::Output, u32) {
+    RETRIES
+        .scope(Cell::new(0), async move {
+            let output = future.await;
+            (output, RETRIES.with(Cell::get))
+        })
+        .await
+}
+
+/// Counts a retry for the future being run by `counted`, if any
+pub fn count() {
+    let _ = RETRIES.try_with(|retries| retries.set(retries.get() + 1));
+}
+
+/// Whether a request may be sent again without side effects, so it can be
+/// retried after a server error or a dropped connection
+pub fn idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS)
+}
+
+/// Whether a response tells that the request was rate limited: 429, or
+/// 403 for GitHub's primary and secondary rate limits
+pub fn rate_limited(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::FORBIDDEN
+            && (header(headers, "x-ratelimit-remaining") == Some("0")
+                || headers.contains_key("retry-after")
+                || String::from_utf8_lossy(body).to_lowercase().contains("rate limit"))
+}
+
+/// How long to wait before retrying a request after its response, none
+/// when it shouldn't be retried. Waits as long as the Retry-After or
+/// X-RateLimit-Reset header tells and backs off exponentially otherwise.
+pub fn delay(method: &Method, status: StatusCode, headers: &HeaderMap, body: &[u8], attempt: u32) -> Option<Duration> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    delay_at(method, status, headers, body, attempt, now, jitter())
+}
+
+fn delay_at(
+    method: &Method,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+    attempt: u32,
+    now: u64,
+    jitter: f64,
+) -> Option<Duration> {
+    if rate_limited(status, headers, body) {
+        if let Some(secs) = header(headers, "retry-after").and_then(|value| value.parse::<u64>().ok()) {
+            return Some(Duration::from_secs(secs));
+        }
+        if header(headers, "x-ratelimit-remaining") == Some("0") {
+            if let Some(reset) = header(headers, "x-ratelimit-reset").and_then(|value| value.parse::<u64>().ok()) {
+                // One more second as the reset time is rounded down
+                return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+            }
+        }
+        Some(exponential(attempt, jitter))
+    } else if status.is_server_error() && idempotent(method) {
+        Some(exponential(attempt, jitter))
+    } else {
+        None
+    }
+}
+
+/// Delay of the given retry, between half and all of the doubled base
+/// delay so that concurrent requests don't retry in lockstep
+fn exponential(attempt: u32, jitter: f64) -> Duration {
+    let millis = BASE_DELAY_MILLIS.saturating_mul(1 << attempt.min(16)) as f64;
+    Duration::from_millis((millis * (0.5 + jitter / 2.0)) as u64)
+}
+
+/// Delay of the given retry after a dropped connection
+pub fn exponential_delay(attempt: u32) -> Duration {
+    exponential(attempt, jitter())
+}
+
+/// Random fraction between 0 and 1, good enough to spread retries
+fn jitter() -> f64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+    f64::from(nanos % 1000) / 1000.0
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn waits_as_rate_limit_headers_tell() {
+        let get = Method::GET;
+        let retry_after = headers(&[("retry-after", "30")]);
+        assert_eq!(delay_at(&get, StatusCode::FORBIDDEN, &retry_after, b"", 0, 1000, 0.0), Some(Duration::from_secs(30)));
+        assert_eq!(delay_at(&get, StatusCode::TOO_MANY_REQUESTS, &retry_after, b"", 0, 1000, 0.0), Some(Duration::from_secs(30)));
+
+        let exhausted = headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "1012")]);
+        assert_eq!(delay_at(&get, StatusCode::FORBIDDEN, &exhausted, b"", 0, 1000, 0.0), Some(Duration::from_secs(13)));
+
+        let secondary = br#"{"message":"You have exceeded a secondary rate limit."}"#;
+        assert_eq!(
+            delay_at(&Method::POST, StatusCode::FORBIDDEN, &HeaderMap::new(), secondary, 2, 1000, 0.0),
+            Some(Duration::from_millis(1000)),
+        );
+    }
+
+    #[test]
+    fn retries_server_errors_of_idempotent_requests_only() {
+        let none = HeaderMap::new();
+        assert_eq!(delay_at(&Method::GET, StatusCode::BAD_GATEWAY, &none, b"", 1, 0, 1.0), Some(Duration::from_millis(1000)));
+        assert_eq!(delay_at(&Method::POST, StatusCode::BAD_GATEWAY, &none, b"", 1, 0, 1.0), None);
+        assert_eq!(delay_at(&Method::GET, StatusCode::FORBIDDEN, &none, br#"{"message":"Resource not accessible"}"#, 0, 0, 1.0), None);
+        assert_eq!(delay_at(&Method::GET, StatusCode::NOT_FOUND, &none, b"", 0, 0, 1.0), None);
+    }
+}
@@ -0,0 +1,2518 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::{ArgEnum, Args};
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use hyper::body::Bytes;
+use hyper::StatusCode;
+use semver::Version;
+use serde::Deserialize;
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::backoff;
+use crate::config::{Defaults, PolicyConfig, PolicyMode, Severity};
+use crate::discover;
+use crate::ecosystem::{Ecosystem, EcosystemArg, ManifestSummary};
+use crate::errors::{self, Error, Failure, Stage};
+use crate::github::{self, Access, GitHub};
+use crate::gitlab;
+use crate::history::{self, TimeArgs};
+use crate::inventory::Inventory;
+use crate::jira;
+use crate::local;
+use crate::lockfile::{self, DependencyKind};
+use crate::notify::{self, AlertOn, NotifyFormat, NotifyOn};
+use crate::picker;
+use crate::policy::{self, NamedPolicy};
+use crate::progress::Progress;
+use crate::provider::Provider;
+use crate::publish;
+use crate::quarantine::Quarantine;
+use crate::range::Range;
+use crate::registry::{Packument, Registry, REGISTRY_URL};
+use crate::report::{self, CsvStyle, Migration, MigrationStatus, Record, Report};
+use crate::sign;
+use crate::tasks::{TaskError, Tasks};
+use crate::telemetry::Tracer;
+use crate::tracking::Tracker;
+use crate::version::{self, Delta, VersionStatus};
+use crate::workspaces;
+use crate::Result;
+
+#[derive(Deserialize, Debug)]
+struct Waiver {
+    repo: String,
+    reason: String,
+    expires: Option<String>,
+}
+
+impl Waiver {
+    /// Waivers on a repository apply to all its refs and directories, like
+    /// annotations
+    fn applies_to(&self, repo: &str) -> bool {
+        self.repo == repo || self.repo == github::split_entry(repo).0
+    }
+}
+
+const PARALLEL_REQUESTS: usize = 100;
+
+/// Repositories fetched at once with --low-memory
+const LOW_MEMORY_PARALLEL_REQUESTS: usize = 8;
+
+/// Length of the bar of the most common version in the --summary
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// One of several partitions of the repositories, written as `index/count`
+/// with a 1-based index
+#[derive(Debug, Clone, Copy)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Shard, String> {
+        let invalid = || format!("invalid shard {:?}, expected index/count such as 2/5", s);
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index: u64 = index.parse().map_err(|_| invalid())?;
+        let count: u64 = count.parse().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl Shard {
+    /// Whether a repository belongs to this shard. Repositories are assigned
+    /// by a stable hash of their name, so every job of a sharded run sees a
+    /// disjoint part of the same list regardless of its order.
+    fn contains(&self, repo: &str) -> bool {
+        // FNV-1a, stable across platforms and Rust versions
+        let hash = repo.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        hash % self.count == self.index - 1
+    }
+}
+
+#[derive(ArgEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// A row per repository as results arrive
+    #[clap(alias = "table")]
+    Text,
+    /// A JSON object per repository and package as results arrive, one per
+    /// line, with its version, lockfile and error, following the schema
+    /// printed by --schema
+    Json,
+    /// A CSV row per repository and package as results arrive, after a
+    /// header row
+    Csv,
+    /// A compact 72-column summary for e-mails, with regressions since the
+    /// last --history snapshot
+    EmailText,
+}
+
+/// Order of the rows with --sort
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Lowest version first, repositories without one last
+    Version,
+    /// By repository name
+    Repo,
+}
+
+/// Row held back until every result is in, to be printed in order
+struct HeldRow {
+    records: Vec<Record>,
+    /// Versions column of the row
+    versions: String,
+    /// Repository column of the row
+    name: String,
+    /// Row printed with --format text, none for rows only printed in the
+    /// structured formats
+    line: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CheckArgs {
+    /// Path of the file containing json list of repositories, or - to read
+    /// newline-delimited repositories from stdin, or a .txt list with one
+    /// per line and # comments as kept by discover --update, or a .csv
+    /// inventory with a
+    /// repo column whose other columns are carried through to the output,
+    /// or the URL of a tracking issue whose task list or of a project whose
+    /// items name the repositories. Repositories on a GitHub Enterprise
+    /// Server are prefixed with its host, e.g. ghe.example.com/org/repo,
+    /// and GitLab projects with gitlab:, e.g. gitlab:group/project, read
+    /// with GITLAB_TOKEN from GITLAB_URL or gitlab.com
+    #[clap(short, long)]
+    repos: Option<String>,
+
+    /// Check every repository of this organization instead of --repos,
+    /// prefixed with its host when it's on another GitHub instance, e.g.
+    /// ghe.example.com/org
+    #[clap(long, conflicts_with = "repos")]
+    org: Option<String>,
+
+    /// Check the clones under this directory instead of --repos, without
+    /// the GitHub API: a row per directory holding a lockfile, skipping
+    /// those ignored by the .gitignore files of the clones
+    #[clap(long, conflicts_with_all = &["repos", "org"])]
+    local: Option<String>,
+
+    #[clap(flatten)]
+    org_filter: discover::Filter,
+
+    /// Directory of the project to check in every repository, e.g.
+    /// packages/api, instead of the root; a repository given as
+    /// org/repo:packages/api checks that directory instead
+    #[clap(long)]
+    path: Option<String>,
+
+    /// Branch, tag or commit to check every repository at instead of its
+    /// default branch; a repository given as org/repo@release/1.2 is
+    /// checked at that ref instead
+    #[clap(long = "ref")]
+    git_ref: Option<String>,
+
+    /// Package name to check versions on; can be repeated or comma-separated
+    /// to check several packages with one fetch of each manifest. With npm,
+    /// a glob such as `@internal/*` checks every matching package and ends
+    /// with an adoption report per package
+    #[clap(short, long, use_value_delimiter = true)]
+    package: Vec<String>,
+
+    /// Ecosystem of the package, or auto to pick each repository's
+    /// ecosystem from its languages
+    #[clap(long, arg_enum, default_value = "npm")]
+    ecosystem: EcosystemArg,
+
+    /// Path of the file containing json list of waived repositories
+    #[clap(short, long)]
+    waivers: Option<String>,
+
+    /// Path of a json file of notes on repositories, e.g. {"org/api":
+    /// "blocked on Node 18 upgrade"}, carried into every row and report
+    #[clap(long)]
+    annotations: Option<String>,
+
+    /// Path of a json file to write every failure to, for later retries
+    #[clap(short, long)]
+    errors_file: Option<String>,
+
+    /// Path of a json file to write the results to
+    #[clap(long)]
+    report: Option<String>,
+
+    /// Path of a minisign secret key to sign the report with, writing a
+    /// detached signature next to it as <report>.minisig; an encrypted
+    /// key's password is read from CHECK_VERSIONS_SIGNING_PASSWORD
+    #[clap(long, requires = "report")]
+    sign_report: Option<String>,
+
+    /// Only check the given part of the repositories, e.g. 2/5 for the
+    /// second of five parallel jobs
+    #[clap(long)]
+    shard: Option<Shard>,
+
+    /// Pick the repositories to check from the list on the terminal,
+    /// fuzzy-searching them
+    #[clap(long)]
+    interactive: bool,
+
+    /// Only check the first this many repositories, e.g. to smoke-test a
+    /// configuration
+    #[clap(long)]
+    limit: Option<usize>,
+
+    /// Only check this many repositories picked at random, e.g. to
+    /// estimate the state of the fleet quickly
+    #[clap(long)]
+    sample: Option<usize>,
+
+    /// Abort the checks of repositories taking longer than this many
+    /// seconds, reporting them as failed; the timeout column of a .csv
+    /// inventory sets it per repository
+    #[clap(long)]
+    task_timeout: Option<u64>,
+
+    /// File counting the runs in a row each repository timed out in, to
+    /// skip for a week those timing out in --quarantine-after runs in a
+    /// row
+    #[clap(long)]
+    quarantine: Option<String>,
+
+    /// Runs in a row a repository may time out in before it's quarantined
+    #[clap(long, default_value = "3")]
+    quarantine_after: u32,
+
+    /// Leave out repositories that demonstrably aren't projects of the
+    /// ecosystem (e.g. no package.json anywhere) instead of reporting them
+    /// as missing the package
+    #[clap(long)]
+    skip_irrelevant: bool,
+
+    /// Show the number of packages in each lockfile, split into prod and
+    /// dev dependencies (npm only)
+    #[clap(long)]
+    stats: bool,
+
+    /// Show the chains of dependencies pulling the package in, from the
+    /// root or a workspace to each installed copy, like `npm ls` (npm
+    /// lockfiles v2 and later)
+    #[clap(long)]
+    why: bool,
+
+    /// Only report the repositories declaring the package in their
+    /// dependencies or optionalDependencies (npm only)
+    #[clap(long, conflicts_with = "only-dev")]
+    only_direct: bool,
+
+    /// Only report the repositories declaring the package in their
+    /// devDependencies (npm only)
+    #[clap(long)]
+    only_dev: bool,
+
+    /// Show how many packages each lockfile installs at more than one
+    /// version (npm only)
+    #[clap(long)]
+    duplicates: bool,
+
+    /// Compare the locked version with the newest published version
+    /// satisfying the range declared in package.json, to spot repositories
+    /// that only need an `npm update` (npm only)
+    #[clap(long)]
+    latest_in_range: bool,
+
+    /// Flag repositories whose package.json was committed more recently
+    /// than their lockfile, hinting at dependencies edited without
+    /// installing (npm only)
+    #[clap(long)]
+    freshness: bool,
+
+    /// Show the latest published version next to outdated locked versions
+    /// (npm only)
+    #[clap(long)]
+    outdated: bool,
+
+    /// Annotate each version with the latest published one and how far
+    /// behind it is, e.g. `1.2.3 (latest 2.0.1, 1 major behind)` (npm only)
+    #[clap(long)]
+    compare_latest: bool,
+
+    /// Flag repositories whose version is lower than this one, e.g. the
+    /// first release with a security fix
+    #[clap(long)]
+    min_version: Option<Version>,
+
+    /// Only print the repositories whose version is lower than
+    /// --min-version
+    #[clap(long, requires = "min-version")]
+    only_outdated: bool,
+
+    /// Fail the run, listing the offending repositories, when any of them
+    /// is on a version lower than this one, e.g. to gate a pipeline on a
+    /// security fix
+    #[clap(long)]
+    fail_below: Option<Version>,
+
+    /// Fail the run, listing the offending repositories, when any of them
+    /// doesn't install the package or has no manifest
+    #[clap(long)]
+    fail_on_missing: bool,
+
+    /// Show the unpacked size of the locked version, and with --outdated
+    /// how much it grows or shrinks in the latest version (npm only)
+    #[clap(long)]
+    show_size: bool,
+
+    /// Show when the locked version was published, or how long ago with
+    /// --relative-time (npm only)
+    #[clap(long)]
+    show_age: bool,
+
+    /// After the results, show the maintainers and funding of the package
+    /// from the registry along with how many repositories use it (npm only)
+    #[clap(long)]
+    maintainers: bool,
+
+    /// Host of a registry packages may be resolved from (can be repeated);
+    /// flags lockfiles resolving the package from any other host (npm only)
+    #[clap(long)]
+    approved_registry: Vec<String>,
+
+    /// Check the resolved URLs of every package of the lockfiles against
+    /// --approved-registry, not just the checked one
+    #[clap(long, requires = "approved-registry")]
+    all_deps: bool,
+
+    /// Create a check run on the inspected commit of every repository,
+    /// summarizing its result; needs a GitHub App installation token
+    #[clap(long)]
+    publish_check_run: bool,
+
+    /// Set a commit status with this context, e.g. fleet/deps, on the
+    /// inspected commit of every repository, failing the non-compliant
+    /// ones so branch protection can require it
+    #[clap(long)]
+    publish_status: Option<String>,
+
+    /// Repository, e.g. org/infra-reports, in which to create or update an
+    /// issue holding the Markdown report of the run
+    #[clap(long)]
+    digest_issue: Option<String>,
+
+    /// Upload the report as Markdown and JSON to a secret gist and print
+    /// its URL
+    #[clap(long)]
+    publish_gist: bool,
+
+    /// Path of a TOML policy file to evaluate besides the [policy] section
+    /// of the config file, e.g. a security baseline and a team policy, each
+    /// reported on its own against the same results (can be repeated)
+    #[clap(long)]
+    policy: Vec<String>,
+
+    /// Whether the failing rules of the policies fail the run, or only
+    /// warn, e.g. for a grace period after they're announced; rules with a
+    /// mode of their own keep it
+    #[clap(long, arg_enum, default_value = "enforce")]
+    policy_mode: PolicyMode,
+
+    /// Output format, text unless set in the config file
+    #[clap(long, visible_alias = "output", arg_enum)]
+    format: Option<Format>,
+
+    /// Separator of the fields with --format csv, e.g. ';' for
+    /// spreadsheets in locales using the decimal comma
+    #[clap(long)]
+    csv_delimiter: Option<char>,
+
+    /// Print the rows in this order once every repository is checked,
+    /// instead of as results arrive
+    #[clap(long, arg_enum)]
+    sort: Option<SortBy>,
+
+    /// Print each distinct version once with the repositories on it
+    /// underneath, lowest first, once every repository is checked; the
+    /// structured formats are sorted by version instead
+    #[clap(long)]
+    group_by_version: bool,
+
+    /// Print how many repositories were checked, are on each version,
+    /// miss the package or failed, once every repository is checked; on
+    /// stderr with the structured formats
+    #[clap(long)]
+    summary: bool,
+
+    /// Only print the --summary, without a row per repository, e.g. for
+    /// weekly fleet reports
+    #[clap(long)]
+    summary_only: bool,
+
+    /// Don't draw the progress bar on stderr, which is only drawn on a
+    /// terminal
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// With --format csv, write a UTF-8 byte order mark and versions as
+    /// text so that the file opens in Excel without mangling them
+    #[clap(long)]
+    excel: bool,
+
+    /// Repositories fetched at once, 100 by default and 8 with
+    /// --low-memory
+    #[clap(long)]
+    concurrency: Option<usize>,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+
+    /// Webhook URL to post a summary of the run to
+    #[clap(long)]
+    notify: Option<String>,
+
+    /// Payload format of the --notify webhook
+    #[clap(long, arg_enum, default_value = "slack")]
+    notify_format: NotifyFormat,
+
+    /// When to post to the --notify webhook; change compares the results
+    /// with the previous --history snapshot
+    #[clap(long, arg_enum, default_value = "always", requires_if("change", "history"))]
+    notify_on: NotifyOn,
+
+    /// Raise an alert on changes since the previous --history snapshot
+    #[clap(long, arg_enum, requires_all = &["history", "pagerduty-key"])]
+    alert_on: Option<AlertOn>,
+
+    /// PagerDuty Events API v2 routing key to send --alert-on events to
+    #[clap(long)]
+    pagerduty_key: Option<String>,
+
+    /// Open or update a Jira issue per repository with problems, as set up
+    /// in the [jira] section of the config file
+    #[clap(long)]
+    jira: bool,
+
+    /// Directory to store a snapshot of the results in after the run, as
+    /// rendered by the site command
+    #[clap(long)]
+    history: Option<String>,
+
+    /// Check every manifest found in the repositories, e.g. each
+    /// package-lock.json of a monorepo, reporting a row per workspace named
+    /// after the package.json next to it
+    #[clap(long)]
+    scan: bool,
+
+    /// With --scan, only check the manifests whose path matches this glob,
+    /// e.g. 'services/**' (can be repeated)
+    #[clap(long, requires = "scan")]
+    include_path: Vec<String>,
+
+    /// With --scan, skip the manifests whose path matches this glob, e.g.
+    /// '**/examples/**' (can be repeated)
+    #[clap(long, requires = "scan")]
+    exclude_path: Vec<String>,
+
+    /// Check the package per sub-project of workspace repositories, found
+    /// from the workspaces of package.json (npm, Yarn, Turborepo),
+    /// lerna.json and nx.json or workspace.json (npm only)
+    #[clap(long, conflicts_with = "scan")]
+    workspaces: bool,
+
+    /// List which manifest of each ecosystem was found and parsed in every
+    /// repository, to tell the ecosystems a repository doesn't use from
+    /// the manifests that couldn't be read
+    #[clap(long)]
+    ecosystem_summary: bool,
+
+    /// Show the template repository each repository was generated from
+    #[clap(long)]
+    template: bool,
+
+    /// URL of the npm registry to look up published versions in
+    #[clap(long, default_value = REGISTRY_URL)]
+    registry: String,
+
+    /// Directory to cache registry metadata in between runs
+    #[clap(long)]
+    registry_cache: Option<String>,
+
+    /// How long cached registry metadata stays fresh, in seconds
+    #[clap(long, default_value = "3600")]
+    registry_cache_ttl: u64,
+
+    /// Most requests to send to the registry in a run; lookups beyond it
+    /// fail unless cached
+    #[clap(long)]
+    registry_budget: Option<usize>,
+
+    /// Stream manifests to temporary files and parse them incrementally,
+    /// keeping only the checked packages of each package-lock.json, and
+    /// fetch fewer repositories at once, for huge lockfiles on small
+    /// runners. Other lockfiles, such as yarn.lock and pnpm-lock.yaml, are
+    /// still read whole.
+    #[clap(long, conflicts_with_all = &["stats", "duplicates", "all-deps", "why"])]
+    low_memory: bool,
+}
+
+impl CheckArgs {
+    /// Fills in the options not given on the command line from the
+    /// [defaults] section of the config file
+    pub fn apply_defaults(&mut self, defaults: &Defaults) {
+        if self.repos.is_none() && self.org.is_none() {
+            self.repos = defaults.repos.clone();
+        }
+        if self.package.is_empty() {
+            self.package = defaults.package.clone();
+        }
+        self.format = self.format.or(defaults.format);
+        self.concurrency = self.concurrency.or(defaults.concurrency);
+        if self.annotations.is_none() {
+            self.annotations = defaults.annotations.clone();
+        }
+    }
+
+    fn format(&self) -> Format {
+        self.format.unwrap_or(Format::Text)
+    }
+
+    fn csv_style(&self) -> CsvStyle {
+        CsvStyle { delimiter: self.csv_delimiter.unwrap_or(','), excel: self.excel }
+    }
+
+    /// Path of the report the results are written to, with --report
+    pub(crate) fn report_path(&self) -> Option<&str> {
+        self.report.as_deref()
+    }
+
+    /// Kind of dependency the rows are limited to, with --only-direct or
+    /// --only-dev
+    fn only_kind(&self) -> Option<DependencyKind> {
+        if self.only_direct {
+            Some(DependencyKind::Direct)
+        } else if self.only_dev {
+            Some(DependencyKind::Dev)
+        } else {
+            None
+        }
+    }
+}
+
+/// Re-run the repositories that failed in a previous run, merging their
+/// results into its report
+#[derive(Args, Debug, Clone)]
+pub struct RetryArgs {
+    /// Path of the errors file written by the previous run; replaces --repos
+    #[clap(long)]
+    from: String,
+
+    #[clap(flatten)]
+    pub check: CheckArgs,
+}
+
+pub async fn check(cli: CheckArgs, github: GitHub, tracer: &Tracer) -> Result<()> {
+    let filtered = !cli.org_filter.topic.is_empty() || cli.org_filter.language.is_some() || cli.org_filter.include_archived;
+    if filtered && cli.org.is_none() {
+        return Err("--topic, --language and --include-archived need --org".into());
+    }
+
+    let mut inventory = None;
+    let repos = match (&cli.org, cli.repos.as_deref()) {
+        _ if cli.local.is_some() => {
+            let dir = cli.local.as_deref().unwrap_or_default();
+            let entries = local::entries(dir, cli.ecosystem.fixed())
+                .map_err(|e| format!("Unable to find the lockfiles under {}: {}", dir, e))?;
+            stream::iter(entries).boxed()
+        }
+        (Some(org), _) => {
+            let (host, org) = github::parse_repo(org);
+            let repos = discover::org_repos(&github, host, org, &cli.org_filter).await
+                .map_err(|e| format!("Unable to list the repositories of {}: {}", org, e))?;
+            stream::iter(repos).boxed()
+        }
+        (None, Some(repos_path)) => read_repos(repos_path, &github, &mut inventory).await?,
+        (None, None) => return Err("--repos or --org is required, or repos in the [defaults] of the config file".into()),
+    };
+
+    let repos = if cli.interactive {
+        let listed: Vec<String> = repos.collect().await;
+        let picked = tokio::task::spawn_blocking(move || picker::pick(listed)).await??;
+        stream::iter(picked).boxed()
+    } else {
+        repos
+    };
+
+    let policies = policies(&github, &cli)?;
+    let records = run(repos, &cli, github, inventory.as_ref(), &policies, tracer).await?;
+    let enforced = enforce_policies(&policies, cli.policy_mode, &records);
+    let asserted = assert_versions(cli.fail_below.as_ref(), cli.fail_on_missing, &records);
+
+    if let Some(report) = &cli.report {
+        Report::new(records).write(report)?;
+        if let Some(key) = &cli.sign_report {
+            eprintln!("Signature: {}", sign::sign_report(report, key)?);
+        }
+    }
+
+    enforced.and(asserted)
+}
+
+/// Reads the repositories given with --repos, keeping the inventory when
+/// they're read from a CSV file
+pub(crate) async fn read_repos(repos_path: &str, github: &GitHub, inventory: &mut Option<Inventory>) -> Result<BoxStream<'static, String>> {
+    let repos = if repos_path == "-" {
+        let lines = BufReader::new(io::stdin()).lines();
+        stream::unfold(lines, |mut lines| async move {
+            match lines.next_line().await {
+                Ok(Some(line)) => Some((line, lines)),
+                Ok(None) => None,
+                Err(e) => {
+                    eprintln!("Error reading repositories from stdin: {}", e);
+                    None
+                }
+            }
+        })
+        .map(|line| line.trim().to_string())
+        .filter(|repo| future::ready(!repo.is_empty()))
+        .boxed()
+    } else if let Some(tracker) = Tracker::parse(repos_path) {
+        let repos = tracker.repos(github).await
+            .map_err(|e| format!("Unable to read repositories from {}: {}", repos_path, e))?;
+        stream::iter(repos).boxed()
+    } else if repos_path.ends_with(".txt") {
+        let data = fs::read_to_string(repos_path)
+            .map_err(|source| Error::Read { path: repos_path.to_string(), source })?;
+        stream::iter(discover::read_lines(&data)).boxed()
+    } else if repos_path.ends_with(".csv") {
+        let read = Inventory::read(repos_path)?;
+        let repos = read.repos();
+        *inventory = Some(read);
+        stream::iter(repos).boxed()
+    } else {
+        let data = fs::read_to_string(repos_path)
+            .map_err(|source| Error::Read { path: repos_path.to_string(), source })?;
+
+        let json: Vec<String> = serde_json::from_str(&data)
+            .map_err(|source| Error::Json { path: repos_path.to_string(), source })?;
+
+        stream::iter(json).boxed()
+    };
+    Ok(repos)
+}
+
+pub async fn retry(args: RetryArgs, github: GitHub, tracer: &Tracer) -> Result<()> {
+    let mut repos: Vec<String> = Vec::new();
+    for failure in errors::read(&args.from)? {
+        if !repos.contains(&failure.repo) {
+            repos.push(failure.repo);
+        }
+    }
+
+    let policies = policies(&github, &args.check)?;
+    let records = recheck(repos, &args.check, github, &policies, tracer).await?;
+    let enforced = enforce_policies(&policies, args.check.policy_mode, &records);
+    let asserted = assert_versions(args.check.fail_below.as_ref(), args.check.fail_on_missing, &records);
+    enforced.and(asserted)
+}
+
+/// Checks repositories again, merging their results into the --report
+/// file when there's one
+pub(crate) async fn recheck(
+    repos: Vec<String>,
+    cli: &CheckArgs,
+    github: GitHub,
+    policies: &[NamedPolicy],
+    tracer: &Tracer,
+) -> Result<Vec<Record>> {
+    let records = run(stream::iter(repos).boxed(), cli, github, None, policies, tracer).await?;
+
+    if let Some(path) = &cli.report {
+        let mut report = if Path::new(path).exists() {
+            Report::read(path)?
+        } else {
+            Report::default()
+        };
+        report.merge(records.clone());
+        report.write(path)?;
+        if let Some(key) = &cli.sign_report {
+            eprintln!("Signature: {}", sign::sign_report(path, key)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Picks a random subset of the given size of the repositories, in their
+/// order, by reservoir sampling with a xorshift generator
+fn sample(repos: Vec<String>, size: usize, seed: u64) -> Vec<String> {
+    let mut state = seed | 1;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut picked: Vec<(usize, String)> = Vec::with_capacity(size);
+    for (i, repo) in repos.into_iter().enumerate() {
+        if i < size {
+            picked.push((i, repo));
+        } else {
+            let slot = (next() % (i as u64 + 1)) as usize;
+            if slot < size {
+                picked[slot] = (i, repo);
+            }
+        }
+    }
+    picked.sort_by_key(|(i, _)| *i);
+    picked.into_iter().map(|(_, repo)| repo).collect()
+}
+
+/// Seed of --sample, different on every run
+fn random_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64) ^ std::process::id() as u64
+}
+
+/// Policies of a run: the [policy] section of the config file, then the
+/// files given with --policy
+pub(crate) fn policies(github: &GitHub, cli: &CheckArgs) -> Result<Vec<NamedPolicy>> {
+    let mut policies: Vec<NamedPolicy> = github.config().policy
+        .iter()
+        .map(|policy| NamedPolicy { name: "config".to_string(), policy: policy.clone() })
+        .collect();
+    for path in &cli.policy {
+        policies.push(policy::read(path)?);
+    }
+    Ok(policies)
+}
+
+/// Fails when a repository has problems of the fail severity of any of the
+/// policies, after reporting per policy how many do, how many warn and how
+/// many will fail once their rule is enforced
+fn enforce_policies(policies: &[NamedPolicy], mode: PolicyMode, records: &[Record]) -> Result<()> {
+    let mut failed = Vec::new();
+    for named in policies {
+        // Messages only name the policy when there are several
+        let prefix = if policies.len() > 1 { format!("{}: ", named.name) } else { String::new() };
+        let failing = enforce_policy(&named.policy, &prefix, mode, records);
+        if failing > 0 {
+            failed.push(format!("{}{} repositories have problems that fail the policy", prefix, failing));
+        } else if policies.len() > 1 {
+            eprintln!("{}passed", prefix);
+        }
+    }
+    if !failed.is_empty() {
+        return Err(failed.join("; ").into());
+    }
+    Ok(())
+}
+
+/// Reports how many repositories warn and will fail once their rule is
+/// enforced under a policy, and returns how many fail it
+fn enforce_policy(policy: &PolicyConfig, prefix: &str, mode: PolicyMode, records: &[Record]) -> usize {
+    let mut severities: BTreeMap<&str, Severity> = BTreeMap::new();
+    let mut deferred = BTreeSet::new();
+    for record in records {
+        let severity = severities.entry(&record.repo).or_insert(Severity::Ignore);
+        *severity = (*severity).max(policy::severity(policy, record, mode));
+        if policy::deferred(policy, record, mode) {
+            deferred.insert(record.repo.as_str());
+        }
+    }
+
+    let count = |wanted| severities.values().filter(|&&severity| severity == wanted).count();
+    let (failing, warning) = (count(Severity::Fail), count(Severity::Warn));
+    if warning > 0 {
+        eprintln!("{}{} repositories have problems that only warn", prefix, warning);
+    }
+    let deferred = deferred.iter().filter(|repo| severities.get(*repo) == Some(&Severity::Warn)).count();
+    if deferred > 0 {
+        eprintln!("{}{} of them will fail the policy once its rules in warn mode are enforced", prefix, deferred);
+    }
+    failing
+}
+
+/// Fails when a repository is below --fail-below or, with
+/// --fail-on-missing, doesn't install the package, after listing them
+fn assert_versions(fail_below: Option<&Version>, fail_on_missing: bool, records: &[Record]) -> Result<()> {
+    let below: Vec<&Record> = match fail_below {
+        Some(minimum) => records.iter().filter(|record| below_minimum(record, minimum)).collect(),
+        None => Vec::new(),
+    };
+    let missing: Vec<&Record> = records.iter().filter(|record| fail_on_missing && is_missing(record)).collect();
+
+    let mut failed = Vec::new();
+    if let (Some(minimum), false) = (fail_below, below.is_empty()) {
+        eprintln!("{} repositories below {}:", below.len(), minimum);
+        for record in &below {
+            eprintln!("  {}: {} {}", record.repo, record.package, record.version.as_deref().unwrap_or_default());
+        }
+        failed.push(format!("{} repositories below {}", below.len(), minimum));
+    }
+    if !missing.is_empty() {
+        eprintln!("{} repositories missing the package:", missing.len());
+        for record in &missing {
+            match &record.error {
+                Some(error) => eprintln!("  {}: {} ({})", record.repo, record.package, error),
+                None => eprintln!("  {}: {}", record.repo, record.package),
+            }
+        }
+        failed.push(format!("{} repositories missing the package", missing.len()));
+    }
+    if !failed.is_empty() {
+        return Err(failed.join("; ").into());
+    }
+    Ok(())
+}
+
+/// Whether a record shows the package isn't installed: neither locked,
+/// declared nor linked, or the repository has no manifest. Repositories
+/// which couldn't be checked for other reasons aren't.
+fn is_missing(record: &Record) -> bool {
+    let unreadable = record.error.is_some() && record.access != Some(Access::Missing);
+    let matched = record.matches.as_ref().is_some_and(|matches| !matches.is_empty());
+    !unreadable && !matched && record.shown_version().is_none() && record.local_path.is_none()
+}
+
+/// Checks the package in every repository of the stream, printing a row
+/// per repository as results arrive
+async fn run(
+    repos: BoxStream<'static, String>,
+    cli: &CheckArgs,
+    github: GitHub,
+    inventory: Option<&Inventory>,
+    policies: &[NamedPolicy],
+    tracer: &Tracer,
+) -> Result<Vec<Record>> {
+    if cli.package.is_empty() {
+        return Err("--package is required, or package in the [defaults] of the config file".into());
+    }
+    let packages = &cli.package;
+    if cli.low_memory && packages.iter().any(|package_name| package_name.contains('*')) {
+        return Err("--low-memory can't check package globs, which need the whole lockfile".into());
+    }
+    // Names the run in titles when several packages are checked
+    let package_names = packages.join(", ");
+    let mut run_span = tracer.span("check", None);
+    run_span.attribute("packages", &package_names);
+
+    let waivers: Vec<Waiver> = match &cli.waivers {
+        Some(waivers_path) => {
+            let data = fs::read_to_string(waivers_path)
+                .map_err(|source| Error::Read { path: waivers_path.to_string(), source })?;
+            serde_json::from_str(&data)
+                .map_err(|source| Error::Json { path: waivers_path.to_string(), source })?
+        }
+        None => Vec::new(),
+    };
+    let annotations: BTreeMap<String, String> = match &cli.annotations {
+        Some(annotations_path) => {
+            let data = fs::read_to_string(annotations_path)
+                .map_err(|source| Error::Read { path: annotations_path.to_string(), source })?;
+            serde_json::from_str(&data)
+                .map_err(|source| Error::Json { path: annotations_path.to_string(), source })?
+        }
+        None => BTreeMap::new(),
+    };
+    // Notes on a repository apply to all its refs and directories
+    let note = |repo: &str| annotations.get(repo).or_else(|| annotations.get(github::split_entry(repo).0)).cloned();
+
+    let packuments: HashMap<&str, Arc<Packument>> = if cli.latest_in_range || cli.outdated || cli.compare_latest || cli.show_size || cli.show_age || cli.maintainers {
+        let registry = Registry::new(
+            github.clone(),
+            &cli.registry,
+            cli.registry_cache.as_deref(),
+            Duration::from_secs(cli.registry_cache_ttl),
+            cli.registry_budget,
+        );
+        future::try_join_all(packages.iter().map(|package_name| {
+            let registry = registry.clone();
+            async move {
+                let packument = registry.packument(package_name).await
+                    .map_err(|e| format!("Unable to fetch {} from {}: {}", package_name, cli.registry, e))?;
+                Ok::<_, String>((package_name.as_str(), packument))
+            }
+        }))
+        .await?
+        .into_iter()
+        .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let mut inspect_options = InspectOptions::from(cli);
+    for named in policies {
+        inspect_options.migrations.extend(named.policy.migrations.clone());
+    }
+    let options = FetchOptions {
+        ecosystem: cli.ecosystem.fixed(),
+        // Lockfile URLs only appear in the report and check runs and
+        // statuses need a commit, so only spend a request on resolving it
+        // when one of them is wanted
+        resolve_sha: cli.report.is_some() || cli.publish_check_run || cli.publish_status.is_some(),
+        check_relevance: cli.skip_irrelevant,
+        check_freshness: cli.freshness,
+        resolve_template: cli.template,
+        scan: cli.scan,
+        include_paths: cli.include_path.clone(),
+        exclude_paths: cli.exclude_path.clone(),
+        workspaces: cli.workspaces,
+        ecosystem_summary: cli.ecosystem_summary,
+        prune_to: cli.low_memory.then(|| kept_packages(packages, &inspect_options.migrations).into()),
+    };
+    let inspect_options = &inspect_options;
+    let colored = !policies.is_empty() && policy::colored();
+    let parallel_requests = cli.concurrency.unwrap_or(if cli.low_memory { LOW_MEMORY_PARALLEL_REQUESTS } else { PARALLEL_REQUESTS }).max(1);
+    let skip_irrelevant = cli.skip_irrelevant;
+    let shard = cli.shard;
+    let publisher = github.clone();
+    let tasks = Tasks::new(cli.task_timeout.map(Duration::from_secs));
+    // Rows are looked up by the entries as checked, with --ref and --path
+    let inventory = inventory.map(|inventory| inventory.with_defaults(cli.git_ref.as_deref(), cli.path.as_deref()));
+    let inventory = inventory.as_ref();
+    let timeouts = inventory.map(Inventory::timeouts).unwrap_or_default();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let quarantine = cli.quarantine.as_deref().map(Quarantine::read).transpose()?;
+    let skipped = quarantine.as_ref().map(|quarantine| quarantine.skipped(now)).unwrap_or_default();
+    tasks.cancel_on_ctrl_c();
+    let parent_span = &run_span;
+    let (path, git_ref) = (cli.path.clone(), cli.git_ref.clone());
+    let repos = repos
+        .map(move |repo| github::with_defaults(repo, git_ref.as_deref(), path.as_deref()))
+        .filter(move |repo| future::ready(shard.is_none_or(|shard| shard.contains(repo))))
+        .filter(move |repo| {
+            let quarantined = skipped.get(repo);
+            if let Some(timeouts) = quarantined {
+                eprintln!("Skipping {}: quarantined after timing out in {} runs in a row", repo, timeouts);
+            }
+            future::ready(quarantined.is_none())
+        });
+    let repos = match cli.sample {
+        Some(size) => stream::iter(sample(repos.collect().await, size, random_seed())).boxed(),
+        None => repos.boxed(),
+    };
+    let repos = match cli.limit {
+        Some(limit) => repos.take(limit).boxed(),
+        None => repos,
+    };
+    // Repositories listed up front are counted for the progress bar, while
+    // those read from stdin are checked as they come
+    let (repos, total) = match repos.size_hint().1 {
+        Some(_) => {
+            let repos: Vec<String> = repos.collect().await;
+            let total = repos.len();
+            (stream::iter(repos).boxed(), Some(total))
+        }
+        None => (repos, None),
+    };
+    // Counted for the --quarantine file
+    let (checked, timed_out) = (RefCell::new(BTreeSet::new()), RefCell::new(BTreeSet::new()));
+    let results = repos
+        .map(move |repo| {
+            let mut span = tracer.span("fetch", Some(parent_span));
+            span.attribute("repo", &repo);
+            let fetched = fetch(github.clone(), repo.clone(), options.clone()).map(move |fetched| {
+                if let Err(failure) = &fetched {
+                    span.fail(&failure.message);
+                }
+                fetched
+            });
+            let started = Instant::now();
+            tasks.spawn(backoff::counted(fetched), timeouts.get(&repo).copied()).map(move |fetched| (repo, fetched, started.elapsed().as_millis() as u64))
+        })
+        .buffered(parallel_requests)
+        .flat_map(|(repo, fetched, duration_ms)| {
+            // Repositories cancelled by Ctrl-C neither timed out nor not
+            if !matches!(fetched, Err(TaskError::Cancelled)) {
+                checked.borrow_mut().insert(repo.clone());
+            }
+            if matches!(fetched, Err(TaskError::TimedOut(_))) {
+                timed_out.borrow_mut().insert(repo.clone());
+            }
+            let (fetched, retries) = match fetched {
+                Ok((Err(mut failure), retries)) if retries > 0 => {
+                    failure.message = format!("{} (after {} retries)", failure.message, retries);
+                    (Err(failure), retries)
+                }
+                Ok((fetched, retries)) => (fetched, retries),
+                Err(e) => (Err(Failure::new(&repo, Stage::Task, None, e.to_string())), 0),
+            };
+            // Every manifest is fetched once and inspected for each package
+            let found = match fetched {
+                Ok(fetched) => fetched
+                    .into_iter()
+                    .filter(|fetched| {
+                        if fetched.irrelevant && skip_irrelevant {
+                            eprintln!("Skipping {}: not a project of the ecosystem", repo);
+                            return false;
+                        }
+                        if fetched.irrelevant && fetched.ecosystem.is_none() {
+                            eprintln!("No supported ecosystem detected in {}", repo);
+                        }
+                        if fetched.truncated_tree {
+                            eprintln!("Tree of {} is too large to list entirely, some manifests may be missed", repo);
+                        }
+                        true
+                    })
+                    .map(|fetched| {
+                        let mut span = tracer.span("parse", Some(parent_span));
+                        span.attribute("repo", &repo);
+                        let records = packages
+                            .iter()
+                            .map(|package_name| {
+                                let packument = packuments.get(package_name.as_str()).map(Arc::as_ref);
+                                let mut record = inspect(&repo, package_name, fetched.clone(), inspect_options, packument)?;
+                                record.status = Some(fetched.status);
+                                record.duration_ms = Some(duration_ms);
+                                record.retries = retries;
+                                Ok(record)
+                            })
+                            .collect::<std::result::Result<Vec<_>, _>>()
+                            .map_err(|(status, message)| Failure::new(&repo, Stage::Parse, Some(status), message));
+                        if let Err(failure) = &records {
+                            span.fail(&failure.message);
+                        }
+                        (repo.clone(), records)
+                    })
+                    .collect(),
+                Err(failure) => vec![(repo, Err(failure))],
+            };
+            let found = found.into_iter().map(move |(repo, records)| (repo, records, duration_ms, retries));
+            stream::iter(found)
+        });
+
+    let rows = !cli.summary_only;
+    if cli.format() == Format::Text && packages.len() > 1 && rows {
+        println!("{}\t: repository", packages.join("\t"));
+    }
+    if cli.format() == Format::Csv && rows {
+        println!("{}", cli.csv_style().header());
+    }
+
+    let mut failures = Vec::new();
+    let mut still_failing = Vec::new();
+    let mut records = Vec::new();
+    // Rows are printed as results arrive unless they're to be ordered
+    let hold = cli.sort.is_some() || cli.group_by_version;
+    let mut held = Vec::new();
+    let mut handle = |repo: String, found: std::result::Result<Vec<Record>, Failure>, duration_ms: u64, retries: u32| {
+        let mut found = match found {
+            Ok(found) => found,
+            Err(failure) => {
+                let mut found: Vec<Record> = packages
+                    .iter()
+                    .map(|package_name| {
+                        let mut record = Record::new(&repo, package_name);
+                        record.error = Some(failure.message.clone());
+                        record.status = failure.status;
+                        record.access = failure.access;
+                        record.ecosystems = failure.ecosystems.clone();
+                        record.duration_ms = Some(duration_ms);
+                        record.retries = retries;
+                        record
+                    })
+                    .collect();
+                let stage = failure.stage;
+                match stage {
+                    Stage::Parse => eprintln!("{}", failure.message),
+                    Stage::Task => eprintln!("{}: {}", repo, failure.message),
+                    Stage::Fetch => match (failure.status, &failure.uri) {
+                        (Some(status), Some(uri)) => {
+                            let line = match failure.access {
+                                Some(access) => format!("{}: {:?} ({})", status, uri, access.describe()),
+                                None => format!("{}: {:?}", status, uri),
+                            };
+                            // Missing manifests are listed along with the rows
+                            if failure.access == Some(Access::Missing) && cli.format() == Format::Text && rows {
+                                println!("{}", line);
+                            } else {
+                                eprintln!("{}", line);
+                            }
+                        }
+                        _ => eprintln!("{}: {}", repo, failure.message),
+                    },
+                }
+                if retries > 0 {
+                    still_failing.push(repo.clone());
+                }
+                failures.push(failure);
+                if stage == Stage::Task {
+                    for record in &mut found {
+                        record.inventory = inventory.and_then(|inventory| inventory.metadata(&repo));
+                        record.note = note(&repo);
+                    }
+                    if hold && !cli.only_outdated && rows {
+                        held.push(HeldRow { records: found.clone(), versions: String::new(), name: repo.clone(), line: None });
+                    } else if !cli.only_outdated && rows {
+                        print_structured(cli.format(), cli.csv_style(), &found);
+                    }
+                    records.extend(found);
+                    return;
+                }
+                found
+            }
+        };
+
+        for record in &mut found {
+            record.inventory = inventory.and_then(|inventory| inventory.metadata(&repo));
+            record.note = note(&repo);
+        }
+        // Repositories which couldn't be checked are still reported
+        if let Some(wanted) = cli.only_kind() {
+            found.retain(|record| record.error.is_some() || record.dependency_kind == Some(wanted));
+            if found.is_empty() {
+                return;
+            }
+        }
+
+        // The records of a manifest only differ by package
+        let manifest = &found[0];
+        let mut name = github::parse_repo(&repo).1.rsplit('/').next().unwrap_or_default().to_string();
+        if let Some(git_ref) = github::repo_ref(&repo) {
+            name = format!("{}@{}", name, git_ref);
+        }
+        if let Some(path) = manifest.path.as_deref().filter(|_| cli.scan || github::repo_dir(&repo).is_some()) {
+            name = format!("{}:{}", name, path);
+        }
+        if let Some(dir) = &manifest.workspace_dir {
+            name = format!("{}/{}", name, dir);
+        }
+        if let Some(workspace) = &manifest.workspace {
+            name = format!("{} ({})", name, workspace);
+        }
+        let versions: Vec<String> = found
+            .iter()
+            .map(|record| match (record.shown_version(), &record.local_path) {
+                (Some(version), _) => match (&record.version, record.latest.as_deref().filter(|_| cli.compare_latest)) {
+                    (Some(locked), Some(latest)) => compare_latest(locked, latest),
+                    _ => version,
+                },
+                (None, Some(_)) => "local".to_string(),
+                (None, None) => "-------".to_string(),
+            })
+            .collect();
+        let mut columns = vec![format!("{}\t: {}", versions.join("\t"), name)];
+        if let Some(values) = inventory.and_then(|inventory| inventory.values(&repo)) {
+            columns.extend(values.iter().cloned());
+        }
+        match manifest.template.as_deref() {
+            Some("") => columns.push("no template".to_string()),
+            Some(template) => columns.push(format!("template {}", template)),
+            None => {}
+        }
+        if let Some(stats) = &manifest.stats {
+            columns.push(format!("{} packages ({} prod, {} dev)", stats.total, stats.prod, stats.dev));
+        }
+        if let Some(duplicates) = &manifest.duplicates {
+            columns.push(format!("{} duplicated", duplicates.len()));
+        }
+        for record in &found {
+            let package_columns = package_columns(record, &cli.time, now, cli.compare_latest, cli.min_version.as_ref());
+            if found.len() > 1 {
+                columns.extend(package_columns.into_iter().map(|column| format!("{}: {}", record.package, column)));
+            } else {
+                columns.extend(package_columns);
+            }
+        }
+        if let Some(ecosystems) = &manifest.ecosystems {
+            let summary: Vec<String> = ecosystems.iter().map(ManifestSummary::describe).collect();
+            columns.push(format!("ECOSYSTEMS: {}", summary.join(", ")));
+        }
+        if manifest.stale_lockfile == Some(true) {
+            columns.push("STALE LOCKFILE: package.json committed after it".to_string());
+        }
+        if let Some(waiver) = waivers.iter().find(|waiver| waiver.applies_to(&repo)) {
+            let expires = waiver.expires.as_deref().unwrap_or("never");
+            columns.push(format!("WAIVED: {} (expires {})", waiver.reason, expires));
+        }
+        if let Some(note) = &manifest.note {
+            columns.push(format!("NOTE: {}", note));
+        }
+        let outdated = cli.min_version.as_ref().is_some_and(|minimum| {
+            found.iter().any(|record| below_minimum(record, minimum))
+        });
+        if rows && (outdated || !cli.only_outdated) {
+            let line = (cli.format() == Format::Text).then(|| {
+                let row = columns.join("\t");
+                if colored {
+                    // Rows take the most serious severity of any policy
+                    let severity = policies
+                        .iter()
+                        .flat_map(|named| found.iter().map(|record| policy::severity(&named.policy, record, cli.policy_mode)))
+                        .max()
+                        .unwrap_or(Severity::Ignore);
+                    policy::paint(&row, severity)
+                } else {
+                    row
+                }
+            });
+            if hold {
+                held.push(HeldRow { records: found.clone(), versions: versions.join("\t"), name, line });
+            } else {
+                if let Some(line) = line {
+                    println!("{}", line);
+                }
+                print_structured(cli.format(), cli.csv_style(), &found);
+            }
+        }
+
+        records.extend(found);
+    };
+    let mut progress = Progress::new(cli.quiet, total);
+    results
+        .for_each(|(repo, found, duration_ms, retries)| {
+            progress.clear();
+            progress.advance(&repo, found.is_err());
+            handle(repo, found, duration_ms, retries);
+            progress.draw(publisher.rate_limit_remaining());
+            future::ready(())
+        })
+        .await;
+    progress.clear();
+    if hold {
+        print_held(held, cli);
+    }
+    if let (Some(path), Some(mut quarantine)) = (&cli.quarantine, quarantine) {
+        let quarantined = quarantine.update(&checked.into_inner(), &timed_out.into_inner(), cli.quarantine_after, now);
+        if !quarantined.is_empty() {
+            eprintln!("Quarantined {} repositories which timed out in {} runs in a row: {}", quarantined.len(), cli.quarantine_after, quarantined.join(", "));
+        }
+        quarantine.write(path)?;
+    }
+    run_span.attribute("records", records.len());
+    run_span.attribute("failures", failures.len());
+    if !still_failing.is_empty() {
+        eprintln!("{} repositories still failed after retries: {}", still_failing.len(), still_failing.join(", "));
+    }
+    let _report_span = tracer.span("report", Some(&run_span));
+
+    if let Some(errors_file) = &cli.errors_file {
+        errors::write(errors_file, &failures)?;
+    }
+
+    if packages.iter().any(|package_name| package_name.contains('*')) {
+        print_adoption(&records);
+    }
+    if cli.summary || cli.summary_only {
+        let summary = summarize(&records);
+        if cli.format() == Format::Text {
+            if rows {
+                println!();
+            }
+            summary.iter().for_each(|line| println!("{}", line));
+        } else {
+            summary.iter().for_each(|line| eprintln!("{}", line));
+        }
+    }
+
+    let previous = match &cli.history {
+        Some(dir) if Path::new(dir).exists() => history::load(dir)?.pop(),
+        _ => None,
+    };
+
+    if cli.format() == Format::EmailText {
+        print!("{}", Report::new(records.clone()).to_email_text(&package_names, previous.as_ref(), &cli.time));
+    }
+
+    if let Some(dir) = &cli.history {
+        history::save(dir, &records)?;
+    }
+
+    if cli.publish_check_run {
+        publish::check_runs(&publisher, &records, packages.len() > 1).await?;
+    }
+    if let Some(context) = &cli.publish_status {
+        publish::statuses(&publisher, &records, context, packages.len() > 1).await?;
+    }
+    if let Some(repo) = &cli.digest_issue {
+        let report = Report::new(records.clone());
+        let url = publish::digest_issue(&publisher, repo, &package_names, &report).await
+            .map_err(|e| format!("Unable to publish the digest issue in {}: {}", repo, e))?;
+        eprintln!("Digest issue: {}", url);
+    }
+    if let (Some(AlertOn::Regression), Some(key), Some(previous)) = (cli.alert_on, &cli.pagerduty_key, &previous) {
+        let report = Report::new(records.clone());
+        let regressions = report.regressions(previous);
+        if !regressions.is_empty() {
+            eprintln!("Alerting on {} regressions", regressions.len());
+            notify::pagerduty(&publisher, key, &regressions).await
+                .map_err(|e| format!("Unable to send PagerDuty events: {}", e))?;
+        }
+    }
+    if cli.jira {
+        let config = publisher.config().jira.as_ref().ok_or("--jira needs a [jira] section in the config file")?;
+        jira::sync(&publisher, config, &records).await
+            .map_err(|e| format!("Unable to sync Jira issues: {}", e))?;
+    }
+    if let Some(url) = &cli.notify {
+        let report = Report::new(records.clone());
+        let unchanged = cli.notify_on == NotifyOn::Change
+            && previous.as_ref().is_some_and(|previous| !report.differs_from(previous));
+        if unchanged {
+            eprintln!("Results unchanged since the previous run, not notifying");
+        } else {
+            notify::notify(&publisher, url, cli.notify_format, &package_names, &report).await
+                .map_err(|e| format!("Unable to notify {}: {}", url, e))?;
+        }
+    }
+    if cli.publish_gist {
+        let report = Report::new(records.clone());
+        let url = publish::gist(&publisher, &package_names, &report).await
+            .map_err(|e| format!("Unable to publish the gist: {}", e))?;
+        println!("{}", url);
+    }
+
+    for package_name in packages.iter().filter(|_| cli.maintainers) {
+        let packument = match packuments.get(package_name.as_str()) {
+            Some(packument) => packument,
+            None => continue,
+        };
+        let users = records
+            .iter()
+            .filter(|record| &record.package == package_name && record.version.is_some())
+            .count();
+        let checked = records.iter().filter(|record| &record.package == package_name).count();
+        println!();
+        println!("{}: used by {} of {} repositories", package_name, users, checked);
+
+        let maintainers: Vec<String> = packument.maintainers
+            .iter()
+            .map(|maintainer| match &maintainer.email {
+                Some(email) => format!("{} <{}>", maintainer.name, email),
+                None => maintainer.name.clone(),
+            })
+            .collect();
+        println!("maintainers: {}", if maintainers.is_empty() { "none".to_string() } else { maintainers.join(", ") });
+
+        let funding = packument.funding();
+        println!("funding: {}", if funding.is_empty() { "none".to_string() } else { funding.join(", ") });
+    }
+
+    Ok(records)
+}
+
+/// Prints the rows held back until the end in the order of --sort, or
+/// grouped by version with --group-by-version
+fn print_held(mut held: Vec<HeldRow>, cli: &CheckArgs) {
+    sort_held(&mut held, cli.sort, cli.group_by_version);
+    if !cli.group_by_version || cli.format() != Format::Text {
+        for row in &held {
+            if let Some(line) = &row.line {
+                println!("{}", line);
+            }
+            print_structured(cli.format(), cli.csv_style(), &row.records);
+        }
+        return;
+    }
+    let rows: Vec<&HeldRow> = held.iter().filter(|row| row.line.is_some()).collect();
+    for group in rows.chunk_by(|a, b| a.versions == b.versions) {
+        let noun = if group.len() == 1 { "repository" } else { "repositories" };
+        println!("{} ({} {})", group[0].versions, group.len(), noun);
+        for row in group {
+            println!("  {}", row.name);
+        }
+    }
+}
+
+/// Orders held rows by repository, or by version and then repository so
+/// that the rows of a version are next to each other
+fn sort_held(held: &mut [HeldRow], sort: Option<SortBy>, group_by_version: bool) {
+    let by_version = |row: &HeldRow| {
+        // Repositories without a version or with a non-semver one go last
+        let versions: Vec<(bool, Option<Version>)> = row.records
+            .iter()
+            .map(|record| record.version.as_deref().and_then(version::parse))
+            .map(|parsed| (parsed.is_none(), parsed))
+            .collect();
+        (versions, row.versions.clone(), row.name.clone())
+    };
+    match sort {
+        Some(SortBy::Repo) if !group_by_version => held.sort_by(|a, b| (&a.records[0].repo, &a.name).cmp(&(&b.records[0].repo, &b.name))),
+        _ => held.sort_by_cached_key(by_version),
+    }
+}
+
+/// Prints the records of a repository as lines of JSON or CSV, with those
+/// formats
+fn print_structured(format: Format, csv_style: CsvStyle, records: &[Record]) {
+    for record in records {
+        match format {
+            Format::Json => match record.to_json_line() {
+                Ok(line) => println!("{}", line),
+                Err(e) => eprintln!("{}: {}", record.repo, e),
+            },
+            Format::Csv => println!("{}", record.to_csv_row(csv_style)),
+            Format::Text | Format::EmailText => {}
+        }
+    }
+}
+
+/// Annotates a locked version with the latest published one and how far
+/// behind it is, e.g. `1.2.3 (latest 2.0.1, 1 major behind)`
+fn compare_latest(version: &str, latest: &str) -> String {
+    match version::delta(version, latest) {
+        Some(Delta::UpToDate) => format!("{} (latest)", version),
+        Some(delta) => format!("{} (latest {}, {})", version, latest, delta),
+        None => format!("{} (latest {})", version, latest),
+    }
+}
+
+/// Whether the version of a record is lower than --min-version; missing
+/// and non-semver versions aren't
+fn below_minimum(record: &Record, minimum: &Version) -> bool {
+    record.version.as_deref().and_then(|found| version::below(found, minimum)) == Some(true)
+}
+
+/// Columns of a row describing the package of a record, as opposed to its
+/// manifest as a whole
+fn package_columns(record: &Record, time: &TimeArgs, now: u64, compare_latest: bool, min_version: Option<&Version>) -> Vec<String> {
+    let mut columns = Vec::new();
+    if let Some(matches) = &record.matches {
+        columns.push(format!("{} matching packages", matches.len()));
+    }
+    if let Some(range) = record.range.as_ref().filter(|_| !record.unlocked()) {
+        columns.push(match (&record.newest_in_range, &record.version) {
+            (None, _) => format!("range {}, no published version in range", range),
+            (Some(newest), Some(version)) if newest != version => {
+                format!("range {}, newest in range {} (npm update)", range, newest)
+            }
+            (Some(_), _) => format!("range {}, up to date", range),
+        });
+    }
+    // --compare-latest shows the latest version next to the locked one
+    if let (Some(latest), Some(version), false) = (&record.latest, &record.version, compare_latest) {
+        if latest != version {
+            columns.push(format!("outdated, latest {}", latest));
+        }
+    }
+    if let Some(minimum) = min_version.filter(|minimum| below_minimum(record, minimum)) {
+        columns.push(format!("BELOW MINIMUM: {}", minimum));
+    }
+    if let Some(size) = record.size {
+        let mut column = format!("size {}", format_size(size));
+        if let Some(latest_size) = record.latest_size {
+            if record.latest != record.version {
+                let delta = latest_size as i64 - size as i64;
+                let sign = if delta < 0 { "-" } else { "+" };
+                column.push_str(&format!(" ({}{} in latest)", sign, format_size(delta.unsigned_abs())));
+            }
+        }
+        columns.push(column);
+    }
+    if let Some(released) = record.released.as_deref().and_then(history::timestamp) {
+        columns.push(format!("released {}", time.format_at(released, now)));
+    }
+    if let Some(kind) = record.dependency_kind {
+        columns.push(kind.describe().to_string());
+    }
+    if let Some(local_path) = &record.local_path {
+        columns.push(format!("LOCAL: {}", local_path));
+    }
+    if let Some(git_source) = &record.git_source {
+        columns.push(format!("GIT: installed from {}", git_source));
+    }
+    match record.unapproved_sources.as_deref() {
+        Some([]) | None => {}
+        Some([source]) => columns.push(format!("UNAPPROVED REGISTRY: {}", source)),
+        Some(sources) => columns.push(format!("UNAPPROVED REGISTRY: {} and {} more", sources[0], sources.len() - 1)),
+    }
+    if let Some(conflict) = &record.hoist_conflict {
+        columns.push(format!("HOIST CONFLICT: {}", report::describe_conflict(conflict)));
+    }
+    if record.version_status == Some(VersionStatus::NonSemver) {
+        columns.push("NON-SEMVER: can't be compared as a version".to_string());
+    }
+    if let Some(migration) = &record.migration {
+        columns.push(format!("MIGRATION: {}", migration.describe()));
+    }
+    match record.dependency_chains.as_deref() {
+        Some([]) | None => {}
+        Some(chains) => columns.push(format!("WHY: {}", chains.join("; "))),
+    }
+    columns
+}
+
+/// Packages to keep of lockfiles pruned with --low-memory: the checked
+/// ones, and both sides of the policy migrations they're part of, which
+/// are looked up to tell how far the migration went
+fn kept_packages(packages: &[String], migrations: &BTreeMap<String, String>) -> Vec<String> {
+    let mut kept = packages.to_vec();
+    for (deprecated, replacement) in migrations {
+        if packages.contains(deprecated) || packages.contains(replacement) {
+            kept.extend([deprecated.clone(), replacement.clone()]);
+        }
+    }
+    kept.sort();
+    kept.dedup();
+    kept
+}
+
+/// Prints how many repositories use each package matched by a glob and
+/// at which versions, most adopted first
+fn print_adoption(records: &[Record]) {
+    let mut adoption: BTreeMap<&str, BTreeMap<&str, usize>> = BTreeMap::new();
+    for matches in records.iter().filter_map(|record| record.matches.as_ref()) {
+        for (name, version) in matches {
+            *adoption.entry(name).or_default().entry(version).or_default() += 1;
+        }
+    }
+
+    let mut adoption: Vec<_> = adoption
+        .into_iter()
+        .map(|(name, versions)| (name, versions.values().sum::<usize>(), versions))
+        .collect();
+    adoption.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    println!();
+    for (name, repos, versions) in adoption {
+        let spread: Vec<String> = versions
+            .iter()
+            .map(|(version, count)| format!("{} ({})", version, count))
+            .collect();
+        println!("{}\t{} repositories\t{}", name, repos, spread.join(", "));
+    }
+}
+
+/// Lines of --summary: how many repositories were checked, then per
+/// package how many are on each version, lowest first with a bar
+/// proportional to the count, miss the package or couldn't be checked
+fn summarize(records: &[Record]) -> Vec<String> {
+    let checked: BTreeSet<&str> = records.iter().map(|record| record.repo.as_str()).collect();
+    let mut lines = vec![format!("{} repositories checked", checked.len())];
+
+    let mut packages: Vec<&str> = Vec::new();
+    for record in records {
+        if !packages.contains(&record.package.as_str()) {
+            packages.push(&record.package);
+        }
+    }
+    for package_name in packages {
+        // Repositories are counted once per version however many of their
+        // manifests are on it
+        let mut versions: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+        let (mut missing, mut failed) = (BTreeSet::new(), BTreeSet::new());
+        for record in records.iter().filter(|record| record.package == package_name) {
+            if let Some(version) = record.shown_version() {
+                versions.entry(version).or_default().insert(&record.repo);
+            } else if record.local_path.is_some() {
+                versions.entry("local".to_string()).or_default().insert(&record.repo);
+            } else if is_missing(record) {
+                missing.insert(record.repo.as_str());
+            } else if record.error.is_some() {
+                failed.insert(record.repo.as_str());
+            }
+        }
+
+        let mut versions: Vec<(String, usize)> = versions.into_iter().map(|(version, repos)| (version, repos.len())).collect();
+        // Non-semver versions go last
+        versions.sort_by_cached_key(|(version, _)| {
+            let parsed = version::parse(version);
+            (parsed.is_none(), parsed, version.clone())
+        });
+        let most = versions.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let width = versions.iter().map(|(version, _)| version.len()).chain(["missing".len(), "failed".len()]).max().unwrap_or(0);
+
+        lines.push(package_name.to_string());
+        for (version, count) in &versions {
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most));
+            lines.push(format!("  {:width$}  {:>5}  {}", version, count, bar, width = width));
+        }
+        lines.push(format!("  {:width$}  {:>5}", "missing", missing.len(), width = width));
+        lines.push(format!("  {:width$}  {:>5}", "failed", failed.len(), width = width));
+    }
+    lines
+}
+
+/// What to read from a manifest and the registry metadata besides the
+/// version of the package
+#[derive(Debug, Clone, Default)]
+pub struct InspectOptions {
+    /// Registry hosts whose packages are approved, none to skip the check
+    pub approved_registry: Vec<String>,
+    /// Check the registries of every package instead of the checked one
+    pub all_deps: bool,
+    pub stats: bool,
+    pub duplicates: bool,
+    pub latest: bool,
+    pub show_size: bool,
+    pub show_age: bool,
+    pub latest_in_range: bool,
+    pub why: bool,
+    /// Replacements of deprecated packages, from the policy
+    pub migrations: BTreeMap<String, String>,
+}
+
+impl From<&CheckArgs> for InspectOptions {
+    fn from(cli: &CheckArgs) -> InspectOptions {
+        InspectOptions {
+            approved_registry: cli.approved_registry.clone(),
+            all_deps: cli.all_deps,
+            stats: cli.stats,
+            duplicates: cli.duplicates,
+            latest: cli.outdated || cli.compare_latest,
+            show_size: cli.show_size,
+            show_age: cli.show_age,
+            latest_in_range: cli.latest_in_range,
+            why: cli.why,
+            migrations: BTreeMap::new(),
+        }
+    }
+}
+
+/// Reads the version of a package and what else the options ask for from
+/// a fetched manifest, failing with the status and message of the parse
+/// error
+pub(crate) fn inspect(
+    repo: &str,
+    package_name: &str,
+    fetched: Fetched,
+    options: &InspectOptions,
+    packument: Option<&Packument>,
+) -> std::result::Result<Record, (u16, String)> {
+    let mut record = Record::new(repo, package_name);
+    record.ecosystem = fetched.ecosystem;
+    record.stale_lockfile = fetched.stale_lockfile;
+    record.template = fetched.template;
+    record.ecosystems = fetched.ecosystems;
+    record.workspace = fetched.workspace;
+    record.workspace_dir = fetched.workspace_dir.clone();
+    if fetched.path.is_some() {
+        record.path = fetched.path.clone();
+    }
+
+    let ecosystem = match fetched.ecosystem {
+        Some(ecosystem) => ecosystem,
+        None => return Ok(record),
+    };
+
+    let parse_error = |message| (fetched.status, message);
+    let path = fetched.path.as_deref().unwrap_or(ecosystem.manifest());
+    // The lockfile checks other than the version only read package-lock.json
+    let package_lock = ecosystem == Ecosystem::Npm && path.rsplit('/').next() == Some(ecosystem.manifest());
+    if package_name.contains('*') && package_lock {
+        record.matches = Some(lockfile::matching(&fetched.body, package_name).map_err(parse_error)?);
+        return Ok(record);
+    }
+    // Without a lockfile only the range package.json declares is known
+    let package_json = ecosystem == Ecosystem::Npm && path.rsplit('/').next() == Some(lockfile::PACKAGE_JSON);
+    if package_json {
+        record.range = lockfile::package_json_range(&fetched.body, package_name).map_err(parse_error)?;
+        record.dependency_kind = lockfile::package_json_kind(&fetched.body, package_name).map_err(parse_error)?;
+    } else {
+        record.version = match &fetched.workspace_dir {
+            Some(dir) if package_lock => lockfile::find_workspace_version(&fetched.body, dir, package_name).map_err(parse_error)?,
+            _ => ecosystem.find_version_in(path, &fetched.body, package_name).map_err(parse_error)?,
+        };
+    }
+    let migration = options.migrations
+        .iter()
+        .find(|(deprecated, replacement)| *deprecated == package_name || *replacement == package_name);
+    if let Some((deprecated, replacement)) = migration {
+        let installs = |name: &str| -> std::result::Result<bool, String> {
+            let found = if package_json {
+                lockfile::package_json_range(&fetched.body, name)?
+            } else {
+                match &fetched.workspace_dir {
+                    Some(dir) if package_lock => lockfile::find_workspace_version(&fetched.body, dir, name)?,
+                    _ => ecosystem.find_version_in(path, &fetched.body, name)?,
+                }
+            };
+            Ok(found.is_some())
+        };
+        let status = match (installs(deprecated).map_err(parse_error)?, installs(replacement).map_err(parse_error)?) {
+            (true, false) => MigrationStatus::Deprecated,
+            (false, true) => MigrationStatus::Replacement,
+            (true, true) => MigrationStatus::Both,
+            (false, false) => MigrationStatus::Neither,
+        };
+        record.migration = Some(Migration { deprecated: deprecated.clone(), replacement: replacement.clone(), status });
+    }
+    if let Some(found) = record.version.take() {
+        let (found, status) = version::normalize(&found);
+        record.version = Some(found);
+        record.version_status = Some(status);
+    }
+    if package_lock {
+        record.git_source = lockfile::git_source(&fetched.body, package_name).map_err(parse_error)?;
+        record.local_path = lockfile::local_source(&fetched.body, package_name).map_err(parse_error)?;
+        record.dev = lockfile::is_dev(&fetched.body, package_name).map_err(parse_error)?;
+        let workspace_dir = fetched.workspace_dir.as_deref();
+        record.dependency_kind = lockfile::dependency_kind(&fetched.body, workspace_dir, package_name).map_err(parse_error)?;
+        if record.local_path.is_some() {
+            record.version = None;
+            record.version_status = None;
+        }
+        let versions = lockfile::workspace_versions(&fetched.body, package_name).map_err(parse_error)?;
+        if versions.values().collect::<BTreeSet<_>>().len() > 1 {
+            record.hoist_conflict = Some(versions);
+        }
+        if options.why {
+            record.dependency_chains = Some(lockfile::dependency_chains(&fetched.body, package_name).map_err(parse_error)?);
+        }
+    }
+    if !options.approved_registry.is_empty() && package_lock {
+        let package_name = if options.all_deps { None } else { Some(package_name) };
+        let sources = lockfile::unapproved_sources(&fetched.body, package_name, &options.approved_registry).map_err(parse_error)?;
+        record.unapproved_sources = Some(sources);
+    }
+    if options.stats && package_lock {
+        record.stats = Some(lockfile::stats(&fetched.body).map_err(parse_error)?);
+    }
+    if options.duplicates && package_lock {
+        record.duplicates = Some(lockfile::duplicates(&fetched.body).map_err(parse_error)?);
+    }
+    if let (Some(packument), Ecosystem::Npm) = (packument, ecosystem) {
+        if options.latest {
+            record.latest = packument.latest().map(str::to_string);
+        }
+        if options.show_size {
+            record.size = record.version.as_deref().and_then(|version| packument.unpacked_size(version));
+            record.latest_size = record.latest.as_deref().and_then(|latest| packument.unpacked_size(latest));
+        }
+        if options.show_age {
+            record.released = record.version.as_deref().and_then(|version| packument.published(version)).map(str::to_string);
+        }
+    }
+    if let Some(packument) = packument.filter(|_| package_lock && options.latest_in_range) {
+        record.range = lockfile::declared_range(&fetched.body, package_name).map_err(parse_error)?;
+        if let Some(range) = record.range.as_deref().and_then(Range::parse) {
+            record.newest_in_range = packument.newest_matching(&range).map(|version| version.to_string());
+        }
+    }
+
+    if let Some(sha) = fetched.sha {
+        let (host, full_name) = github::parse_repo(repo);
+        let path = fetched.path.as_deref().unwrap_or(ecosystem.manifest());
+        record.html_url = Some(github::html_url(host, full_name, &sha, path));
+        record.raw_url = Some(github::raw_url(host, full_name, &sha, path));
+        record.path = Some(path.to_string());
+        record.sha = Some(sha.clone());
+    }
+
+    Ok(record)
+}
+
+/// Formats a size in bytes with decimal units, as npm does
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=999 => format!("{} B", bytes),
+        1_000..=999_999 => format!("{:.1} kB", bytes as f64 / 1e3),
+        _ => format!("{:.1} MB", bytes as f64 / 1e6),
+    }
+}
+
+/// A manifest fetched from a repository
+#[derive(Clone)]
+pub(crate) struct Fetched {
+    status: u16,
+    body: Bytes,
+    /// Ecosystem of the manifest, none when no supported ecosystem was
+    /// detected in the repository and nothing was fetched
+    ecosystem: Option<Ecosystem>,
+    /// Whether the repository demonstrably isn't a project of the ecosystem
+    irrelevant: bool,
+    /// Commit the manifest was read at, when resolved
+    sha: Option<String>,
+    /// Whether package.json was committed after the lockfile, when checked
+    stale_lockfile: Option<bool>,
+    /// Template the repository was generated from, empty for none, when
+    /// resolved
+    template: Option<String>,
+    /// Path of the manifest when it isn't the usual one at the root: with
+    /// --scan or a project directory, or the lockfile of another package
+    /// manager such as yarn.lock when the repository has no
+    /// package-lock.json
+    path: Option<String>,
+    /// Name of the workspace package the manifest belongs to, with --scan
+    /// or --workspaces
+    workspace: Option<String>,
+    /// Directory of the workspace to check the root lockfile for, with
+    /// --workspaces
+    workspace_dir: Option<String>,
+    /// Whether the tree listed with --scan was too large to be listed
+    /// entirely, so some manifests may be missed
+    truncated_tree: bool,
+    /// Manifest of each ecosystem found in the repository, with
+    /// --ecosystem-summary
+    ecosystems: Option<Vec<ManifestSummary>>,
+}
+
+/// What to fetch besides the manifest, shared by every repository of a run
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FetchOptions {
+    /// Ecosystem of the manifest, none to detect it per repository
+    ecosystem: Option<Ecosystem>,
+    resolve_sha: bool,
+    check_relevance: bool,
+    check_freshness: bool,
+    resolve_template: bool,
+    /// Whether to fetch every manifest of the tree instead of the root one
+    scan: bool,
+    /// Globs of the manifest paths to keep and to skip with --scan
+    include_paths: Vec<String>,
+    exclude_paths: Vec<String>,
+    /// Whether to check the root lockfile once per workspace
+    workspaces: bool,
+    /// Whether to look for the manifest of every ecosystem
+    ecosystem_summary: bool,
+    /// With --low-memory, the packages to keep of each package-lock.json,
+    /// which is streamed to disk and pruned while it's parsed
+    prune_to: Option<Arc<[String]>>,
+}
+
+impl FetchOptions {
+    /// Fetches only the root manifest of each repository
+    pub(crate) fn new(ecosystem: Option<Ecosystem>) -> FetchOptions {
+        FetchOptions { ecosystem, ..FetchOptions::default() }
+    }
+}
+
+/// Fetches a manifest, with --low-memory through a temporary file and
+/// pruning package-lock.json to the checked packages
+async fn get_manifest(github: &GitHub, repo: &str, uri: &str, manifest: &str, prune_to: Option<&[String]>) -> std::result::Result<(StatusCode, Bytes), Failure> {
+    let fetch_error = |e: Error| Failure::new(repo, Stage::Fetch, None, e.to_string());
+    let prune_to = match prune_to {
+        Some(prune_to) => prune_to,
+        None => return github.get(uri, "application/vnd.github.raw").await.map_err(fetch_error),
+    };
+
+    let (status, download) = github.download(uri, "application/vnd.github.raw").await.map_err(fetch_error)?;
+    if status.is_success() && manifest.rsplit('/').next() == Some(Ecosystem::Npm.manifest()) {
+        let pruned = prune(download.open().map_err(fetch_error)?, prune_to).await
+            .map_err(|message| Failure::new(repo, Stage::Parse, Some(status.as_u16()), message))?;
+        return Ok((status, Bytes::from(pruned)));
+    }
+    // Other lockfiles are read whole, and errors are small
+    Ok((status, download.bytes().map_err(fetch_error)?))
+}
+
+/// Prunes a package-lock.json on a blocking thread, since reading and
+/// parsing megabytes of it would hold up the requests of the runtime's
+async fn prune(file: std::fs::File, prune_to: &[String]) -> std::result::Result<Vec<u8>, String> {
+    let prune_to = prune_to.to_vec();
+    tokio::task::spawn_blocking(move || lockfile::prune(file, &prune_to))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn fetch_manifest(github: GitHub, repo: String, options: FetchOptions) -> std::result::Result<Fetched, Failure> {
+    let FetchOptions { ecosystem, resolve_sha, check_relevance, check_freshness, resolve_template, prune_to, .. } = options;
+    let (host, full_name) = github::parse_repo(&repo);
+
+    let template = if resolve_template {
+        let repository = github.repository(host, full_name).await
+            .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+        Some(repository.template_repository.map(|template| template.full_name).unwrap_or_default())
+    } else {
+        None
+    };
+
+    let ecosystem = match ecosystem {
+        Some(ecosystem) => ecosystem,
+        None => {
+            let languages = github.languages(host, full_name).await
+                .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+            match Ecosystem::detect(&languages) {
+                Some(ecosystem) => ecosystem,
+                None => {
+                    let fetched = Fetched { status: 200, body: Bytes::new(), ecosystem: None, irrelevant: true, sha: None, stale_lockfile: None, template, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: None };
+                    return Ok(fetched);
+                }
+            }
+        }
+    };
+
+    // Entries such as org/repo@release/1.2 are checked at that ref
+    let repo_ref = github::repo_ref(&repo);
+    let sha = if resolve_sha {
+        let sha = github.commit_sha(host, full_name, repo_ref.unwrap_or("HEAD")).await
+            .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+        Some(sha)
+    } else {
+        None
+    };
+
+    let contents_uri = |manifest: &str| github::contents_uri(host, full_name, manifest, sha.as_deref().or(repo_ref));
+    // Entries such as org/repo:packages/api check the project of a
+    // subdirectory
+    let prefix = github::repo_dir(&repo).map(|dir| format!("{}/", dir)).unwrap_or_default();
+    let mut manifest = format!("{}{}", prefix, ecosystem.manifest());
+    let uri = contents_uri(&manifest);
+    let (mut status, mut body_bytes) = get_manifest(&github, &repo, &uri, &manifest, prune_to.as_deref()).await?;
+    // Projects of other package managers have another lockfile instead,
+    // and projects without any lockfile still declare a range
+    let fallbacks = if status == 404 { ecosystem.fallback_manifests() } else { &[] };
+    for fallback in fallbacks {
+        let fallback = format!("{}{}", prefix, fallback);
+        let (fallback_status, fallback_body) = get_manifest(&github, &repo, &contents_uri(&fallback), &fallback, prune_to.as_deref()).await?;
+        if fallback_status.is_success() {
+            manifest = fallback;
+            status = fallback_status;
+            body_bytes = fallback_body;
+            break;
+        }
+    }
+    if status == 404 && check_relevance && ecosystem == Ecosystem::Npm {
+        let tree = github.tree(host, full_name, sha.as_deref().or(repo_ref).unwrap_or("HEAD")).await
+            .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+        let has_package_json = tree.tree
+            .iter()
+            .filter(|entry| entry.kind == "blob" && entry.path.starts_with(&prefix))
+            .any(|entry| entry.path.rsplit('/').next() == Some("package.json"));
+        if !tree.truncated && !has_package_json {
+            let fetched = Fetched { status: 404, body: Bytes::new(), ecosystem: Some(ecosystem), irrelevant: true, sha, stale_lockfile: None, template, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: None };
+            return Ok(fetched);
+        }
+    }
+
+    // A missing manifest and a repository the token can't read both
+    // answer 404, so the repository's metadata tells them apart
+    let access = if status == 403 || status == 404 {
+        let access = github.access(host, full_name, status).await
+            .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+        Some(access)
+    } else {
+        None
+    };
+    if !status.is_success() {
+        let message = match access {
+            Some(access) => format!("{}: {}", access.describe(), String::from_utf8_lossy(&body_bytes)),
+            None => String::from_utf8_lossy(&body_bytes).into_owned(),
+        };
+        let mut failure = Failure::new(&repo, Stage::Fetch, Some(status.as_u16()), message);
+        failure.uri = Some(uri);
+        failure.access = access;
+        return Err(failure);
+    }
+
+    let stale_lockfile = if check_freshness && ecosystem == Ecosystem::Npm && manifest.rsplit('/').next() != Some(lockfile::PACKAGE_JSON) {
+        let git_ref = sha.as_deref().or(repo_ref).unwrap_or("HEAD");
+        let package_json = format!("{}package.json", prefix);
+        let (package_json_date, lockfile_date) = future::try_join(
+            github.last_commit_date(host, full_name, &package_json, git_ref),
+            github.last_commit_date(host, full_name, &manifest, git_ref),
+        )
+        .await
+        .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+        // Both dates are ISO 8601 in UTC, so they compare as strings
+        match (package_json_date, lockfile_date) {
+            (Some(package_json_date), Some(lockfile_date)) => Some(package_json_date > lockfile_date),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(Fetched {
+        status: status.as_u16(),
+        body: body_bytes,
+        ecosystem: Some(ecosystem),
+        irrelevant: false,
+        sha,
+        stale_lockfile,
+        template,
+        path: (manifest != ecosystem.manifest()).then_some(manifest),
+        workspace: None,
+        workspace_dir: None,
+        truncated_tree: false,
+        ecosystems: None,
+    })
+}
+
+/// Fetches the manifests of a repository: the root one, or with --scan
+/// every one found in its tree
+pub(crate) async fn fetch(github: GitHub, repo: String, options: FetchOptions) -> std::result::Result<Vec<Fetched>, Failure> {
+    match Provider::of(&repo) {
+        // GitLab projects are read through another API and not summarized
+        Provider::GitLab => return fetch_gitlab_manifest(github, repo, options).await.map(|fetched| vec![fetched]),
+        Provider::Local => return read_local_manifest(repo, options).await.map(|fetched| vec![fetched]),
+        Provider::GitHub => {}
+    }
+    let ecosystem_summary = options.ecosystem_summary;
+    let fetched = if options.scan {
+        fetch_workspaces(github.clone(), repo.clone(), options).await
+    } else if options.workspaces {
+        fetch_workspace_projects(github.clone(), repo.clone(), options).await
+    } else {
+        fetch_manifest(github.clone(), repo.clone(), options).await.map(|fetched| vec![fetched])
+    };
+    if !ecosystem_summary {
+        return fetched;
+    }
+
+    match fetched {
+        Ok(mut fetched) => {
+            // Every manifest of a repository shares its summary
+            let checked = fetched.first().and_then(|first| {
+                let ecosystem = first.ecosystem.filter(|_| !first.irrelevant)?;
+                let path = first.path.clone().unwrap_or_else(|| ecosystem.manifest().to_string());
+                Some((ecosystem, path, first.body.clone()))
+            });
+            let summary = summarize_ecosystems(&github, &repo, checked).await;
+            for fetched in &mut fetched {
+                fetched.ecosystems = Some(summary.clone());
+            }
+            Ok(fetched)
+        }
+        Err(mut failure) if failure.stage == Stage::Fetch && failure.status == Some(404) => {
+            failure.ecosystems = Some(summarize_ecosystems(&github, &repo, None).await);
+            Err(failure)
+        }
+        Err(failure) => Err(failure),
+    }
+}
+
+/// Looks for the manifest of every ecosystem in a repository, including
+/// the fallback ones, given the manifest the versions were read from
+/// which isn't fetched again. Manifests which can't be fetched are listed
+/// as missing.
+async fn summarize_ecosystems(github: &GitHub, repo: &str, checked: Option<(Ecosystem, String, Bytes)>) -> Vec<ManifestSummary> {
+    let (host, full_name) = github::parse_repo(repo);
+    let git_ref = github::repo_ref(repo).unwrap_or("HEAD");
+    let prefix = github::repo_dir(repo).map(|dir| format!("{}/", dir)).unwrap_or_default();
+    let mut summary = Vec::new();
+    for ecosystem in Ecosystem::ALL {
+        if let Some((_, path, body)) = checked.as_ref().filter(|(checked, ..)| *checked == ecosystem) {
+            let parsed = ecosystem.parses(path, body);
+            summary.push(ManifestSummary { ecosystem, manifest: Some(path.clone()), parsed: Some(parsed), checked: true });
+            continue;
+        }
+        let mut found = ManifestSummary { ecosystem, manifest: None, parsed: None, checked: false };
+        for manifest in iter::once(ecosystem.manifest()).chain(ecosystem.fallback_manifests().iter().copied()) {
+            let path = format!("{}{}", prefix, manifest);
+            if let Ok(Some(body)) = github.file(host, full_name, &path, git_ref).await {
+                found.parsed = Some(ecosystem.parses(&path, &body));
+                found.manifest = Some(path);
+                break;
+            }
+        }
+        summary.push(found);
+    }
+    summary
+}
+
+/// Fetches the manifest of a GitLab project through the repository files
+/// API. Only the manifest is read: the commit, freshness and template
+/// aren't resolved, and --scan and --workspaces need GitHub's trees.
+async fn fetch_gitlab_manifest(github: GitHub, repo: String, options: FetchOptions) -> std::result::Result<Fetched, Failure> {
+    let fetch_error = |e: Box<dyn std::error::Error + Send + Sync>| Failure::new(&repo, Stage::Fetch, None, e.to_string());
+    let project = gitlab::project(&repo).unwrap_or_default();
+    if options.scan || options.workspaces {
+        return Err(Failure::new(&repo, Stage::Fetch, None, "--scan and --workspaces only support GitHub repositories"));
+    }
+
+    let ecosystem = match options.ecosystem {
+        Some(ecosystem) => ecosystem,
+        None => match Ecosystem::detect(&gitlab::languages(&github, project).await.map_err(fetch_error)?) {
+            Some(ecosystem) => ecosystem,
+            None => {
+                let fetched = Fetched { status: 200, body: Bytes::new(), ecosystem: None, irrelevant: true, sha: None, stale_lockfile: None, template: None, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: None };
+                return Ok(fetched);
+            }
+        },
+    };
+
+    let git_ref = github::repo_ref(&repo).unwrap_or("HEAD");
+    let prefix = github::repo_dir(&repo).map(|dir| format!("{}/", dir)).unwrap_or_default();
+    let manifests = std::iter::once(ecosystem.manifest()).chain(ecosystem.fallback_manifests().iter().copied());
+    let mut not_found = None;
+    for manifest in manifests {
+        let manifest = format!("{}{}", prefix, manifest);
+        let uri = gitlab::file_url(project, &manifest, git_ref);
+        let (status, body_bytes) = gitlab::get(&github, &uri).await.map_err(|e| fetch_error(e.into()))?;
+        if status == StatusCode::NOT_FOUND {
+            not_found.get_or_insert((uri, body_bytes));
+            continue;
+        }
+        if !status.is_success() {
+            let mut failure = Failure::new(&repo, Stage::Fetch, Some(status.as_u16()), String::from_utf8_lossy(&body_bytes));
+            failure.uri = Some(uri);
+            return Err(failure);
+        }
+        return Ok(Fetched {
+            status: status.as_u16(),
+            body: body_bytes,
+            ecosystem: Some(ecosystem),
+            irrelevant: false,
+            sha: None,
+            stale_lockfile: None,
+            template: None,
+            path: (manifest != ecosystem.manifest()).then_some(manifest),
+            workspace: None,
+            workspace_dir: None,
+            truncated_tree: false,
+            ecosystems: None,
+        });
+    }
+
+    // The first manifest is the one reported missing, as for GitHub
+    let (uri, body_bytes) = not_found.unwrap_or_default();
+    let mut failure = Failure::new(&repo, Stage::Fetch, Some(404), String::from_utf8_lossy(&body_bytes));
+    failure.uri = Some(uri);
+    Err(failure)
+}
+
+/// Reads the manifest of a directory of a clone found by --local, the
+/// first of local::manifests found in it
+async fn read_local_manifest(repo: String, options: FetchOptions) -> std::result::Result<Fetched, Failure> {
+    if options.scan || options.workspaces {
+        return Err(Failure::new(&repo, Stage::Fetch, None, "--local already finds every lockfile, without --scan or --workspaces"));
+    }
+    let root = local::root(&repo).unwrap_or_default();
+    let prefix = github::repo_dir(&repo).map(|dir| format!("{}/", dir)).unwrap_or_default();
+    for (ecosystem, manifest) in local::manifests(options.ecosystem) {
+        let manifest = format!("{}{}", prefix, manifest);
+        let file = root.join(&manifest);
+        let read_error = |e: std::io::Error| Failure::new(&repo, Stage::Fetch, None, format!("{}: {}", file.display(), e));
+        let mut opened = match tokio::fs::File::open(&file).await {
+            Ok(opened) => opened,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(read_error(e)),
+        };
+        // Pruned lockfiles are parsed as they're read, never whole in memory
+        let body = match options.prune_to.as_deref().filter(|_| manifest.rsplit('/').next() == Some(Ecosystem::Npm.manifest())) {
+            Some(prune_to) => prune(opened.into_std().await, prune_to).await
+                .map_err(|message| Failure::new(&repo, Stage::Parse, Some(200), message))?,
+            None => {
+                let mut body = Vec::new();
+                opened.read_to_end(&mut body).await.map_err(read_error)?;
+                body
+            }
+        };
+        return Ok(Fetched {
+            status: 200,
+            body: Bytes::from(body),
+            ecosystem: Some(ecosystem),
+            irrelevant: false,
+            sha: None,
+            stale_lockfile: None,
+            template: None,
+            path: (manifest != ecosystem.manifest()).then_some(manifest),
+            workspace: None,
+            workspace_dir: None,
+            truncated_tree: false,
+            ecosystems: None,
+        });
+    }
+
+    let mut failure = Failure::new(&repo, Stage::Fetch, Some(404), format!("No manifest found in {}", root.join(prefix).display()));
+    failure.access = Some(Access::Missing);
+    Err(failure)
+}
+
+/// Fetches the root lockfile of a repository and, when it's a workspace
+/// repository, returns it once per sub-project so each is checked on its own
+async fn fetch_workspace_projects(github: GitHub, repo: String, options: FetchOptions) -> std::result::Result<Vec<Fetched>, Failure> {
+    let root = fetch_manifest(github.clone(), repo.clone(), options).await?;
+    // Only package-lock.json records which version each workspace uses, so
+    // the lockfiles of other package managers are checked as a whole
+    if root.irrelevant || root.ecosystem != Some(Ecosystem::Npm) || root.path.is_some() {
+        return Ok(vec![root]);
+    }
+
+    let (host, full_name) = github::parse_repo(&repo);
+    let git_ref = root.sha.as_deref().or(github::repo_ref(&repo)).unwrap_or("HEAD");
+    let dirs = workspaces::discover(&github, host, full_name, git_ref).await
+        .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+    if dirs.is_empty() {
+        return Ok(vec![root]);
+    }
+
+    let projects = dirs
+        .into_iter()
+        .map(|dir| Fetched {
+            status: root.status,
+            body: root.body.clone(),
+            ecosystem: root.ecosystem,
+            irrelevant: false,
+            sha: root.sha.clone(),
+            stale_lockfile: root.stale_lockfile,
+            template: root.template.clone(),
+            path: None,
+            workspace: lockfile::workspace_name(&root.body, &dir),
+            workspace_dir: Some(dir),
+            truncated_tree: false,
+            ecosystems: None,
+        })
+        .collect();
+    Ok(projects)
+}
+
+/// Fetches every manifest of the ecosystem found in the tree of a
+/// repository, e.g. each package-lock.json of a monorepo, along with the
+/// name of the package.json next to it
+async fn fetch_workspaces(github: GitHub, repo: String, options: FetchOptions) -> std::result::Result<Vec<Fetched>, Failure> {
+    let (host, full_name) = github::parse_repo(&repo);
+    let fetch_error = |e: Box<dyn std::error::Error + Send + Sync>| Failure::new(&repo, Stage::Fetch, None, e.to_string());
+    let ecosystem = options.ecosystem.ok_or_else(|| Failure::new(&repo, Stage::Fetch, None, "--scan needs a fixed --ecosystem"))?;
+
+    let sha = if options.resolve_sha {
+        Some(github.commit_sha(host, full_name, github::repo_ref(&repo).unwrap_or("HEAD")).await.map_err(fetch_error)?)
+    } else {
+        None
+    };
+    let git_ref = sha.as_deref().or(github::repo_ref(&repo)).unwrap_or("HEAD");
+
+    let tree = github.tree(host, full_name, git_ref).await.map_err(fetch_error)?;
+    let manifest = ecosystem.manifest();
+    let prefix = github::repo_dir(&repo).map(|dir| format!("{}/", dir)).unwrap_or_default();
+    let paths: Vec<&str> = tree.tree
+        .iter()
+        .filter(|entry| entry.kind == "blob" && entry.path.starts_with(&prefix) && !entry.path.contains("node_modules/"))
+        .map(|entry| entry.path.as_str())
+        .filter(|path| path.rsplit('/').next() == Some(manifest))
+        .filter(|path| options.include_paths.is_empty() || options.include_paths.iter().any(|glob| lockfile::path_matches(glob, path)))
+        .filter(|path| !options.exclude_paths.iter().any(|glob| lockfile::path_matches(glob, path)))
+        .collect();
+
+    let mut fetched = Vec::new();
+    for path in paths {
+        let uri = github::contents_uri(host, full_name, path, Some(git_ref));
+        let (status, body) = get_manifest(&github, &repo, &uri, path, options.prune_to.as_deref()).await?;
+        if !status.is_success() {
+            let mut failure = Failure::new(&repo, Stage::Fetch, Some(status.as_u16()), String::from_utf8_lossy(&body));
+            failure.uri = Some(uri);
+            return Err(failure);
+        }
+
+        let workspace = if ecosystem == Ecosystem::Npm {
+            let dir = path.rsplit_once('/').map(|(dir, _)| format!("{}/", dir)).unwrap_or_default();
+            let uri = github::contents_uri(host, full_name, &format!("{}package.json", dir), Some(git_ref));
+            let (status, package_json) = github.get(&uri, "application/vnd.github.raw").await
+                .map_err(|e| Failure::new(&repo, Stage::Fetch, None, e.to_string()))?;
+            if status.is_success() {
+                lockfile::package_name(&package_json)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        fetched.push(Fetched {
+            status: status.as_u16(),
+            body,
+            ecosystem: Some(ecosystem),
+            irrelevant: false,
+            sha: sha.clone(),
+            stale_lockfile: None,
+            template: None,
+            path: Some(path.to_string()),
+            workspace,
+            workspace_dir: None,
+            truncated_tree: tree.truncated && fetched.is_empty(),
+            ecosystems: None,
+        });
+    }
+
+    if fetched.is_empty() {
+        return Err(Failure::new(&repo, Stage::Fetch, Some(404), format!("No {} found in the tree", manifest)));
+    }
+    Ok(fetched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_migration_progress() {
+        let body = br#"{"lockfileVersion":3,"packages":{"":{},"node_modules/request":{"version":"2.88.2"},"node_modules/got":{"version":"14.4.1"}}}"#;
+        let fetched = Fetched { status: 200, body: Bytes::from_static(body), ecosystem: Some(Ecosystem::Npm), irrelevant: false, sha: None, stale_lockfile: None, template: None, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: None };
+        let mut options = InspectOptions::default();
+        options.migrations.insert("request".to_string(), "got".to_string());
+        options.migrations.insert("moment".to_string(), "dayjs".to_string());
+
+        let record = inspect("org/api", "request", fetched.clone(), &options, None).unwrap();
+        assert_eq!(record.migration.map(|migration| migration.status), Some(MigrationStatus::Both));
+        let record = inspect("org/api", "dayjs", fetched.clone(), &options, None).unwrap();
+        assert_eq!(record.migration.map(|migration| migration.status), Some(MigrationStatus::Neither));
+        assert_eq!(inspect("org/api", "express", fetched, &options, None).unwrap().migration, None);
+    }
+
+    #[test]
+    fn keeps_both_sides_of_migrations_when_pruning() {
+        let body = br#"{"lockfileVersion":3,"packages":{"":{},"node_modules/request":{"version":"2.88.2"},"node_modules/got":{"version":"14.4.1"},"node_modules/left-pad":{"version":"1.3.0"}}}"#;
+        let migrations = BTreeMap::from([("request".to_string(), "got".to_string()), ("moment".to_string(), "dayjs".to_string())]);
+        let kept = kept_packages(&["request".to_string()], &migrations);
+        assert_eq!(kept, ["got", "request"]);
+
+        let pruned = lockfile::prune(&body[..], &kept).unwrap();
+        let fetched = Fetched { status: 200, body: Bytes::from(pruned), ecosystem: Some(Ecosystem::Npm), irrelevant: false, sha: None, stale_lockfile: None, template: None, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: None };
+        let options = InspectOptions { migrations, ..InspectOptions::default() };
+        let record = inspect("org/api", "request", fetched, &options, None).unwrap();
+        assert_eq!(record.migration.map(|migration| migration.status), Some(MigrationStatus::Both));
+    }
+
+    #[tokio::test]
+    async fn prunes_local_lockfiles_as_they_are_read() {
+        let work = std::env::temp_dir().join(format!("check-versions-check-{}", std::process::id()));
+        fs::create_dir_all(work.join("web")).unwrap();
+        let body = br#"{"lockfileVersion":3,"packages":{"":{},"node_modules/request":{"version":"2.88.2"},"node_modules/left-pad":{"version":"1.3.0"}}}"#;
+        fs::write(work.join("web/package-lock.json"), body).unwrap();
+
+        let repo = format!("{}{}:web", local::PREFIX, work.display());
+        let options = FetchOptions { prune_to: Some(vec!["request".to_string()].into()), ..FetchOptions::new(Some(Ecosystem::Npm)) };
+        let fetched = read_local_manifest(repo.clone(), options).await.unwrap();
+        assert_eq!(&fetched.body[..], br#"{"lockfileVersion":3,"packages":{"":{},"node_modules/request":{"version":"2.88.2"}}}"#);
+        let fetched = read_local_manifest(repo, FetchOptions::new(Some(Ecosystem::Npm))).await.unwrap();
+        assert_eq!(&fetched.body[..], &body[..]);
+        fs::remove_dir_all(&work).unwrap();
+    }
+
+    #[test]
+    fn summarizes_manifests_per_ecosystem() {
+        let summary = |ecosystem: Ecosystem, manifest: Option<(&str, &[u8])>, checked: bool| ManifestSummary {
+            ecosystem,
+            manifest: manifest.map(|(path, _)| path.to_string()),
+            parsed: manifest.map(|(path, body)| ecosystem.parses(path, body)),
+            checked,
+        };
+        let ecosystems = vec![
+            summary(Ecosystem::Npm, Some(("yarn.lock", b"lodash@^4.0.0:\n  version \"4.17.21\"\n")), true),
+            summary(Ecosystem::Go, Some(("go.mod", b"\xff")), false),
+            summary(Ecosystem::Python, None, false),
+        ];
+        let described: Vec<String> = ecosystems.iter().map(ManifestSummary::describe).collect();
+        assert_eq!(described, ["npm yarn.lock (checked)", "go go.mod (invalid)", "python none"]);
+
+        let fetched = Fetched { status: 200, body: Bytes::new(), ecosystem: None, irrelevant: true, sha: None, stale_lockfile: None, template: None, path: None, workspace: None, workspace_dir: None, truncated_tree: false, ecosystems: Some(ecosystems.clone()) };
+        let record = inspect("org/api", "lodash", fetched, &InspectOptions::default(), None).unwrap();
+        assert_eq!(record.ecosystems, Some(ecosystems));
+    }
+
+    #[test]
+    fn enforces_each_policy_on_the_same_records() {
+        let named = |name: &str, toml: &str| NamedPolicy { name: name.to_string(), policy: toml::from_str(toml).unwrap() };
+        let baseline = named("baseline", r#"prod = "warn""#);
+        let team = named("team", r#"prod = "fail""#);
+        let mut record = Record::new("org/api", "express");
+        record.version = Some("4.17.1".to_string());
+        record.latest = Some("4.18.2".to_string());
+
+        assert!(enforce_policies(std::slice::from_ref(&baseline), PolicyMode::Enforce, std::slice::from_ref(&record)).is_ok());
+        let failed = enforce_policies(&[baseline, team], PolicyMode::Enforce, &[record]).unwrap_err();
+        assert_eq!(failed.to_string(), "team: 1 repositories have problems that fail the policy");
+    }
+
+    #[test]
+    fn fails_below_a_version_or_without_the_package() {
+        let record = |repo: &str, version: Option<&str>| {
+            let mut record = Record::new(repo, "lodash");
+            record.version = version.map(str::to_string);
+            record
+        };
+        let mut no_manifest = record("org/docs", None);
+        no_manifest.error = Some("Not Found".to_string());
+        no_manifest.access = Some(Access::Missing);
+        let mut unreadable = record("org/secret", None);
+        unreadable.error = Some("Not Found".to_string());
+        unreadable.access = Some(Access::Hidden);
+        let records = [record("org/api", Some("4.17.21")), record("org/web", Some("4.17.15")), record("org/cli", None), no_manifest, unreadable];
+        let fixed = Version::parse("4.17.21").unwrap();
+
+        assert!(assert_versions(Some(&fixed), false, &records[..1]).is_ok());
+        let failed = assert_versions(Some(&fixed), false, &records).unwrap_err();
+        assert_eq!(failed.to_string(), "1 repositories below 4.17.21");
+        let failed = assert_versions(None, true, &records).unwrap_err();
+        assert_eq!(failed.to_string(), "2 repositories missing the package");
+        let failed = assert_versions(Some(&fixed), true, &records).unwrap_err();
+        assert_eq!(failed.to_string(), "1 repositories below 4.17.21; 2 repositories missing the package");
+    }
+
+    #[test]
+    fn orders_held_rows_by_version_or_repository() {
+        let row = |repo: &str, version: Option<&str>| {
+            let mut record = Record::new(repo, "express");
+            record.version = version.map(str::to_string);
+            let versions = version.unwrap_or("-------").to_string();
+            HeldRow { records: vec![record], versions, name: repo.to_string(), line: None }
+        };
+        let mut held = vec![
+            row("org/web", Some("4.9.0")),
+            row("org/docs", None),
+            row("org/api", Some("4.18.2")),
+            row("org/cli", Some("4.9.0")),
+            row("org/app", Some("next")),
+        ];
+        let names = |held: &[HeldRow]| held.iter().map(|row| row.name.clone()).collect::<Vec<_>>();
+
+        sort_held(&mut held, Some(SortBy::Repo), false);
+        assert_eq!(names(&held), ["org/api", "org/app", "org/cli", "org/docs", "org/web"]);
+        sort_held(&mut held, Some(SortBy::Version), false);
+        assert_eq!(names(&held), ["org/cli", "org/web", "org/api", "org/docs", "org/app"]);
+        sort_held(&mut held, Some(SortBy::Repo), true);
+        assert_eq!(names(&held), ["org/cli", "org/web", "org/api", "org/docs", "org/app"]);
+    }
+
+    #[test]
+    fn summarizes_versions_missing_packages_and_failures() {
+        let record = |repo: &str, version: Option<&str>, error: Option<&str>, access: Option<Access>| {
+            let mut record = Record::new(repo, "express");
+            record.version = version.map(str::to_string);
+            record.error = error.map(str::to_string);
+            record.access = access;
+            record
+        };
+        let records = vec![
+            record("org/web", Some("4.9.0"), None, None),
+            record("org/api", Some("4.18.2"), None, None),
+            record("org/cli", Some("4.9.0"), None, None),
+            // Two manifests of a repository on the same version count once
+            record("org/cli:tools", Some("4.18.2"), None, None),
+            record("org/cli:tools", Some("4.18.2"), None, None),
+            record("org/docs", None, None, None),
+            record("org/site", None, Some("no lockfile"), Some(Access::Missing)),
+            record("org/old", None, Some("timed out"), None),
+        ];
+        assert_eq!(summarize(&records), [
+            "7 repositories checked",
+            "express",
+            "  4.9.0        2  ########################################",
+            "  4.18.2       2  ########################################",
+            "  missing      2",
+            "  failed       1",
+        ]);
+
+        let lopsided = vec![record("org/web", Some("5.0.0"), None, None), record("org/api", Some("4.21.0"), None, None), record("org/cli", Some("4.21.0"), None, None), record("org/docs", Some("4.21.0"), None, None)];
+        let lines = summarize(&lopsided);
+        assert_eq!(lines[2], format!("  4.21.0       3  {}", "#".repeat(40)));
+        assert_eq!(lines[3], format!("  5.0.0        1  {}", "#".repeat(14)));
+    }
+
+    #[test]
+    fn shards_partition_the_repositories() {
+        let repos: Vec<String> = (0..200).map(|i| format!("org/repo-{}", i)).collect();
+        let shards: Vec<Shard> = (1..=3).map(|index| format!("{}/3", index).parse().unwrap()).collect();
+        let assigned = |repos: &[String]| -> Vec<Vec<String>> {
+            shards
+                .iter()
+                .map(|shard| {
+                    let mut mine: Vec<String> = repos.iter().filter(|repo| shard.contains(repo)).cloned().collect();
+                    mine.sort();
+                    mine
+                })
+                .collect()
+        };
+
+        let parts = assigned(&repos);
+        for repo in &repos {
+            assert_eq!(parts.iter().filter(|part| part.contains(repo)).count(), 1, "{}", repo);
+        }
+        assert!(parts.iter().all(|part| !part.is_empty()));
+        let mut reversed = repos.clone();
+        reversed.reverse();
+        assert_eq!(assigned(&reversed), parts);
+
+        assert!("0/3".parse::<Shard>().is_err());
+        assert!("4/3".parse::<Shard>().is_err());
+        assert!("1-3".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn shows_release_times_as_asked() {
+        let mut record = Record::new("org/api", "express");
+        record.released = Some("2017-09-12T20:13:06.000Z".to_string());
+        let now = 1_505_247_186 + 12 * 86_400;
+        let columns = |time: TimeArgs| package_columns(&record, &time, now, false, None);
+
+        assert_eq!(columns(TimeArgs { utc: true, relative_time: false }), ["released 2017-09-12 20:13 UTC"]);
+        assert_eq!(columns(TimeArgs { utc: false, relative_time: true }), ["released 12 days ago"]);
+        assert!(columns(TimeArgs::default())[0].starts_with("released 2017-09-1"));
+    }
+
+    #[test]
+    fn waives_repositories_checked_at_a_ref_or_path() {
+        let waiver = Waiver { repo: "org/api".to_string(), reason: "frozen".to_string(), expires: None };
+        assert!(waiver.applies_to("org/api"));
+        assert!(waiver.applies_to(&github::with_defaults("org/api".to_string(), Some("main"), None)));
+        assert!(waiver.applies_to(&github::with_defaults("org/api".to_string(), None, Some("packages/ui"))));
+        assert!(!waiver.applies_to(&github::with_defaults("org/api-v2".to_string(), Some("main"), None)));
+
+        let scoped = Waiver { repo: "org/api@release".to_string(), ..waiver };
+        assert!(scoped.applies_to("org/api@release"));
+        assert!(!scoped.applies_to(&github::with_defaults("org/api".to_string(), Some("main"), None)));
+    }
+
+    #[test]
+    fn samples_repositories_in_their_order() {
+        let repos: Vec<String> = (0..100).map(|i| format!("org/repo-{:03}", i)).collect();
+        let picked = sample(repos.clone(), 10, 42);
+        assert_eq!(picked.len(), 10);
+        assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(picked.iter().all(|repo| repos.contains(repo)));
+        assert_ne!(picked, repos[..10]);
+        assert_eq!(sample(repos[..3].to_vec(), 10, 42), repos[..3]);
+    }
+}
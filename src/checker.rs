@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future, stream, StreamExt};
+
+use crate::check::{self, FetchOptions, InspectOptions};
+use crate::discover::{self, Filter};
+use crate::ecosystem::Ecosystem;
+use crate::errors::{Failure, Stage};
+use crate::github::{self, GitHub};
+use crate::registry::{Packument, Registry, REGISTRY_URL};
+use crate::report::Record;
+use crate::Result;
+
+/// Repositories fetched at once, unless set with `parallel_requests`
+const PARALLEL_REQUESTS: usize = 10;
+
+/// Where the repositories to check come from
+#[derive(Debug, Clone)]
+pub enum RepoSource {
+    /// Repository entries such as `org/repo`, `ghe.example.com/org/repo`,
+    /// `org/repo@release/1.2` or `org/repo:packages/api`
+    List(Vec<String>),
+    /// What --repos reads: a JSON list or CSV inventory of repositories,
+    /// `-` for one per line on stdin, or the URL of a tracking issue or
+    /// project
+    Path(String),
+    /// Repositories of an organization, optionally prefixed with its host
+    Org { org: String, filter: Filter },
+}
+
+impl RepoSource {
+    /// Lists the repository entries of the source
+    pub async fn repos(&self, github: &GitHub) -> Result<Vec<String>> {
+        match self {
+            RepoSource::List(repos) => Ok(repos.clone()),
+            RepoSource::Path(path) => Ok(check::read_repos(path, github, &mut None).await?.collect().await),
+            RepoSource::Org { org, filter } => {
+                let (host, org) = github::parse_repo(org);
+                discover::org_repos(github, host, org, filter).await
+                    .map_err(|e| format!("Unable to list the repositories of {}: {}", org, e).into())
+            }
+        }
+    }
+}
+
+/// Results of a check: a record per manifest and package, and the
+/// repositories that couldn't be checked
+#[derive(Debug, Clone, Default)]
+pub struct VersionReport {
+    pub records: Vec<Record>,
+    pub failures: Vec<Failure>,
+}
+
+/// Checks the versions of packages across repositories without printing
+/// anything, for embedding the checks in another program
+///
+/// ```ignore
+/// let checker = VersionChecker::new(GitHub::new(Config::default()), ["express"]);
+/// let report = checker.check(RepoSource::List(vec!["org/api".into()])).await?;
+/// ```
+#[derive(Clone)]
+pub struct VersionChecker {
+    github: GitHub,
+    packages: Vec<String>,
+    ecosystem: Option<Ecosystem>,
+    options: InspectOptions,
+    parallel_requests: usize,
+}
+
+impl VersionChecker {
+    pub fn new(github: GitHub, packages: impl IntoIterator<Item = impl Into<String>>) -> VersionChecker {
+        VersionChecker {
+            github,
+            packages: packages.into_iter().map(Into::into).collect(),
+            ecosystem: None,
+            options: InspectOptions::default(),
+            parallel_requests: PARALLEL_REQUESTS,
+        }
+    }
+
+    /// Reads the manifest of this ecosystem instead of detecting it from
+    /// the languages of each repository
+    pub fn ecosystem(self, ecosystem: Ecosystem) -> VersionChecker {
+        VersionChecker { ecosystem: Some(ecosystem), ..self }
+    }
+
+    /// What to read besides the versions; the registry is only queried
+    /// when the options need published versions
+    pub fn options(self, options: InspectOptions) -> VersionChecker {
+        VersionChecker { options, ..self }
+    }
+
+    pub fn parallel_requests(self, parallel_requests: usize) -> VersionChecker {
+        VersionChecker { parallel_requests: parallel_requests.max(1), ..self }
+    }
+
+    /// Checks the repositories of a source, in their order
+    pub async fn check(&self, source: RepoSource) -> Result<VersionReport> {
+        if self.packages.is_empty() {
+            return Err("no package to check".into());
+        }
+        let repos = source.repos(&self.github).await?;
+        let packuments = self.packuments().await?;
+
+        let options = FetchOptions::new(self.ecosystem);
+        let fetched: Vec<_> = stream::iter(repos)
+            .map(|repo| {
+                let fetched = check::fetch(self.github.clone(), repo.clone(), options.clone());
+                async move { (repo, fetched.await) }
+            })
+            .buffered(self.parallel_requests)
+            .collect()
+            .await;
+
+        let mut report = VersionReport::default();
+        for (repo, fetched) in fetched {
+            let fetched = match fetched {
+                Ok(fetched) => fetched,
+                Err(failure) => {
+                    report.failures.push(failure);
+                    continue;
+                }
+            };
+            for fetched in fetched {
+                let records = self.packages
+                    .iter()
+                    .map(|package_name| {
+                        let packument = packuments.get(package_name).map(Arc::as_ref);
+                        check::inspect(&repo, package_name, fetched.clone(), &self.options, packument)
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>();
+                match records {
+                    Ok(records) => report.records.extend(records),
+                    Err((status, message)) => report.failures.push(Failure::new(&repo, Stage::Parse, Some(status), message)),
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Fetches the registry metadata of the packages when the options read
+    /// published versions
+    async fn packuments(&self) -> Result<HashMap<String, Arc<Packument>>> {
+        let options = &self.options;
+        if !(options.latest || options.show_size || options.show_age || options.latest_in_range) {
+            return Ok(HashMap::new());
+        }
+        let registry = Registry::new(self.github.clone(), REGISTRY_URL, None, Duration::ZERO, None);
+        let packuments = future::try_join_all(self.packages.iter().map(|package_name| {
+            let registry = registry.clone();
+            async move {
+                let packument = registry.packument(package_name).await
+                    .map_err(|e| format!("Unable to fetch {} from {}: {}", package_name, REGISTRY_URL, e))?;
+                Ok::<_, String>((package_name.clone(), packument))
+            }
+        }))
+        .await?;
+        Ok(packuments.into_iter().collect())
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use clap::ArgEnum;
+use log::warn;
+use serde::Deserialize;
+
+use crate::check::Format;
+use crate::Result;
+
+/// Name of the config file looked up when --config isn't given
+const FILE_NAME: &str = "check-versions.toml";
+
+/// Sections read from a config file found in the working directory, which
+/// may belong to an untrusted checkout: none of those choosing where
+/// requests, and the credentials sent with them, go
+const PROJECT_SECTIONS: [&str; 2] = ["defaults", "policy"];
+
+/// Settings read from the TOML file given with --config
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Per-host settings, keyed by the host requests are sent to
+    /// (e.g. api.github.com or ghe.example.com)
+    pub hosts: HashMap<String, HostConfig>,
+
+    /// Tuning of the HTTP client connection pool
+    pub http: HttpConfig,
+
+    /// How connections are established
+    pub transport: TransportConfig,
+
+    /// GitHub App to authenticate as instead of with a token
+    pub app: Option<AppConfig>,
+
+    /// Jira project to open issues in with --jira
+    pub jira: Option<JiraConfig>,
+
+    /// How serious problems are, failing the run when any is
+    pub policy: Option<PolicyConfig>,
+
+    /// Options used when they aren't given on the command line
+    pub defaults: Defaults,
+}
+
+/// Defaults of command line options, so that they don't have to be typed
+/// on every run
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Defaults {
+    /// Repositories to check, as given to --repos
+    pub repos: Option<String>,
+
+    /// Packages to check, as given to --package
+    pub package: Vec<String>,
+
+    /// Output format, as given to --format
+    pub format: Option<Format>,
+
+    /// Notes on repositories, as given to --annotations
+    pub annotations: Option<String>,
+
+    /// Repositories fetched at once, as given to --concurrency
+    pub concurrency: Option<usize>,
+
+    /// REST API URL of the default GitHub instance, as given to --api-url;
+    /// takes precedence over GITHUB_API_URL
+    pub api_url: Option<String>,
+}
+
+/// Severity of a repository with problems, from least to most serious
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Ignore,
+    Warn,
+    /// Makes the run exit with an error
+    Fail,
+}
+
+/// Whether failing rules of the policy fail the run, or only warn during a
+/// grace period after they're introduced
+#[derive(ArgEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyMode {
+    /// Report problems that would fail as warnings
+    Warn,
+    Enforce,
+}
+
+/// Severity of the problems of a kind of dependency, either alone
+/// (`dev = "fail"`) or with the mode of the rule
+/// (`dev = { severity = "fail", mode = "warn" }`)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Rule {
+    Severity(Severity),
+    Detailed {
+        severity: Severity,
+        /// Overrides --policy-mode for this rule
+        mode: Option<PolicyMode>,
+    },
+}
+
+impl Rule {
+    pub fn severity(self) -> Severity {
+        match self {
+            Rule::Severity(severity) | Rule::Detailed { severity, .. } => severity,
+        }
+    }
+
+    pub fn mode(self) -> Option<PolicyMode> {
+        match self {
+            Rule::Severity(_) => None,
+            Rule::Detailed { mode, .. } => mode,
+        }
+    }
+}
+
+/// Severities of problems by the kind of dependency the package is, e.g.
+/// fail on prod and warn on dev. Packages not known to be dev
+/// dependencies, such as those of other ecosystems, count as prod.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PolicyConfig {
+    #[serde(default = "default_rule")]
+    pub prod: Rule,
+    #[serde(default = "default_rule")]
+    pub dev: Rule,
+    /// Replacements of deprecated packages being migrated off, e.g.
+    /// `request = "got"`, to report which of the two each repository
+    /// installs when either is checked
+    #[serde(default)]
+    pub migrations: BTreeMap<String, String>,
+}
+
+fn default_rule() -> Rule {
+    Rule::Severity(Severity::Fail)
+}
+
+/// GitHub App registered on the instance of the repositories not prefixed
+/// with a host, authenticated with its installation tokens
+#[derive(Deserialize, Debug, Clone)]
+pub struct AppConfig {
+    pub app_id: u64,
+    /// Path of the PEM private key generated for the app
+    pub private_key: String,
+    /// Installation to check the repositories of; needed when the app is
+    /// installed on several accounts
+    pub installation_id: Option<u64>,
+}
+
+/// Jira integration. Summary, description and labels are templates where
+/// {repo}, {package}, {version} and {problems} are replaced.
+#[derive(Deserialize, Debug, Clone)]
+pub struct JiraConfig {
+    /// Base URL, e.g. https://example.atlassian.net
+    pub url: String,
+    /// Key of the project issues are created in
+    pub project: String,
+    #[serde(default = "default_issue_type")]
+    pub issue_type: String,
+    #[serde(default = "default_summary")]
+    pub summary: String,
+    #[serde(default = "default_description")]
+    pub description: String,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Account e-mail for basic authentication with the JIRA_TOKEN API
+    /// token (Jira Cloud); without it JIRA_TOKEN is sent as a bearer
+    /// personal access token (Jira Data Center)
+    pub user: Option<String>,
+}
+
+fn default_issue_type() -> String {
+    "Task".to_string()
+}
+
+fn default_summary() -> String {
+    "Update {package} in {repo}".to_string()
+}
+
+fn default_description() -> String {
+    "{repo} locks {package} {version}:\n\n{problems}".to_string()
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct TransportConfig {
+    /// Send every request through this Unix domain socket (e.g. a sidecar
+    /// proxy) instead of connecting to the request's host over TCP
+    pub unix_socket: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Only speak HTTP/2 (the default); disable for proxies that only
+    /// accept HTTP/1.1
+    pub http2_only: Option<bool>,
+
+    /// Maximum number of idle connections kept per host
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Seconds an idle connection is kept in the pool
+    pub pool_idle_timeout_secs: Option<u64>,
+
+    /// Interval in seconds of HTTP/2 keep-alive pings
+    pub http2_keep_alive_interval_secs: Option<u64>,
+
+    /// Seconds to wait for a keep-alive ping acknowledgement
+    pub http2_keep_alive_timeout_secs: Option<u64>,
+
+    /// Send keep-alive pings on connections without open streams
+    pub http2_keep_alive_while_idle: Option<bool>,
+
+    /// User-Agent sent with every request instead of check-versions, so
+    /// that proxies can attribute the traffic
+    pub user_agent: Option<String>,
+
+    /// Value of an X-Request-Source header sent with every request, e.g.
+    /// the team or CI job running the checks
+    pub request_source: Option<String>,
+
+    /// Times a request is retried after a server error, a dropped
+    /// connection or rate limiting; 0 disables retries
+    pub max_retries: Option<u32>,
+
+    /// Longest wait in seconds before a retry; requests rate limited for
+    /// longer fail right away
+    pub max_retry_wait_secs: Option<u64>,
+
+    /// X-GitHub-Api-Version sent to the GitHub APIs instead of 2022-11-28;
+    /// empty to not send it
+    pub api_version: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct HostConfig {
+    /// Extra headers sent with every request to the host, e.g. API keys
+    /// required by a corporate gateway
+    pub headers: HashMap<String, String>,
+
+    /// Fixed address to connect to instead of resolving the host via DNS
+    pub resolve: Option<IpAddr>,
+
+    /// X-GitHub-Api-Version sent to the host instead of the one of [http],
+    /// e.g. an older one for a GitHub Enterprise Server lagging behind
+    /// github.com; empty to not send it
+    pub api_version: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file given with --config, or else the first of
+    /// ./check-versions.toml and ~/.config/check-versions/check-versions.toml
+    /// that exists. Only the [defaults] but api_url and the [policy] of
+    /// ./check-versions.toml are read.
+    pub fn load(path: Option<&str>) -> Result<Config> {
+        let (path, trusted) = match path.map(PathBuf::from) {
+            Some(path) => (path, true),
+            None => match discover() {
+                Some(found) => found,
+                None => return Ok(Config::default()),
+            },
+        };
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("Unable to read config file {}: {}", path.display(), e))?;
+        let invalid = |e: toml::de::Error| format!("Invalid config file {}: {}", path.display(), e);
+        if trusted {
+            return Ok(toml::from_str(&data).map_err(invalid)?);
+        }
+        let mut table: toml::value::Table = toml::from_str(&data).map_err(invalid)?;
+        for ignored in project_only(&mut table) {
+            warn!("Ignoring {} in {}, only read from --config or ~/.config/check-versions", ignored, path.display());
+        }
+        Ok(toml::Value::Table(table).try_into().map_err(invalid)?)
+    }
+}
+
+/// Finds the config file used when --config isn't given, and whether it's
+/// the user's own rather than one in the working directory
+fn discover() -> Option<(PathBuf, bool)> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let candidates = [Some((PathBuf::from(FILE_NAME), false)), config_home.map(|dir| (dir.join("check-versions").join(FILE_NAME), true))];
+    candidates.into_iter().flatten().find(|(path, _)| path.is_file())
+}
+
+/// Removes the settings of a config file in the working directory that
+/// aren't read from it, returning their names
+fn project_only(table: &mut toml::value::Table) -> Vec<String> {
+    let sections: Vec<String> = table.keys().filter(|key| !PROJECT_SECTIONS.contains(&key.as_str())).cloned().collect();
+    let mut ignored = Vec::new();
+    for section in sections {
+        table.remove(&section);
+        ignored.push(format!("[{}]", section));
+    }
+    if let Some(toml::Value::Table(defaults)) = table.get_mut("defaults") {
+        if defaults.remove("api_url").is_some() {
+            ignored.push("api_url of [defaults]".to_string());
+        }
+    }
+    ignored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [defaults]
+            repos = "repos.json"
+            package = ["express", "lodash"]
+            format = "email-text"
+            concurrency = 20
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.defaults.repos.as_deref(), Some("repos.json"));
+        assert_eq!(config.defaults.package, ["express", "lodash"]);
+        assert_eq!(config.defaults.format, Some(Format::EmailText));
+        assert_eq!(config.defaults.concurrency, Some(20));
+        assert_eq!(config.defaults.api_url, None);
+    }
+
+    #[test]
+    fn reads_only_defaults_and_policy_from_the_working_directory() {
+        let mut table: toml::value::Table = toml::from_str(
+            r#"
+            [defaults]
+            package = ["express"]
+            api_url = "https://attacker.example/api/v3"
+
+            [policy]
+            dev = "warn"
+
+            [transport]
+            unix_socket = "/tmp/proxy.sock"
+
+            [hosts."api.github.com"]
+            resolve = "203.0.113.7"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(project_only(&mut table), ["[hosts]", "[transport]", "api_url of [defaults]"]);
+
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.defaults.package, ["express"]);
+        assert_eq!(config.defaults.api_url, None);
+        assert_eq!(config.policy.map(|policy| policy.dev), Some(Rule::Severity(Severity::Warn)));
+        assert!(config.hosts.is_empty());
+        assert_eq!(config.transport.unix_socket, None);
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::debug;
+use tokio::process::Command;
+use tokio::sync::OnceCell;
+
+use crate::app::App;
+
+/// Environment variables holding the token for github.com, in the order
+/// they're read; GitHub Actions sets GITHUB_TOKEN
+const GITHUB_VARS: [&str; 2] = ["GITHUB_TOKEN", "GHP_TOKEN"];
+
+/// Environment variable holding the token for GitHub Enterprise Server
+const GHES_VAR: &str = "GHES_TOKEN";
+
+/// Environment variable holding comma-separated tokens to share the
+/// requests to the API of the default host between, when --token isn't
+/// given
+pub const TOKENS_VAR: &str = "GITHUB_TOKENS";
+
+/// Where the token of an API host was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Env(&'static str),
+    GhCli,
+}
+
+/// Token of each API host, resolved on first use from --token, then the
+/// environment, then the token stored by `gh auth login`. Hosts without
+/// any are sent unauthenticated requests, which only read public
+/// repositories.
+#[derive(Clone, Default)]
+pub struct Credentials {
+    /// --token, only sent to the API of the default host
+    flag: Option<(String, String)>,
+    /// Tokens sharing the requests to the API of the default host, when
+    /// --token is given several times
+    pool: Option<(String, Arc<TokenPool>)>,
+    /// GitHub App authenticating the requests to the API of the default
+    /// host instead of a token
+    app: Option<(String, Arc<App>)>,
+    /// Token of each API host once resolved, by the first request to it
+    /// while the others wait
+    resolved: Arc<Mutex<HashMap<String, Resolved>>>,
+}
+
+/// Token of an API host, none once resolved to send unauthenticated
+/// requests
+type Resolved = Arc<OnceCell<Option<String>>>;
+
+impl Credentials {
+    /// Credentials with the tokens given on the command line, or else in
+    /// GITHUB_TOKENS, for the API host of the repositories not prefixed
+    /// with one. Several tokens take turns.
+    pub fn new(tokens: Vec<String>, default_api_host: &str) -> Credentials {
+        let mut tokens: Vec<String> = tokens.into_iter().filter(|token| !token.is_empty()).collect();
+        if tokens.is_empty() {
+            let listed = env::var(TOKENS_VAR).unwrap_or_default();
+            tokens = listed.split(',').map(str::trim).filter(|token| !token.is_empty()).map(str::to_string).collect();
+        }
+        let (flag, pool) = match tokens.len() {
+            0 => (None, None),
+            1 => (tokens.pop().map(|token| (default_api_host.to_string(), token)), None),
+            _ => (None, Some((default_api_host.to_string(), Arc::new(TokenPool::new(tokens))))),
+        };
+        Credentials { flag, pool, app: None, resolved: Arc::default() }
+    }
+
+    /// Authenticates the requests to an API host as a GitHub App
+    pub fn with_app(self, api_host: &str, app: App) -> Credentials {
+        Credentials { app: Some((api_host.to_string(), Arc::new(app))), ..self }
+    }
+
+    /// GitHub App authenticating the requests to an API host, if any
+    pub fn app(&self, api_host: &str) -> Option<&Arc<App>> {
+        self.app.as_ref().filter(|(host, _)| host == api_host).map(|(_, app)| app)
+    }
+
+    /// Tokens taking turns for the requests to an API host, if any
+    pub fn pool(&self, api_host: &str) -> Option<&Arc<TokenPool>> {
+        self.pool.as_ref().filter(|(host, _)| host == api_host).map(|(_, pool)| pool)
+    }
+
+    /// Token for an API host, none to send unauthenticated requests
+    pub async fn token(&self, api_host: &str) -> Option<String> {
+        let cell = self.resolved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(api_host.to_string())
+            .or_default()
+            .clone();
+        let flag = self.flag.as_ref().filter(|(host, _)| host == api_host).map(|(_, token)| token.as_str());
+        let token = cell.get_or_init(|| async {
+            match resolve(api_host, flag, |name| env::var(name).ok(), gh_token).await {
+                Some((token, source)) => {
+                    debug!("Using the token of {:?} for {}", source, api_host);
+                    Some(token)
+                }
+                None => {
+                    eprintln!("No token found for {} (tried {}), sending unauthenticated requests which only read public repositories", api_host, tried(api_host));
+                    None
+                }
+            }
+        });
+        token.await.clone()
+    }
+}
+
+/// Tokens sharing the requests to an API host, for organizations too
+/// large to sweep within the hourly rate limit of one token. Each request
+/// is sent with the next token in turn which has requests left, going by
+/// the rate limit the last response to each told.
+pub struct TokenPool {
+    tokens: Vec<String>,
+    turns: Mutex<Turns>,
+}
+
+struct Turns {
+    /// Rate limit of each token
+    limits: Vec<Limit>,
+    /// Token to try first for the next request
+    next: usize,
+}
+
+/// Rate limit of a token of a pool, as of the last response to it
+#[derive(Debug, Clone, Copy, Default)]
+struct Limit {
+    /// Requests left, none until a response told
+    remaining: Option<u64>,
+    /// Seconds since the epoch at which the limit resets
+    reset: u64,
+}
+
+impl Limit {
+    /// Whether requests may be sent with the token: it has some left or
+    /// its limit was reset since
+    fn available(&self, now: u64) -> bool {
+        self.remaining != Some(0) || self.reset <= now
+    }
+}
+
+impl TokenPool {
+    pub fn new(tokens: Vec<String>) -> TokenPool {
+        let limits = vec![Limit::default(); tokens.len()];
+        TokenPool { tokens, turns: Mutex::new(Turns { limits, next: 0 }) }
+    }
+
+    /// Picks the next token which has requests left, or when none has, the
+    /// one whose limit resets first, with its index to record the rate
+    /// limit of the response with
+    pub fn pick(&self, now: u64) -> (usize, &str) {
+        let mut turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+        let available = (0..self.tokens.len())
+            .map(|offset| (turns.next + offset) % self.tokens.len())
+            .find(|&index| turns.limits[index].available(now));
+        let index = available.unwrap_or_else(|| {
+            (0..self.tokens.len()).min_by_key(|&index| turns.limits[index].reset).unwrap_or_default()
+        });
+        turns.next = index + 1;
+        (index, &self.tokens[index])
+    }
+
+    /// Records the rate limit a response to a token told
+    pub fn update(&self, index: usize, remaining: u64, reset: Option<u64>) {
+        let mut turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+        let limit = &mut turns.limits[index];
+        limit.remaining = Some(remaining);
+        if let Some(reset) = reset {
+            limit.reset = reset;
+        }
+    }
+
+    /// Whether a token other than the given one has requests left
+    pub fn has_other(&self, index: usize, now: u64) -> bool {
+        let turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+        turns.limits.iter().enumerate().any(|(other, limit)| other != index && limit.available(now))
+    }
+
+    /// Requests left across the tokens whose limit is known
+    pub fn remaining(&self) -> u64 {
+        let turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+        turns.limits.iter().filter_map(|limit| limit.remaining).sum()
+    }
+}
+
+/// Environment variables holding the token for an API host
+fn env_vars(api_host: &str) -> &'static [&'static str] {
+    if api_host == "api.github.com" {
+        &GITHUB_VARS
+    } else {
+        std::slice::from_ref(&GHES_VAR)
+    }
+}
+
+/// Host `gh` knows an API host by: github.com for api.github.com, the API
+/// host itself for GitHub Enterprise Server
+fn gh_host(api_host: &str) -> &str {
+    if api_host == "api.github.com" {
+        "github.com"
+    } else {
+        api_host
+    }
+}
+
+/// Lists the sources looked for a token, for messages about a missing one
+pub fn tried(api_host: &str) -> String {
+    let mut tried = vec!["--token".to_string()];
+    tried.extend(env_vars(api_host).iter().map(|name| name.to_string()));
+    tried.push(format!("`gh auth token --hostname {}`", gh_host(api_host)));
+    tried.join(", ")
+}
+
+async fn resolve<F: Future<Output = Option<String>>>(
+    api_host: &str,
+    flag: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+    gh: impl FnOnce(String) -> F,
+) -> Option<(String, Source)> {
+    if let Some(token) = flag {
+        return Some((token.to_string(), Source::Flag));
+    }
+    let from_env = env_vars(api_host)
+        .iter()
+        .find_map(|name| var(name).filter(|token| !token.is_empty()).map(|token| (token, Source::Env(name))));
+    match from_env {
+        Some(found) => Some(found),
+        None => gh(gh_host(api_host).to_string()).await.map(|token| (token, Source::GhCli)),
+    }
+}
+
+/// Token stored by `gh auth login` for a host, none when gh isn't
+/// installed or isn't logged in to it
+async fn gh_token(host: String) -> Option<String> {
+    let output = Command::new("gh")
+        .args(["auth", "token", "--hostname", &host])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    let token = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !token.is_empty()).then_some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolves_tokens_in_order() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        let gh = |host: String| async move { (host == "github.com").then(|| "gho_cli".to_string()) };
+        let no_gh = |_: String| async { None };
+
+        let both = env(&[("GITHUB_TOKEN", "ghs_actions"), ("GHP_TOKEN", "ghp_personal")]);
+        assert_eq!(resolve("api.github.com", Some("ghp_flag"), both, gh).await, Some(("ghp_flag".to_string(), Source::Flag)));
+        assert_eq!(resolve("api.github.com", None, both, gh).await, Some(("ghs_actions".to_string(), Source::Env("GITHUB_TOKEN"))));
+        let personal = env(&[("GITHUB_TOKEN", ""), ("GHP_TOKEN", "ghp_personal")]);
+        assert_eq!(resolve("api.github.com", None, personal, gh).await, Some(("ghp_personal".to_string(), Source::Env("GHP_TOKEN"))));
+        assert_eq!(resolve("api.github.com", None, env(&[]), gh).await, Some(("gho_cli".to_string(), Source::GhCli)));
+        assert_eq!(resolve("api.github.com", None, env(&[]), no_gh).await, None);
+
+        // github.com tokens aren't sent to GitHub Enterprise Server
+        assert_eq!(resolve("ghe.example.com", None, both, gh).await, None);
+        assert_eq!(resolve("ghe.example.com", None, env(&[("GHES_TOKEN", "ghes")]), gh).await, Some(("ghes".to_string(), Source::Env("GHES_TOKEN"))));
+        assert_eq!(tried("ghe.example.com"), "--token, GHES_TOKEN, `gh auth token --hostname ghe.example.com`");
+    }
+
+    #[test]
+    fn takes_turns_between_tokens_with_requests_left() {
+        let pool = TokenPool::new(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        let picked: Vec<&str> = (0..4).map(|_| pool.pick(1000).1).collect();
+        assert_eq!(picked, ["a", "b", "c", "a"]);
+
+        pool.update(1, 0, Some(1600));
+        let picked: Vec<&str> = (0..4).map(|_| pool.pick(1000).1).collect();
+        assert_eq!(picked, ["c", "a", "c", "a"]);
+        assert!(pool.has_other(1, 1000));
+
+        // Once every token is exhausted, the one resetting first is used
+        pool.update(0, 0, Some(1800));
+        pool.update(2, 0, Some(1700));
+        assert!(!pool.has_other(1, 1000));
+        assert_eq!(pool.pick(1000).1, "b");
+        assert_eq!(pool.pick(1650).1, "b");
+        assert_eq!(pool.remaining(), 0);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use clap::{ArgEnum, Args};
+
+use crate::errors::Error;
+use crate::github::{self, GitHub, Repository};
+use crate::telemetry::Tracer;
+use crate::Result;
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON list of repositories, as read by --repos
+    Json,
+    /// One repository per line, as read by --repos -
+    Lines,
+}
+
+/// Which repositories of an organization to keep
+#[derive(Args, Debug, Clone, Default)]
+pub struct Filter {
+    /// Only keep repositories tagged with this topic (can be repeated)
+    #[clap(short, long)]
+    pub topic: Vec<String>,
+
+    /// Only keep repositories whose primary language is this one, e.g.
+    /// javascript
+    #[clap(long)]
+    pub language: Option<String>,
+
+    /// Keep archived repositories
+    #[clap(long)]
+    pub include_archived: bool,
+}
+
+impl Filter {
+    fn matches(&self, repo: &Repository) -> bool {
+        let language = match (&self.language, &repo.language) {
+            (Some(wanted), Some(language)) => wanted.eq_ignore_ascii_case(language),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        (self.include_archived || !repo.archived)
+            && self.topic.iter().all(|topic| repo.topics.contains(topic))
+            && language
+    }
+}
+
+/// Lists the repositories of an organization kept by a filter, prefixed
+/// with the host unless it's the default one
+pub async fn org_repos(github: &GitHub, host: &str, org: &str, filter: &Filter) -> Result<Vec<String>> {
+    Ok(github.org_repos(host, org)
+        .await?
+        .into_iter()
+        .filter(|repo| filter.matches(repo))
+        .map(|repo| github::qualified_name(host, &repo.full_name))
+        .collect())
+}
+
+/// Discover repositories of an organization and print them as a repos list
+#[derive(Args, Debug, Clone)]
+pub struct DiscoverArgs {
+    /// Organization to list repositories of
+    #[clap(short, long)]
+    org: String,
+
+    /// GitHub host of the organization, e.g. a GitHub Enterprise Server;
+    /// defaults to the host of --api-url, or github.com
+    #[clap(long)]
+    host: Option<String>,
+
+    #[clap(flatten)]
+    filter: Filter,
+
+    /// Output format of the repository list
+    #[clap(short, long, arg_enum, default_value = "json")]
+    format: Format,
+
+    /// Update this list of repositories, one per line, in place instead of
+    /// printing the list: comments and annotations are kept, new
+    /// repositories are appended marked `# added` and the repositories of
+    /// the organization which are gone are commented out as `# removed:`
+    #[clap(long, conflicts_with = "format")]
+    update: Option<String>,
+}
+
+/// Mark of the repositories appended by --update
+const ADDED: &str = "# added";
+
+/// Prefix of the entries commented out by --update
+const REMOVED: &str = "# removed: ";
+
+/// Repository entry of a line of a repos list, none for blank lines and
+/// comments
+fn entry(line: &str) -> Option<&str> {
+    let entry = line.split('#').next().unwrap_or_default().trim();
+    (!entry.is_empty()).then_some(entry)
+}
+
+/// Reads a list of repositories with one per line, as written by
+/// --update, skipping blank lines and `#` comments
+pub(crate) fn read_lines(data: &str) -> Vec<String> {
+    data.lines().filter_map(entry).map(str::to_string).collect()
+}
+
+/// Repository of an entry, without its ref or directory, to compare
+/// entries case-insensitively
+fn key(entry: &str) -> (String, String) {
+    let (host, full_name) = github::parse_repo(entry);
+    (host.to_lowercase(), full_name.to_lowercase())
+}
+
+/// Updates a list of repositories with one per line to the ones
+/// discovered in an organization. Returns the new list and how many
+/// repositories were added and removed.
+fn update(data: &str, host: &str, org: &str, discovered: &[String]) -> (String, usize, usize) {
+    let discovered_keys: BTreeSet<_> = discovered.iter().map(|repo| key(repo)).collect();
+    let org_prefix = format!("{}/", org.to_lowercase());
+    let of_org = |(entry_host, full_name): &(String, String)| entry_host.eq_ignore_ascii_case(host) && full_name.starts_with(&org_prefix);
+
+    let mut listed = BTreeSet::new();
+    let (mut added, mut removed) = (0, 0);
+    let mut lines = Vec::new();
+    for line in data.lines() {
+        // Entries removed by an earlier update come back when the
+        // repository is discovered again
+        if let Some(previous) = line.strip_prefix(REMOVED) {
+            match entry(previous).map(key) {
+                Some(previous_key) if discovered_keys.contains(&previous_key) && listed.insert(previous_key.clone()) => {
+                    lines.push(format!("{}  {}", previous.trim_end(), ADDED));
+                    added += 1;
+                }
+                _ => lines.push(line.to_string()),
+            }
+            continue;
+        }
+        let entry_key = match entry(line).map(key) {
+            Some(entry_key) => entry_key,
+            None => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+        if of_org(&entry_key) && !discovered_keys.contains(&entry_key) {
+            lines.push(format!("{}{}", REMOVED, line.strip_suffix(ADDED).unwrap_or(line).trim_end()));
+            removed += 1;
+        } else {
+            // The marks of the previous update were reviewed along with it
+            lines.push(line.strip_suffix(ADDED).unwrap_or(line).trim_end().to_string());
+            listed.insert(entry_key);
+        }
+    }
+    for repo in discovered {
+        if listed.insert(key(repo)) {
+            lines.push(format!("{}  {}", repo, ADDED));
+            added += 1;
+        }
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    (text, added, removed)
+}
+
+pub async fn run(args: DiscoverArgs, github: GitHub, tracer: &Tracer) -> Result<()> {
+    let mut span = tracer.span("discover", None);
+    span.attribute("org", &args.org);
+    let host = match &args.host {
+        Some(host) => host,
+        None => github::default_host(),
+    };
+    let repos = org_repos(&github, host, &args.org, &args.filter)
+        .await
+        .inspect_err(|e| span.fail(e))?;
+    span.attribute("repositories", repos.len());
+    drop(span);
+
+    if let Some(path) = &args.update {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(source) => return Err(Error::Read { path: path.clone(), source }.into()),
+        };
+        let (text, added, removed) = update(&data, host, &args.org, &repos);
+        fs::write(path, text).map_err(|source| Error::Write { path: path.clone(), source })?;
+        eprintln!("Added {} and removed {} repositories in {}", added, removed, path);
+        return Ok(());
+    }
+
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&repos)?),
+        Format::Lines => {
+            for repo in repos {
+                println!("{}", repo);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_lists_keeping_annotations() {
+        let data = "\
+# Services owned by the platform team
+org/api  # owner: platform
+org/worker@release/2.0
+org/legacy  # sunset in Q3
+other/shared
+# removed: org/revived  # owner: web
+org/web  # added
+";
+        let discovered = ["org/api".to_string(), "org/worker".to_string(), "org/revived".to_string(), "org/web".to_string(), "org/new".to_string()];
+        let (text, added, removed) = update(data, "github.com", "org", &discovered);
+        assert_eq!(text, "\
+# Services owned by the platform team
+org/api  # owner: platform
+org/worker@release/2.0
+# removed: org/legacy  # sunset in Q3
+other/shared
+org/revived  # owner: web  # added
+org/web
+org/new  # added
+");
+        assert_eq!((added, removed), (2, 1));
+        assert_eq!(read_lines(&text), ["org/api", "org/worker@release/2.0", "other/shared", "org/revived", "org/web", "org/new"]);
+    }
+}
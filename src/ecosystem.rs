@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{gomod, lockfile, pnpmlock, requirements, yarnlock};
+
+/// Package ecosystem, deciding which manifest is read from a repository
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    Npm,
+    Go,
+    Python,
+}
+
+/// Ecosystem selected on the command line
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcosystemArg {
+    /// Detect the ecosystem of each repository from its languages
+    Auto,
+    Npm,
+    Go,
+    Python,
+}
+
+impl EcosystemArg {
+    /// Returns the ecosystem to use for every repository, none when it has
+    /// to be detected per repository
+    pub fn fixed(self) -> Option<Ecosystem> {
+        match self {
+            EcosystemArg::Auto => None,
+            EcosystemArg::Npm => Some(Ecosystem::Npm),
+            EcosystemArg::Go => Some(Ecosystem::Go),
+            EcosystemArg::Python => Some(Ecosystem::Python),
+        }
+    }
+}
+
+impl Ecosystem {
+    /// Every ecosystem, in the order summaries list them
+    pub const ALL: [Ecosystem; 3] = [Ecosystem::Npm, Ecosystem::Go, Ecosystem::Python];
+
+    /// Path of the manifest listing the versions of the ecosystem
+    pub fn manifest(self) -> &'static str {
+        match self {
+            Ecosystem::Npm => "package-lock.json",
+            Ecosystem::Go => "go.mod",
+            Ecosystem::Python => "requirements.txt",
+        }
+    }
+
+    /// Paths of the manifests to look for in turn when the repository
+    /// doesn't have the usual one, e.g. the lockfiles of other package
+    /// managers, and lastly package.json when it has no lockfile at all
+    pub fn fallback_manifests(self) -> &'static [&'static str] {
+        match self {
+            Ecosystem::Npm => &[yarnlock::MANIFEST, pnpmlock::MANIFEST, lockfile::PACKAGE_JSON],
+            Ecosystem::Go | Ecosystem::Python => &[],
+        }
+    }
+
+    fn of_language(language: &str) -> Option<Ecosystem> {
+        match language {
+            "JavaScript" | "TypeScript" => Some(Ecosystem::Npm),
+            "Go" => Some(Ecosystem::Go),
+            "Python" => Some(Ecosystem::Python),
+            _ => None,
+        }
+    }
+
+    /// Picks the ecosystem of the supported language with the most code,
+    /// given the bytes per language of a repository
+    pub fn detect(languages: &HashMap<String, u64>) -> Option<Ecosystem> {
+        let mut supported: HashMap<Ecosystem, u64> = HashMap::new();
+        for (language, bytes) in languages {
+            if let Some(ecosystem) = Ecosystem::of_language(language) {
+                *supported.entry(ecosystem).or_default() += bytes;
+            }
+        }
+
+        supported
+            .into_iter()
+            .max_by_key(|(_, bytes)| *bytes)
+            .map(|(ecosystem, _)| ecosystem)
+    }
+
+    /// Finds the version of a package in the manifest of the ecosystem.
+    /// Returns an error message when the body isn't a valid manifest.
+    pub fn find_version(self, body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+        match self {
+            Ecosystem::Npm => lockfile::find_version(body, package_name),
+            Ecosystem::Go => gomod::find_version(body, package_name),
+            Ecosystem::Python => requirements::find_version(body, package_name),
+        }
+    }
+
+    /// Finds the version of a package in a manifest of the ecosystem given
+    /// its path, which may be one of the fallback manifests
+    pub fn find_version_in(self, path: &str, body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+        match path.rsplit('/').next() {
+            Some(yarnlock::MANIFEST) => yarnlock::find_version(body, package_name),
+            Some(pnpmlock::MANIFEST) => pnpmlock::find_version(body, package_name),
+            _ => self.find_version(body, package_name),
+        }
+    }
+
+    /// Whether a manifest of the ecosystem is valid, given its path
+    pub fn parses(self, path: &str, body: &[u8]) -> bool {
+        match path.rsplit('/').next() {
+            Some(lockfile::PACKAGE_JSON) => lockfile::package_json_range(body, "").is_ok(),
+            _ => self.find_version_in(path, body, "").is_ok(),
+        }
+    }
+}
+
+/// Manifest of an ecosystem looked for in a repository, listed with
+/// --ecosystem-summary to tell the ecosystems a repository doesn't use
+/// from the manifests that couldn't be read
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestSummary {
+    pub ecosystem: Ecosystem,
+    /// Path of the manifest found, none when the repository has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+    /// Whether the manifest found is valid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<bool>,
+    /// Whether the versions were read from this manifest
+    #[serde(default)]
+    pub checked: bool,
+}
+
+impl ManifestSummary {
+    /// e.g. "npm yarn.lock (checked)", "go go.mod (invalid)" or "python
+    /// none"
+    pub fn describe(&self) -> String {
+        let ecosystem = format!("{:?}", self.ecosystem).to_lowercase();
+        match (&self.manifest, self.parsed) {
+            (None, _) => format!("{} none", ecosystem),
+            (Some(manifest), Some(false)) => format!("{} {} (invalid)", ecosystem, manifest),
+            (Some(manifest), _) if self.checked => format!("{} {} (checked)", ecosystem, manifest),
+            (Some(manifest), _) => format!("{} {}", ecosystem, manifest),
+        }
+    }
+}
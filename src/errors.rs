@@ -0,0 +1,83 @@
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ecosystem::ManifestSummary;
+use crate::github::Access;
+use crate::redact;
+use crate::Result;
+
+/// Errors that abort a run, reported with their message instead of a panic
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unable to read {path}: {source}")]
+    Read { path: String, source: io::Error },
+    #[error("Unable to write {path}: {source}")]
+    Write { path: String, source: io::Error },
+    #[error("{path} does not have the expected JSON format: {source}")]
+    Json { path: String, source: serde_json::Error },
+    #[error("Invalid URL {uri:?}: {source}")]
+    Uri { uri: String, source: hyper::http::uri::InvalidUri },
+    #[error("No token found for {host} (tried {tried})")]
+    MissingToken { host: String, tried: String },
+    #[error("{host} doesn't support the API version {version} sent as X-GitHub-Api-Version ({message}), set api_version in [hosts.\"{host}\"] of the config file to one it does")]
+    ApiVersion { host: String, version: String, message: String },
+    #[error("Unable to authenticate as the GitHub App: {0}")]
+    App(String),
+    #[error("Invalid request: {0}")]
+    Request(#[from] hyper::http::Error),
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+}
+
+/// Step of the pipeline at which a repository failed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// The request failed or returned a non-success status
+    Fetch,
+    /// The response body isn't a valid lockfile
+    Parse,
+    /// The worker task panicked, timed out or was cancelled
+    Task,
+}
+
+/// A repository that couldn't be checked, as written to --errors-file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Failure {
+    pub repo: String,
+    pub stage: Stage,
+    pub status: Option<u16>,
+    pub message: String,
+    /// URL of the manifest which answered an unsuccessful status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// Why the manifest couldn't be read, when it answered 403 or 404
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<Access>,
+    /// Manifest of each ecosystem found in the repository, with
+    /// --ecosystem-summary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecosystems: Option<Vec<ManifestSummary>>,
+}
+
+impl Failure {
+    pub fn new(repo: &str, stage: Stage, status: Option<u16>, message: impl Into<String>) -> Failure {
+        // Messages hold response bodies and end up in logs and errors files
+        let message = redact::text(&message.into());
+        Failure { repo: repo.to_string(), stage, status, message, uri: None, access: None, ecosystems: None }
+    }
+}
+
+pub fn read(path: &str) -> Result<Vec<Failure>> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read errors file {}: {}", path, e))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+pub fn write(path: &str, failures: &[Failure]) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(failures)?)
+        .map_err(|e| format!("Unable to write errors file {}: {}", path, e))?;
+    Ok(())
+}
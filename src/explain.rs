@@ -0,0 +1,115 @@
+use clap::Args;
+
+use crate::ecosystem::{Ecosystem, EcosystemArg};
+use crate::github::{self, GitHub};
+use crate::lockfile;
+use crate::redact;
+use crate::Result;
+
+/// Check a single repository and print every step leading to its result
+#[derive(Args, Debug, Clone)]
+pub struct ExplainArgs {
+    /// Repository to check, e.g. org/repo or host/org/repo
+    repo: String,
+
+    /// Name of the package to look up
+    #[clap(short, long)]
+    package: String,
+
+    /// Ecosystem of the manifest to read
+    #[clap(long, arg_enum, default_value = "auto")]
+    ecosystem: EcosystemArg,
+}
+
+pub async fn run(args: ExplainArgs, github: GitHub) -> Result<()> {
+    let (host, full_name) = github::parse_repo(&args.repo);
+    println!("repository: {}/{}", host, full_name);
+    println!("package: {}", args.package);
+
+    let ecosystem = match args.ecosystem.fixed() {
+        Some(ecosystem) => {
+            println!("ecosystem: {:?} (set by --ecosystem)", ecosystem);
+            ecosystem
+        }
+        None => {
+            let languages = github.languages(host, full_name).await?;
+            let mut listed: Vec<_> = languages.iter().collect();
+            listed.sort_by(|a, b| b.1.cmp(a.1));
+            let listed: Vec<_> = listed.iter().map(|(language, bytes)| format!("{} {}", language, bytes)).collect();
+            match Ecosystem::detect(&languages) {
+                Some(ecosystem) => {
+                    println!("ecosystem: {:?} (detected from languages: {})", ecosystem, listed.join(", "));
+                    ecosystem
+                }
+                None => {
+                    println!("ecosystem: none (languages: {})", if listed.is_empty() { "none".to_string() } else { listed.join(", ") });
+                    println!("why: no supported language in the repository, so check reports it without a version");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    let git_ref = github::repo_ref(&args.repo).unwrap_or("HEAD");
+    let sha = github.commit_sha(host, full_name, git_ref).await?;
+    println!("ref: {}", git_ref);
+    println!("sha: {}", sha);
+
+    let manifest = match github::repo_dir(&args.repo) {
+        Some(dir) => format!("{}/{}", dir, ecosystem.manifest()),
+        None => ecosystem.manifest().to_string(),
+    };
+    let uri = github::contents_uri(host, full_name, &manifest, Some(&sha));
+    println!("url: {}", uri);
+    let (status, body) = github.get(&uri, "application/vnd.github.raw").await?;
+    println!("status: {}", status);
+    if !status.is_success() {
+        let message = redact::text(&String::from_utf8_lossy(&body));
+        println!("response: {}", message.trim());
+        if status == 404 {
+            println!("why: {} doesn't exist at {}, so check reports the repository as missing it", manifest, sha);
+        } else {
+            println!("why: GitHub answered {}, so check reports the repository as failed", status);
+        }
+        return Ok(());
+    }
+
+    if ecosystem != Ecosystem::Npm {
+        println!("manifest: {} ({} bytes)", manifest, body.len());
+        println!("probed: entries of {} named {:?}", manifest, args.package);
+        match ecosystem.find_version(&body, &args.package) {
+            Ok(Some(version)) => {
+                println!("version: {}", version);
+                println!("why: {} lists {} at {}", manifest, args.package, version);
+            }
+            Ok(None) => {
+                println!("version: none");
+                println!("why: {} has no entry for {}", manifest, args.package);
+            }
+            Err(e) => println!("why: {} can't be parsed: {}", manifest, e),
+        }
+        return Ok(());
+    }
+
+    let explanation = match lockfile::explain(&body, &args.package) {
+        Ok(explanation) => explanation,
+        Err(e) => {
+            println!("manifest: {} ({} bytes)", manifest, body.len());
+            println!("why: {} can't be parsed: {}", manifest, e);
+            return Ok(());
+        }
+    };
+    let lockfile_version = explanation.lockfile_version.map(|version| version.to_string());
+    println!(
+        "manifest: {} ({} bytes), lockfile version {}",
+        manifest,
+        body.len(),
+        lockfile_version.as_deref().unwrap_or("missing")
+    );
+    for (key, version) in &explanation.probed {
+        println!("probed: {} -> {}", key, version.as_deref().unwrap_or("not found"));
+    }
+    println!("version: {}", explanation.version.as_deref().unwrap_or("none"));
+    println!("why: {}", explanation.reason);
+    Ok(())
+}
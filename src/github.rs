@@ -0,0 +1,1020 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::client::HttpConnector;
+use hyper::body::Bytes;
+use hyper::header::HeaderValue;
+use hyper::body::HttpBody;
+use hyper::{body, Body, Client, Method, Request, Response, StatusCode, Uri};
+use hyper_tls::HttpsConnector;
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+
+use crate::app::App;
+use crate::backoff;
+use crate::cache::Cache;
+use crate::config::{AppConfig, Config};
+use crate::credentials::{self, Credentials};
+use crate::errors::Error;
+use crate::redact;
+use crate::transport::{Connector, Resolver};
+use crate::url;
+use crate::Result;
+
+pub const API_URL: &str = "https://api.github.com";
+
+pub const HOST: &str = "github.com";
+
+/// Environment variable holding the API URL when --api-url isn't given;
+/// GitHub Actions sets it to the API of the instance running the workflow
+pub const API_URL_VAR: &str = "GITHUB_API_URL";
+
+/// Host of the repositories not prefixed with one and its API URL, when
+/// --api-url points at a GitHub Enterprise Server instead of github.com
+static DEFAULT_API: OnceLock<(String, String)> = OnceLock::new();
+
+const PER_PAGE: usize = 100;
+
+pub(crate) const USER_AGENT: &str = "check-versions";
+
+/// X-GitHub-Api-Version sent unless configured otherwise
+pub const API_VERSION: &str = "2022-11-28";
+
+pub type HttpClient = Client<Connector>;
+
+/// Response body written to a temporary file, removed when dropped
+#[derive(Debug)]
+pub struct Download {
+    path: PathBuf,
+    /// Directory only the user can enter holding the file, so that others
+    /// can't plant a symbolic link where it's written
+    _dir: TempDir,
+}
+
+impl Download {
+    fn new() -> std::result::Result<Download, Error> {
+        let dir = tempfile::Builder::new()
+            .prefix("check-versions-")
+            .permissions(fs::Permissions::from_mode(0o700))
+            .tempdir()
+            .map_err(|source| Error::Write { path: env::temp_dir().display().to_string(), source })?;
+        Ok(Download { path: dir.path().join("body"), _dir: dir })
+    }
+
+    /// Creates the file, failing rather than following whatever is there
+    async fn create(&self) -> std::io::Result<tokio::fs::File> {
+        tokio::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&self.path).await
+    }
+
+    pub fn open(&self) -> std::result::Result<File, Error> {
+        File::open(&self.path).map_err(|source| Error::Read { path: self.path.display().to_string(), source })
+    }
+
+    /// Reads the whole body into memory, for small ones such as errors
+    pub fn bytes(&self) -> std::result::Result<Bytes, Error> {
+        let bytes = fs::read(&self.path).map_err(|source| Error::Read { path: self.path.display().to_string(), source })?;
+        Ok(Bytes::from(bytes))
+    }
+
+    fn len(&self) -> u64 {
+        fs::metadata(&self.path).map_or(0, |metadata| metadata.len())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Repository {
+    pub full_name: String,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    #[serde(default)]
+    pub archived: bool,
+    /// Primary language, none when GitHub detected none
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Repository this one was generated from, only returned when fetching
+    /// a single repository
+    #[serde(default)]
+    pub template_repository: Option<Box<Repository>>,
+    /// Permissions of the token's user, only returned to authenticated
+    /// requests
+    #[serde(default)]
+    pub permissions: Option<Permissions>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Permissions {
+    #[serde(default)]
+    pub pull: bool,
+}
+
+/// Why a file of a repository couldn't be read, told apart from the
+/// repository's metadata since GitHub answers 404 for private
+/// repositories the token can't see
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// The repository doesn't exist, or is private and hidden from the token
+    Hidden,
+    /// The token sees the repository but may not read its contents
+    Denied,
+    /// The repository is readable and the file doesn't exist in it
+    Missing,
+}
+
+impl Access {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Access::Hidden => "repository not found or not visible to the token",
+            Access::Denied => "token may not read the repository's contents",
+            Access::Missing => "file not found in the repository",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TreeEntry {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Entry of a directory listing of the contents API
+#[derive(Deserialize, Debug)]
+pub struct ContentEntry {
+    pub name: String,
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Tree {
+    pub tree: Vec<TreeEntry>,
+    /// Whether the listing was cut short because the tree is too large
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    /// Markdown description, none when it's empty
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Signature {
+    date: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommitDetails {
+    committer: Signature,
+}
+
+#[derive(Deserialize, Debug)]
+struct Commit {
+    commit: CommitDetails,
+}
+
+/// Marks the requests to authenticate with an installation token of the
+/// GitHub App
+#[derive(Clone, Copy)]
+struct AppAuth;
+
+/// Marks the requests to authenticate with the next token of the pool
+#[derive(Clone, Copy)]
+struct PoolAuth;
+
+/// Marks the requests to authenticate with the token of their API host,
+/// which may take running `gh` to resolve
+#[derive(Clone, Copy)]
+struct TokenAuth;
+
+/// Credentials sent along a JSON request
+enum Auth<'a> {
+    GitHub,
+    None,
+    Header(&'a str),
+}
+
+/// HTTP client for the GitHub API, applying the per-host configuration to
+/// every request
+#[derive(Clone)]
+pub struct GitHub {
+    pub client: HttpClient,
+    config: Arc<Config>,
+    cache: Option<Arc<Cache>>,
+    credentials: Credentials,
+    /// X-RateLimit-Remaining of the last GitHub response, u64::MAX until
+    /// one is received
+    rate_limit_remaining: Arc<AtomicU64>,
+}
+
+impl GitHub {
+    pub fn new(config: Config) -> GitHub {
+        let connector = match &config.transport.unix_socket {
+            Some(path) => Connector::Unix(Arc::new(path.clone())),
+            None => {
+                let overrides = config.hosts
+                    .iter()
+                    .filter_map(|(host, host_config)| Some((host.clone(), host_config.resolve?)))
+                    .collect();
+                let mut http = HttpConnector::new_with_resolver(Resolver::new(overrides));
+                http.enforce_http(false);
+                Connector::Tcp(HttpsConnector::new_with_connector(http))
+            }
+        };
+
+        let http = &config.http;
+        let mut builder = Client::builder();
+        builder.http2_only(http.http2_only.unwrap_or(true));
+        if let Some(max_idle) = http.pool_max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(secs) = http.pool_idle_timeout_secs {
+            builder.pool_idle_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = http.http2_keep_alive_interval_secs {
+            builder.http2_keep_alive_interval(Duration::from_secs(secs));
+        }
+        if let Some(secs) = http.http2_keep_alive_timeout_secs {
+            builder.http2_keep_alive_timeout(Duration::from_secs(secs));
+        }
+        if let Some(while_idle) = http.http2_keep_alive_while_idle {
+            builder.http2_keep_alive_while_idle(while_idle);
+        }
+        let client = builder.build::<_, Body>(connector);
+
+        GitHub {
+            client,
+            config: Arc::new(config),
+            cache: None,
+            credentials: Credentials::default(),
+            rate_limit_remaining: Arc::new(AtomicU64::new(u64::MAX)),
+        }
+    }
+
+    /// Sends these tokens to the API of the repositories not prefixed with
+    /// a host instead of looking for one in the environment, taking turns
+    /// when there are several
+    pub fn with_tokens(self, tokens: Vec<String>) -> GitHub {
+        let credentials = Credentials::new(tokens, &default_api_host());
+        GitHub { credentials, ..self }
+    }
+
+    /// Authenticates the requests to the API of the repositories not
+    /// prefixed with a host as a GitHub App, with its installation tokens
+    pub fn with_app(self, app: &AppConfig) -> Result<GitHub> {
+        let app = App::new(app, api_url(default_host()))?;
+        let credentials = self.credentials.clone().with_app(&default_api_host(), app);
+        Ok(GitHub { credentials, ..self })
+    }
+
+    /// Caches the responses of GET requests with their ETag and revalidates
+    /// them on later runs
+    pub fn with_cache(self, cache: Cache) -> GitHub {
+        GitHub { cache: Some(Arc::new(cache)), ..self }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Requests left before the rate limit, as of the last response which
+    /// told it
+    pub fn rate_limit_remaining(&self) -> Option<u64> {
+        Some(self.rate_limit_remaining.load(Ordering::Relaxed)).filter(|remaining| *remaining != u64::MAX)
+    }
+
+    /// X-GitHub-Api-Version sent to an API host: the one of its [hosts]
+    /// section, else of [http], else API_VERSION; none when configured
+    /// empty
+    pub(crate) fn api_version(&self, api_host: &str) -> Option<&str> {
+        let configured = self.config.hosts.get(api_host).and_then(|host| host.api_version.as_deref());
+        let version = configured.or(self.config.http.api_version.as_deref()).unwrap_or(API_VERSION);
+        Some(version).filter(|version| !version.is_empty())
+    }
+
+    pub fn request(&self, uri: &str, accept: &str) -> std::result::Result<Request<Body>, Error> {
+        self.build_request(Method::GET, uri, accept, Body::empty(), true)
+    }
+
+    /// Builds a request without GitHub credentials, for other services
+    /// such as package registries
+    pub fn anonymous_request(&self, uri: &str, accept: &str) -> std::result::Result<Request<Body>, Error> {
+        self.build_request(Method::GET, uri, accept, Body::empty(), false)
+    }
+
+    fn build_request(
+        &self,
+        method: Method,
+        uri: &str,
+        accept: &str,
+        body: Body,
+        authenticate: bool,
+    ) -> std::result::Result<Request<Body>, Error> {
+        let uri: Uri = uri.parse().map_err(|source| Error::Uri { uri: uri.to_string(), source })?;
+        let api_host = uri.host().unwrap_or_default().to_string();
+        // Older GitHub Enterprise Server releases only know the media types
+        // naming the API version
+        let accept = match accept {
+            "application/vnd.github.raw" if authenticate && api_host != "api.github.com" => "application/vnd.github.v3.raw",
+            "application/vnd.github.sha" if authenticate && api_host != "api.github.com" => "application/vnd.github.v3.sha",
+            accept => accept,
+        };
+
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Accept", accept)
+            .header("User-Agent", self.config.http.user_agent.as_deref().unwrap_or(USER_AGENT));
+
+        if let Some(source) = &self.config.http.request_source {
+            builder = builder.header("X-Request-Source", source);
+        }
+
+        if authenticate && self.credentials.app(&api_host).is_some() {
+            // The installation token is set when the request is sent, as
+            // minting one is asynchronous
+            builder = builder
+                .extension(AppAuth);
+        } else if authenticate && self.credentials.pool(&api_host).is_some() {
+            // The token is picked when the request is sent, by the rate
+            // limits told by the responses so far
+            builder = builder.extension(PoolAuth);
+        } else if authenticate {
+            // The token is resolved when the request is sent, without
+            // blocking on `gh` meanwhile
+            builder = builder.extension(TokenAuth);
+        }
+        if let Some(version) = self.api_version(&api_host).filter(|_| authenticate) {
+            builder = builder.header("X-Github-Api-Version", version);
+        }
+
+        if let Some(host) = self.config.hosts.get(&api_host) {
+            for (name, value) in &host.headers {
+                builder = builder.header(name, value);
+            }
+        }
+
+        Ok(builder.body(body)?)
+    }
+
+    /// Sends a GET request and reads the whole response body
+    pub async fn get(&self, uri: &str, accept: &str) -> std::result::Result<(StatusCode, Bytes), Error> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.send(self.request(uri, accept)?).await,
+        };
+
+        let mut request = self.request(uri, accept)?;
+        let cached = cache.lookup(uri, accept);
+        if let Some(etag) = cached.as_ref().and_then(|(etag, _)| HeaderValue::from_str(etag).ok()) {
+            request.headers_mut().insert("If-None-Match", etag);
+        }
+        let res = self.respond(request).await?;
+        let status = res.status();
+        if let (StatusCode::NOT_MODIFIED, Some((_, body_bytes))) = (status, cached) {
+            debug!("GET {}: using cached response", uri);
+            return Ok((StatusCode::OK, body_bytes));
+        }
+        let etag = res.headers().get("ETag").and_then(|etag| etag.to_str().ok()).map(str::to_string);
+        let body_bytes = body::to_bytes(res).await?;
+        trace!("GET {}: {}", uri, redact::text(&String::from_utf8_lossy(&body_bytes)));
+        if let (StatusCode::OK, Some(etag)) = (status, etag) {
+            cache.store(uri, accept, &etag, &body_bytes);
+        }
+        Ok((status, body_bytes))
+    }
+
+    /// Sends a GET request without GitHub credentials
+    pub async fn get_anonymous(&self, uri: &str, accept: &str) -> std::result::Result<(StatusCode, Bytes), Error> {
+        self.send(self.anonymous_request(uri, accept)?).await
+    }
+
+    /// Sends a GET request without GitHub credentials but with another
+    /// service's own authentication header
+    pub async fn get_with_header(&self, uri: &str, accept: &str, name: &'static str, value: &str) -> std::result::Result<(StatusCode, Bytes), Error> {
+        let mut request = self.anonymous_request(uri, accept)?;
+        request.headers_mut().insert(name, value.parse().map_err(hyper::http::Error::from)?);
+        self.send(request).await
+    }
+
+    /// Sends a JSON body with a write method such as POST or PATCH and
+    /// parses the JSON response, failing on unsuccessful statuses
+    pub async fn send_json(&self, method: Method, uri: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        self.send_json_as(method, uri, Some(body), Auth::GitHub).await
+    }
+
+    /// Sends a JSON body without GitHub credentials, e.g. to a webhook
+    pub async fn send_json_anonymous(&self, method: Method, uri: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        self.send_json_as(method, uri, Some(body), Auth::None).await
+    }
+
+    /// Sends a request, with a JSON body if any, to another service with
+    /// its own Authorization header value
+    pub async fn send_json_authorized(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<&serde_json::Value>,
+        authorization: &str,
+    ) -> Result<serde_json::Value> {
+        self.send_json_as(method, uri, body, Auth::Header(authorization)).await
+    }
+
+    async fn send_json_as(&self, method: Method, uri: &str, body: Option<&serde_json::Value>, auth: Auth<'_>) -> Result<serde_json::Value> {
+        let body = match body {
+            Some(body) => Body::from(serde_json::to_vec(body)?),
+            None => Body::empty(),
+        };
+        let accept = match auth {
+            Auth::GitHub => "application/vnd.github+json",
+            _ => "application/json",
+        };
+        let mut request = self.build_request(method, uri, accept, body, matches!(auth, Auth::GitHub))?;
+        request.headers_mut().insert("Content-Type", HeaderValue::from_static("application/json"));
+        if let Auth::Header(authorization) = auth {
+            request.headers_mut().insert("Authorization", authorization.parse()?);
+        }
+
+        let (status, body_bytes) = self.send(request).await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        // Webhooks answer with an empty body or plain text such as "ok"
+        Ok(serde_json::from_slice(&body_bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// Sends a GET request and streams the response body to a temporary
+    /// file instead of holding it in memory
+    pub async fn download(&self, uri: &str, accept: &str) -> std::result::Result<(StatusCode, Download), Error> {
+        let mut request = self.request(uri, accept)?;
+        // Bodies are cached as for get, and copied rather than read
+        let cached = self.cache.as_ref().and_then(|cache| cache.lookup_path(uri, accept));
+        if let Some(etag) = cached.as_ref().and_then(|(etag, _)| HeaderValue::from_str(etag).ok()) {
+            request.headers_mut().insert("If-None-Match", etag);
+        }
+        let mut res = self.respond(request).await?;
+        let status = res.status();
+        let download = Download::new()?;
+        let write_error = |source| Error::Write { path: download.path.display().to_string(), source };
+        if let (StatusCode::NOT_MODIFIED, Some((_, body))) = (status, &cached) {
+            debug!("GET {}: using cached response", uri);
+            tokio::fs::copy(body, &download.path).await.map_err(write_error)?;
+            return Ok((StatusCode::OK, download));
+        }
+        let etag = res.headers().get("ETag").and_then(|etag| etag.to_str().ok()).map(str::to_string);
+        let mut file = download.create().await.map_err(write_error)?;
+        while let Some(chunk) = res.body_mut().data().await {
+            file.write_all(&chunk?).await.map_err(write_error)?;
+        }
+        file.flush().await.map_err(write_error)?;
+        trace!("GET {}: {} bytes written to {}", uri, download.len(), download.path.display());
+        if let (StatusCode::OK, Some(cache), Some(etag)) = (status, &self.cache, etag) {
+            cache.store_path(uri, accept, &etag, &download.path).await;
+        }
+        Ok((status, download))
+    }
+
+    async fn send(&self, request: Request<Body>) -> std::result::Result<(StatusCode, Bytes), Error> {
+        let (method, uri) = (request.method().clone(), request.uri().clone());
+        let res = self.respond(request).await?;
+        let status = res.status();
+        let body_bytes = body::to_bytes(res).await?;
+        trace!("{} {}: {}", method, uri, redact::text(&String::from_utf8_lossy(&body_bytes)));
+        Ok((status, body_bytes))
+    }
+
+    /// Sends a request, retrying it with backoff after a server error, a
+    /// dropped connection or rate limiting, up to max_retries times
+    async fn respond(&self, request: Request<Body>) -> std::result::Result<Response<Body>, Error> {
+        let (parts, body) = request.into_parts();
+        let body = body::to_bytes(body).await?;
+        let app = parts.extensions
+            .get::<AppAuth>()
+            .and_then(|_| self.credentials.app(parts.uri.host().unwrap_or_default()));
+        let pool = parts.extensions
+            .get::<PoolAuth>()
+            .and_then(|_| self.credentials.pool(parts.uri.host().unwrap_or_default()));
+        let token = match parts.extensions.get::<TokenAuth>() {
+            Some(_) => {
+                let api_host = parts.uri.host().unwrap_or_default();
+                match self.credentials.token(api_host).await {
+                    Some(token) => Some(HeaderValue::from_str(&authorization(&token)).map_err(hyper::http::Error::from)?),
+                    // Public repositories can be read anonymously, but
+                    // nothing can be written
+                    None if parts.method != Method::GET => {
+                        return Err(Error::MissingToken { host: api_host.to_string(), tried: credentials::tried(api_host) });
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        let max_retries = self.config.http.max_retries.unwrap_or(backoff::MAX_RETRIES);
+        let max_wait = Duration::from_secs(self.config.http.max_retry_wait_secs.unwrap_or(backoff::MAX_WAIT_SECS));
+
+        let mut attempt = 0;
+        loop {
+            let mut request = Request::new(Body::from(body.clone()));
+            *request.method_mut() = parts.method.clone();
+            *request.uri_mut() = parts.uri.clone();
+            *request.headers_mut() = parts.headers.clone();
+            if let Some(token) = &token {
+                request.headers_mut().insert("Authorization", token.clone());
+            }
+            // Asked again on every attempt as retries may wait past the
+            // expiry of the token
+            if let Some(app) = app {
+                let token = app.token(self).await.map_err(|e| Error::App(e.to_string()))?;
+                request.headers_mut().insert("Authorization", HeaderValue::from_str(&authorization(&token)).map_err(hyper::http::Error::from)?);
+            }
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+            let pooled = pool.map(|pool| pool.pick(now));
+            if let Some((_, token)) = pooled {
+                request.headers_mut().insert("Authorization", HeaderValue::from_str(&authorization(token)).map_err(hyper::http::Error::from)?);
+            }
+            debug!("{} {} {}", parts.method, parts.uri, redact::headers(request.headers()));
+
+            let res = match self.client.request(request).await {
+                Ok(res) => res,
+                Err(e) if attempt < max_retries && backoff::idempotent(&parts.method) && (e.is_connect() || e.is_incomplete_message()) => {
+                    let delay = backoff::exponential_delay(attempt);
+                    warn!("{} {}: {}, retrying in {}ms", parts.method, parts.uri, e, delay.as_millis());
+                    backoff::count();
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let status = res.status();
+            debug!("{} {}: {} {}", parts.method, parts.uri, status, redact::headers(res.headers()));
+            let remaining = res.headers().get("x-ratelimit-remaining").and_then(|value| value.to_str().ok()?.parse().ok());
+            match (pool, pooled, remaining) {
+                (Some(pool), Some((index, _)), Some(remaining)) => {
+                    let reset = res.headers().get("x-ratelimit-reset").and_then(|value| value.to_str().ok()?.parse().ok());
+                    pool.update(index, remaining, reset);
+                    self.rate_limit_remaining.store(pool.remaining(), Ordering::Relaxed);
+                }
+                (_, _, Some(remaining)) => self.rate_limit_remaining.store(remaining, Ordering::Relaxed),
+                _ => {}
+            }
+            if status == StatusCode::BAD_REQUEST && parts.headers.contains_key("X-Github-Api-Version") {
+                // Told apart from other bad requests by the message
+                let (res_parts, res_body) = res.into_parts();
+                let res_body = body::to_bytes(res_body).await?;
+                if let Some(message) = api_version_rejection(&res_body) {
+                    let host = parts.uri.host().unwrap_or_default().to_string();
+                    let version = parts.headers["X-Github-Api-Version"].to_str().unwrap_or_default().to_string();
+                    return Err(Error::ApiVersion { host, version, message });
+                }
+                return Ok(Response::from_parts(res_parts, Body::from(res_body)));
+            }
+            if attempt == max_retries || !(status.is_server_error() || status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS) {
+                return Ok(res);
+            }
+
+            // The body tells secondary rate limits apart from other 403s
+            let (res_parts, res_body) = res.into_parts();
+            let res_body = body::to_bytes(res_body).await?;
+            // Another token of the pool is tried right away rather than
+            // waiting for the limit of this one to reset
+            if let (Some(pool), Some((index, _))) = (pool, pooled) {
+                if backoff::rate_limited(status, &res_parts.headers, &res_body) && pool.has_other(index, now) {
+                    warn!("{} {}: {}, retrying with another token", parts.method, parts.uri, status);
+                    backoff::count();
+                    attempt += 1;
+                    continue;
+                }
+            }
+            match backoff::delay(&parts.method, status, &res_parts.headers, &res_body, attempt) {
+                Some(delay) if delay <= max_wait => {
+                    warn!("{} {}: {}, retrying in {}ms", parts.method, parts.uri, status, delay.as_millis());
+                    backoff::count();
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return Ok(Response::from_parts(res_parts, Body::from(res_body))),
+            }
+        }
+    }
+
+    /// Resolves a ref of a repository to its commit SHA
+    pub async fn commit_sha(&self, host: &str, full_name: &str, git_ref: &str) -> Result<String> {
+        let uri = format!("{}/repos/{full_name}/commits/{}", api_url(host), url::encode_path(git_ref));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github.sha").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(String::from_utf8_lossy(&body_bytes).trim().to_string())
+    }
+
+    /// Returns the committer date of the last commit touching a path at a
+    /// ref, none when the path was never committed
+    pub async fn last_commit_date(&self, host: &str, full_name: &str, path: &str, git_ref: &str) -> Result<Option<String>> {
+        let uri = format!("{}/repos/{full_name}/commits?path={}&sha={}&per_page=1", api_url(host), url::encode(path), url::encode(git_ref));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        let commits: Vec<Commit> = serde_json::from_slice(&body_bytes)?;
+        Ok(commits.into_iter().next().map(|commit| commit.commit.committer.date))
+    }
+
+    pub async fn repository(&self, host: &str, full_name: &str) -> Result<Repository> {
+        let uri = format!("{}/repos/{full_name}", api_url(host));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(serde_json::from_slice(&body_bytes)?)
+    }
+
+    /// Tells why a file of a repository answered with the given status:
+    /// 403 is a permission problem, and 404 one when the repository itself
+    /// can't be seen or pulled
+    pub async fn access(&self, host: &str, full_name: &str, status: StatusCode) -> Result<Access> {
+        if status == StatusCode::FORBIDDEN {
+            return Ok(Access::Denied);
+        }
+        let uri = format!("{}/repos/{full_name}", api_url(host));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        match status {
+            StatusCode::NOT_FOUND => Ok(Access::Hidden),
+            status if status.is_success() => {
+                let repository: Repository = serde_json::from_slice(&body_bytes)?;
+                match repository.permissions {
+                    Some(permissions) if !permissions.pull => Ok(Access::Denied),
+                    _ => Ok(Access::Missing),
+                }
+            }
+            status => Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into()),
+        }
+    }
+
+    /// Returns the bytes of code per language of a repository
+    pub async fn languages(&self, host: &str, full_name: &str) -> Result<HashMap<String, u64>> {
+        let uri = format!("{}/repos/{full_name}/languages", api_url(host));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(serde_json::from_slice(&body_bytes)?)
+    }
+
+    /// Reads a file of a repository at a ref, none when it doesn't exist
+    pub async fn file(&self, host: &str, full_name: &str, path: &str, git_ref: &str) -> Result<Option<Bytes>> {
+        let uri = contents_uri(host, full_name, path, Some(git_ref));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github.raw").await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(Some(body_bytes))
+    }
+
+    /// Lists a directory of a repository at a ref, empty when it doesn't
+    /// exist
+    pub async fn directory(&self, host: &str, full_name: &str, path: &str, git_ref: &str) -> Result<Vec<ContentEntry>> {
+        let uri = contents_uri(host, full_name, path, Some(git_ref));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(serde_json::from_slice(&body_bytes)?)
+    }
+
+    /// Lists every file and directory of a repository at a ref
+    pub async fn tree(&self, host: &str, full_name: &str, git_ref: &str) -> Result<Tree> {
+        let uri = format!("{}/repos/{full_name}/git/trees/{}?recursive=1", api_url(host), url::encode_path(git_ref));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(serde_json::from_slice(&body_bytes)?)
+    }
+
+    pub async fn issue(&self, host: &str, full_name: &str, number: u64) -> Result<Issue> {
+        let uri = format!("{}/repos/{full_name}/issues/{number}", api_url(host));
+        let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        Ok(serde_json::from_slice(&body_bytes)?)
+    }
+
+    /// Runs a GraphQL query, failing when it returns errors
+    pub async fn graphql(&self, host: &str, query: &str, variables: serde_json::Value) -> Result<serde_json::Value> {
+        let body = serde_json::json!({ "query": query, "variables": variables });
+        let mut response = self.send_json(Method::POST, &graphql_url(host), &body).await?;
+        if let Some(errors) = response.get("errors").and_then(|errors| errors.as_array()) {
+            let messages: Vec<&str> = errors.iter().filter_map(|error| error["message"].as_str()).collect();
+            return Err(format!("GraphQL query failed: {}", messages.join(", ")).into());
+        }
+        Ok(response["data"].take())
+    }
+
+    /// Lists the open issues of a repository, following pagination
+    pub async fn open_issues(&self, host: &str, full_name: &str) -> Result<Vec<Issue>> {
+        let api_url = api_url(host);
+        let mut issues = Vec::new();
+
+        for page in 1.. {
+            let uri = format!("{api_url}/repos/{full_name}/issues?state=open&per_page={PER_PAGE}&page={page}");
+            let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+            if !status.is_success() {
+                return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+            }
+
+            let page_issues: Vec<Issue> = serde_json::from_slice(&body_bytes)?;
+            let last_page = page_issues.len() < PER_PAGE;
+            issues.extend(page_issues);
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(issues)
+    }
+
+    /// Lists every repository of an organization, following pagination
+    pub async fn org_repos(&self, host: &str, org: &str) -> Result<Vec<Repository>> {
+        let api_url = api_url(host);
+        let mut repos = Vec::new();
+
+        for page in 1.. {
+            let uri = format!("{api_url}/orgs/{org}/repos?per_page={PER_PAGE}&page={page}");
+            let (status, body_bytes) = self.get(&uri, "application/vnd.github+json").await?;
+            if !status.is_success() {
+                return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+            }
+
+            let page_repos: Vec<Repository> = serde_json::from_slice(&body_bytes)?;
+            let last_page = page_repos.len() < PER_PAGE;
+            repos.extend(page_repos);
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(repos)
+    }
+}
+
+/// Sends the requests for repositories not prefixed with a host to the
+/// REST API at this URL, e.g. https://github.example.com/api/v3, instead
+/// of api.github.com
+pub fn set_api_url(url: &str) -> Result<()> {
+    let url = url.trim_end_matches('/');
+    let uri: Uri = url.parse().map_err(|source| Error::Uri { uri: url.to_string(), source })?;
+    let host = match uri.host() {
+        Some("api.github.com") => return Ok(()),
+        Some(host) => host.to_string(),
+        None => return Err(format!("API URL {} has no host", url).into()),
+    };
+    DEFAULT_API
+        .set((host, url.to_string()))
+        .map_err(|_| "The API URL is already set".into())
+}
+
+/// Returns the host of the API of the repositories not prefixed with one,
+/// e.g. api.github.com
+fn default_api_host() -> String {
+    let api_url = api_url(default_host());
+    api_url.parse::<Uri>().ok().and_then(|uri| uri.host().map(str::to_string)).unwrap_or_default()
+}
+
+/// Returns the host of the repositories not prefixed with one: github.com,
+/// or the host of --api-url
+pub fn default_host() -> &'static str {
+    DEFAULT_API.get().map_or(HOST, |(host, _)| host.as_str())
+}
+
+/// Splits a repository entry such as org/repo@release/1.2:packages/api
+/// into the repository, the ref after `@` and the directory after `:`,
+/// which refs can't contain
+pub(crate) fn split_entry(repo: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (repo, dir) = match repo.rsplit_once(':') {
+        Some((repo, dir)) if repo.contains('/') => (repo, Some(dir)),
+        _ => (repo, None),
+    };
+    let (repo, git_ref) = match repo.split_once('@') {
+        Some((repo, git_ref)) => (repo, Some(git_ref)),
+        None => (repo, None),
+    };
+    (repo, git_ref, dir)
+}
+
+/// Splits a repository into its host and `org/repo` name, defaulting to
+/// default_host() when the repository is not prefixed with a host. The
+/// ref and directory of entries such as org/repo@release/1.2:packages/api
+/// are left out.
+pub fn parse_repo(repo: &str) -> (&str, &str) {
+    let (repo, _, _) = split_entry(repo);
+    match repo.split_once('/') {
+        Some((host, full_name)) if host.contains('.') => (host, full_name),
+        _ => (default_host(), repo),
+    }
+}
+
+/// Returns the directory of the project to check in a repository given
+/// as e.g. org/repo:packages/api, none for the root
+pub fn repo_dir(repo: &str) -> Option<&str> {
+    let (_, _, dir) = split_entry(repo);
+    dir.map(|dir| dir.trim_matches('/')).filter(|dir| !dir.is_empty())
+}
+
+/// Returns the branch, tag or commit to check a repository given as e.g.
+/// org/repo@release/1.2 at, none for its default branch
+pub fn repo_ref(repo: &str) -> Option<&str> {
+    let (_, git_ref, _) = split_entry(repo);
+    git_ref.filter(|git_ref| !git_ref.is_empty())
+}
+
+/// URL of the contents API for a file or directory of a repository, at
+/// its default branch without a ref. Paths and refs are encoded, as
+/// either may hold spaces, # or ?.
+pub fn contents_uri(host: &str, full_name: &str, path: &str, git_ref: Option<&str>) -> String {
+    let uri = format!("{}/repos/{}/contents/{}", api_url(host), full_name, url::encode_path(path));
+    match git_ref {
+        Some(git_ref) => format!("{}?ref={}", uri, url::encode(git_ref)),
+        None => uri,
+    }
+}
+
+/// Adds a ref and a directory to a repository entry that doesn't have
+/// its own, e.g. from --ref and --path
+pub fn with_defaults(repo: String, git_ref: Option<&str>, dir: Option<&str>) -> String {
+    let (name, own_ref, own_dir) = split_entry(&repo);
+    let git_ref = own_ref.or(git_ref);
+    let dir = own_dir.or(dir);
+    if git_ref == own_ref && dir == own_dir {
+        return repo;
+    }
+    let mut entry = name.to_string();
+    if let Some(git_ref) = git_ref {
+        entry = format!("{}@{}", entry, git_ref);
+    }
+    if let Some(dir) = dir {
+        entry = format!("{}:{}", entry, dir);
+    }
+    entry
+}
+
+/// Prefixes a repository with its host unless it's the default one, as
+/// read by --repos
+pub fn qualified_name(host: &str, full_name: &str) -> String {
+    if host == default_host() {
+        full_name.to_string()
+    } else {
+        format!("{}/{}", host, full_name)
+    }
+}
+
+/// Returns the REST API base URL of a GitHub host
+pub fn api_url(host: &str) -> String {
+    match DEFAULT_API.get() {
+        Some((default_host, url)) if default_host == host => url.clone(),
+        _ if host == HOST => API_URL.to_string(),
+        _ => format!("https://{host}/api/v3"),
+    }
+}
+
+/// Returns the GraphQL API URL of a GitHub host
+pub fn graphql_url(host: &str) -> String {
+    let api_url = api_url(host);
+    match api_url.strip_suffix("/v3") {
+        Some(base) => format!("{}/graphql", base),
+        None => format!("{}/graphql", api_url),
+    }
+}
+
+/// Returns the web URL of a file at a commit
+pub fn html_url(host: &str, full_name: &str, sha: &str, path: &str) -> String {
+    format!("https://{host}/{full_name}/blob/{sha}/{path}")
+}
+
+/// Returns the URL serving the raw content of a file at a commit
+pub fn raw_url(host: &str, full_name: &str, sha: &str, path: &str) -> String {
+    if host == HOST {
+        format!("https://raw.githubusercontent.com/{full_name}/{sha}/{path}")
+    } else {
+        format!("https://{host}/{full_name}/raw/{sha}/{path}")
+    }
+}
+
+/// Builds the Authorization header value for a token. Fine-grained PATs,
+/// installation and user-to-server tokens and JWTs use the Bearer scheme,
+/// which some GHES versions require for them; classic PATs keep `token`.
+fn authorization(token: &str) -> String {
+    const BEARER_PREFIXES: [&str; 4] = ["github_pat_", "ghs_", "ghu_", "eyJ"];
+
+    if BEARER_PREFIXES.iter().any(|prefix| token.starts_with(prefix)) {
+        format!("Bearer {}", token)
+    } else {
+        format!("token {}", token)
+    }
+}
+
+/// Message of a 400 response rejecting the X-GitHub-Api-Version of the
+/// request, none for other bad requests
+fn api_version_rejection(body: &[u8]) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Message {
+        message: String,
+    }
+    let message = serde_json::from_slice::<Message>(body).ok()?.message;
+    let lowercase = message.to_lowercase();
+    (lowercase.contains("api version") || lowercase.contains("x-github-api-version")).then_some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_repositories_into_host_name_and_directory() {
+        assert_eq!(parse_repo("org/api"), (HOST, "org/api"));
+        assert_eq!(parse_repo("ghe.example.com/org/api"), ("ghe.example.com", "org/api"));
+        assert_eq!(parse_repo("org/monorepo:packages/api"), (HOST, "org/monorepo"));
+        assert_eq!(repo_dir("ghe.example.com/org/monorepo:packages/api/"), Some("packages/api"));
+        assert_eq!(repo_dir("org/api"), None);
+        assert_eq!(repo_dir("org/api:"), None);
+    }
+
+    #[test]
+    fn reads_refs_of_repository_entries() {
+        assert_eq!(parse_repo("org/api@release/1.2:packages/api"), (HOST, "org/api"));
+        assert_eq!(repo_ref("org/api@release/1.2:packages/api"), Some("release/1.2"));
+        assert_eq!(repo_dir("org/api@release/1.2:packages/api"), Some("packages/api"));
+        assert_eq!(repo_ref("org/api"), None);
+        assert_eq!(with_defaults("org/api".to_string(), Some("main"), Some("web")), "org/api@main:web");
+        assert_eq!(with_defaults("org/api@v2".to_string(), Some("main"), None), "org/api@v2");
+        assert_eq!(with_defaults("org/api:cli".to_string(), Some("main"), Some("web")), "org/api@main:cli");
+    }
+
+    #[test]
+    fn encodes_paths_and_refs_of_contents_urls() {
+        let (_, git_ref, dir) = split_entry("org/api@fix #12?:my app");
+        let path = format!("{}/package-lock.json", dir.unwrap());
+        assert_eq!(
+            contents_uri(HOST, "org/api", &path, git_ref),
+            "https://api.github.com/repos/org/api/contents/my%20app/package-lock.json?ref=fix%20%2312%3F",
+        );
+        assert_eq!(contents_uri(HOST, "org/api", "package-lock.json", None), "https://api.github.com/repos/org/api/contents/package-lock.json");
+        assert!(contents_uri(HOST, "org/api", &path, git_ref).parse::<Uri>().is_ok());
+    }
+
+    #[test]
+    fn tells_rejected_api_versions_apart() {
+        let rejected = br#"{"message": "Unsupported 'X-GitHub-Api-Version': 2026-03-10", "documentation_url": "https://docs.github.com/rest"}"#;
+        assert_eq!(api_version_rejection(rejected).as_deref(), Some("Unsupported 'X-GitHub-Api-Version': 2026-03-10"));
+        assert_eq!(api_version_rejection(br#"{"message": "Problems parsing JSON"}"#), None);
+        assert_eq!(api_version_rejection(b"Bad Request"), None);
+
+        let mut config: Config = toml::from_str(r#"
+            [http]
+            api_version = "2026-03-10"
+            [hosts."ghe.example.com"]
+            api_version = "2022-11-28"
+            [hosts."old.example.com"]
+            api_version = ""
+        "#).unwrap();
+        let github = GitHub::new(config.clone());
+        assert_eq!(github.api_version("api.github.com"), Some("2026-03-10"));
+        assert_eq!(github.api_version("ghe.example.com"), Some("2022-11-28"));
+        assert_eq!(github.api_version("old.example.com"), None);
+        config.http.api_version = None;
+        assert_eq!(GitHub::new(config).api_version("api.github.com"), Some(API_VERSION));
+    }
+
+    #[tokio::test]
+    async fn downloads_into_a_private_directory() {
+        let download = Download::new().unwrap();
+        let dir = download.path.parent().unwrap().to_path_buf();
+        assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        download.create().await.unwrap();
+        assert_eq!(fs::metadata(&download.path).unwrap().permissions().mode() & 0o777, 0o600);
+        // Whatever is already there is never written through
+        assert!(download.create().await.is_err());
+        drop(download);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::env;
+
+use hyper::body::Bytes;
+use hyper::StatusCode;
+
+use crate::errors::Error;
+use crate::github::{self, GitHub};
+use crate::url::encode;
+use crate::Result;
+
+/// Prefix of the repository entries of GitLab projects, e.g.
+/// gitlab:group/project@main:packages/api
+pub const PREFIX: &str = "gitlab:";
+
+/// Environment variable holding the token to read private projects with;
+/// public ones are read anonymously without it
+pub const TOKEN_VAR: &str = "GITLAB_TOKEN";
+
+/// Environment variable holding the URL of a self-managed instance
+pub const URL_VAR: &str = "GITLAB_URL";
+
+const GITLAB_URL: &str = "https://gitlab.com";
+
+/// Returns the path of the project of a GitLab entry, e.g. group/project
+/// for gitlab:group/project@main, none for other entries
+pub fn project(repo: &str) -> Option<&str> {
+    let (project, _, _) = github::split_entry(repo.strip_prefix(PREFIX)?);
+    Some(project)
+}
+
+/// Returns the web URL of the instance, from GITLAB_URL or gitlab.com
+pub fn base_url() -> String {
+    env::var(URL_VAR).map_or_else(|_| GITLAB_URL.to_string(), |url| url.trim_end_matches('/').to_string())
+}
+
+fn api_url() -> String {
+    format!("{}/api/v4", base_url())
+}
+
+/// Returns the URL of the raw content of a file of a project at a ref
+pub fn file_url(project: &str, path: &str, git_ref: &str) -> String {
+    format!("{}/projects/{}/repository/files/{}/raw?ref={}", api_url(), encode(project), encode(path), encode(git_ref))
+}
+
+/// Sends a GET request to the GitLab API, with GITLAB_TOKEN when it's set
+pub async fn get(github: &GitHub, uri: &str) -> std::result::Result<(StatusCode, Bytes), Error> {
+    match env::var(TOKEN_VAR) {
+        Ok(token) => github.get_with_header(uri, "*/*", "PRIVATE-TOKEN", &token).await,
+        Err(_) => github.get_anonymous(uri, "*/*").await,
+    }
+}
+
+/// Returns the share of each language of a project, scaled to basis
+/// points so that it compares like the bytes per language of GitHub
+pub async fn languages(github: &GitHub, project: &str) -> Result<HashMap<String, u64>> {
+    let uri = format!("{}/projects/{}/languages", api_url(), encode(project));
+    let (status, body_bytes) = get(github, &uri).await?;
+    if !status.is_success() {
+        return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+    }
+    let percentages: HashMap<String, f64> = serde_json::from_slice(&body_bytes)?;
+    Ok(percentages.into_iter().map(|(language, percentage)| (language, (percentage * 100.0) as u64)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_gitlab_entries() {
+        assert_eq!(project("gitlab:group/subgroup/project@main:packages/api"), Some("group/subgroup/project"));
+        assert_eq!(project("gitlab:group/project"), Some("group/project"));
+        assert_eq!(project("org/repo"), None);
+        assert_eq!(github::repo_ref("gitlab:group/project@main"), Some("main"));
+        assert_eq!(github::repo_dir("gitlab:group/project:packages/api"), Some("packages/api"));
+        assert_eq!(
+            file_url("group/project", "packages/api/package-lock.json", "HEAD"),
+            "https://gitlab.com/api/v4/projects/group%2Fproject/repository/files/packages%2Fapi%2Fpackage-lock.json/raw?ref=HEAD",
+        );
+    }
+}
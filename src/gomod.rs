@@ -0,0 +1,57 @@
+use std::str;
+
+/// Finds the required version of a module in a go.mod body
+pub fn find_version(body: &[u8], module: &str) -> Result<Option<String>, String> {
+    let body_str = str::from_utf8(body)
+        .map_err(|e| format!("Error converting body to UTF-8: {}", e))?;
+
+    let mut in_require_block = false;
+    for line in body_str.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+
+        let requirement = if in_require_block {
+            if line == ")" {
+                in_require_block = false;
+                continue;
+            }
+            line
+        } else if line == "require (" {
+            in_require_block = true;
+            continue;
+        } else if let Some(requirement) = line.strip_prefix("require ") {
+            requirement
+        } else {
+            continue;
+        };
+
+        let mut fields = requirement.split_whitespace();
+        if fields.next() == Some(module) {
+            return Ok(fields.next().map(str::to_string));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const GO_MOD: &[u8] = include_bytes!("../tests/fixtures/go.mod");
+
+    #[test]
+    fn finds_single_line_and_block_requirements() {
+        assert_eq!(find_version(GO_MOD, "github.com/google/uuid").unwrap().as_deref(), Some("v1.3.0"));
+        assert_eq!(find_version(GO_MOD, "github.com/gin-gonic/gin").unwrap().as_deref(), Some("v1.9.1"));
+        assert_eq!(find_version(GO_MOD, "golang.org/x/net").unwrap().as_deref(), Some("v0.17.0"));
+        assert_eq!(find_version(GO_MOD, "github.com/acme/service").unwrap(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_go_mod_never_panics(body in any::<Vec<u8>>(), module in "\\PC*") {
+            let _ = find_version(&body, &module);
+        }
+    }
+}
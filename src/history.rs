@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, TimeZone};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::report::{Record, Versioned};
+use crate::Result;
+
+/// How timestamps are shown in human-facing output, in the local timezone
+/// by default
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct TimeArgs {
+    /// Show timestamps in UTC instead of the local timezone
+    #[clap(long, conflicts_with = "relative-time")]
+    pub utc: bool,
+
+    /// Show timestamps relative to now, e.g. 12 days ago
+    #[clap(long)]
+    pub relative_time: bool,
+}
+
+impl TimeArgs {
+    /// Formats a timestamp in seconds since the epoch
+    pub fn format(&self, timestamp: u64) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        self.format_at(timestamp, now)
+    }
+
+    /// Formats a timestamp as seen at a time, which relative ones count from
+    pub fn format_at(&self, timestamp: u64, now: u64) -> String {
+        if self.relative_time {
+            ago(now.saturating_sub(timestamp))
+        } else if self.utc {
+            format!("{} {:02}:{:02} UTC", date(timestamp), timestamp % 86_400 / 3_600, timestamp % 3_600 / 60)
+        } else {
+            match Local.timestamp_opt(timestamp as i64, 0).single() {
+                Some(local) => local.format("%Y-%m-%d %H:%M %:z").to_string(),
+                None => date(timestamp),
+            }
+        }
+    }
+}
+
+/// Formats a duration in seconds as how long ago something happened,
+/// e.g. 12 days ago or 2.3 years ago
+pub fn ago(seconds: u64) -> String {
+    const DAY: u64 = 86_400;
+    let days = seconds / DAY;
+    match days {
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        2..=60 => format!("{} days ago", days),
+        61..=729 => format!("{} months ago", days * 12 / 365),
+        _ => format!("{:.1} years ago", days as f64 / 365.25),
+    }
+}
+
+/// Compare runs of the history side by side per repository
+#[derive(Args, Debug, Clone)]
+pub struct CompareArgs {
+    /// History directory written by check --history
+    #[clap(long)]
+    history: String,
+
+    /// Runs to compare, e.g. last-4 for the four most recent ones
+    #[clap(long)]
+    compare: Window,
+
+    /// Only compare the versions of this package
+    #[clap(short, long)]
+    package: Option<String>,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+}
+
+/// Most recent runs of the history, written as `last-N`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    last: usize,
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Window, String> {
+        let invalid = || format!("invalid runs {:?}, expected last-N such as last-4", s);
+        let last: usize = s.strip_prefix("last-").ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+        if last == 0 {
+            return Err(invalid());
+        }
+        Ok(Window { last })
+    }
+}
+
+pub fn compare(args: CompareArgs) -> Result<()> {
+    let snapshots = load(&args.history)?;
+    if snapshots.is_empty() {
+        return Err(format!("No snapshots in {}", args.history).into());
+    }
+    let runs = &snapshots[snapshots.len().saturating_sub(args.compare.last)..];
+    print!("{}", comparison(runs, args.package.as_deref(), &args.time));
+    Ok(())
+}
+
+/// Renders runs as a table with a column per run, oldest first, and a row
+/// per repository and package. Cells hold the version, `error` when the
+/// repository couldn't be checked and `-` when it wasn't part of the run.
+fn comparison(runs: &[Snapshot], package: Option<&str>, time: &TimeArgs) -> String {
+    let mut rows: BTreeMap<(&str, &str), HashMap<usize, String>> = BTreeMap::new();
+    for (run, snapshot) in runs.iter().enumerate() {
+        for record in snapshot.results.iter().filter(|record| package.is_none_or(|package| record.package == package)) {
+            let cell = match (&record.error, record.shown_version()) {
+                (Some(_), _) => "error".to_string(),
+                (None, Some(version)) => version,
+                (None, None) => "none".to_string(),
+            };
+            rows.entry((&record.repo, &record.package)).or_default().insert(run, cell);
+        }
+    }
+
+    let mut header = vec!["repository".to_string(), "package".to_string()];
+    header.extend(runs.iter().map(|snapshot| time.format(snapshot.timestamp)));
+    let mut table = vec![header];
+    for ((repo, package), cells) in &rows {
+        let mut row = vec![repo.to_string(), package.to_string()];
+        row.extend((0..runs.len()).map(|run| cells.get(&run).cloned().unwrap_or_else(|| "-".to_string())));
+        table.push(row);
+    }
+
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|column| table.iter().map(|row| row[column].len()).max().unwrap_or_default())
+        .collect();
+    table
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+/// Results of one run, as stored in the --history directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    /// Seconds since the Unix epoch at which the run ended
+    pub timestamp: u64,
+    pub results: Vec<Record>,
+}
+
+/// Stores the results of a run as a new snapshot file of the history
+/// directory, named after its timestamp so the files sort chronologically
+pub fn save(dir: &str, results: &[Record]) -> Result<()> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let snapshot = Snapshot { timestamp, results: results.to_vec() };
+
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Unable to create history directory {}: {}", dir, e))?;
+    let path = Path::new(dir).join(format!("{}.json", timestamp));
+    fs::write(&path, serde_json::to_string_pretty(&Versioned::new(&snapshot))?)
+        .map_err(|e| format!("Unable to write snapshot {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Reads every snapshot of the history directory, oldest first
+pub fn load(dir: &str) -> Result<Vec<Snapshot>> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Unable to read history directory {}: {}", dir, e))?;
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("Unable to read snapshot {}: {}", path.display(), e))?;
+        let snapshot: Snapshot = serde_json::from_str(&data)
+            .map_err(|e| format!("Invalid snapshot {}: {}", path.display(), e))?;
+        snapshots.push(snapshot);
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+    Ok(snapshots)
+}
+
+/// Formats a timestamp as a UTC date, e.g. 2024-05-01
+pub fn date(timestamp: u64) -> String {
+    // Civil date from days since the epoch, after Howard Hinnant's algorithm
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Parses an ISO 8601 UTC timestamp such as 2017-09-12T20:13:06.143Z, as
+/// found in registry metadata, into seconds since the epoch
+pub fn timestamp(iso: &str) -> Option<u64> {
+    let (date, time) = iso.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let time = time.trim_end_matches('Z');
+    let time = time.split_once('.').map_or(time, |(seconds, _)| seconds);
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch from a civil date, after Howard Hinnant's
+    // algorithm
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days * 86_400 + hours * 3_600 + minutes * 60 + seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registry_timestamps() {
+        assert_eq!(timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(timestamp("2017-09-12T20:13:06.143Z"), Some(1_505_247_186));
+        assert_eq!(timestamp("2024-02-29T00:00:00.000Z").map(date).as_deref(), Some("2024-02-29"));
+        assert_eq!(timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn compares_runs_side_by_side() {
+        let record = |repo: &str, version: Option<&str>| {
+            let mut record = Record::new(repo, "express");
+            record.version = version.map(str::to_string);
+            record
+        };
+        let mut failed = record("org/web", None);
+        failed.error = Some("Not Found".to_string());
+        let runs = [
+            Snapshot { timestamp: 1_714_521_600, results: vec![record("org/api", Some("4.17.1")), record("org/web", Some("4.17.1"))] },
+            Snapshot { timestamp: 1_715_126_400, results: vec![record("org/api", Some("4.18.2")), failed, record("org/worker", None)] },
+        ];
+        let utc = TimeArgs { utc: true, relative_time: false };
+        assert_eq!(
+            comparison(&runs, Some("express"), &utc),
+            "repository  package  2024-05-01 00:00 UTC  2024-05-08 00:00 UTC\n\
+             org/api     express  4.17.1                4.18.2\n\
+             org/web     express  4.17.1                error\n\
+             org/worker  express  -                     none\n",
+        );
+        assert_eq!(comparison(&runs, Some("lodash"), &utc).lines().count(), 1);
+        assert_eq!("last-4".parse(), Ok(Window { last: 4 }));
+        assert!("last-0".parse::<Window>().is_err());
+        assert!("4".parse::<Window>().is_err());
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        let utc = TimeArgs { utc: true, relative_time: false };
+        assert_eq!(utc.format(1_505_247_186), "2017-09-12 20:13 UTC");
+        assert_eq!(ago(12 * 86_400), "12 days ago");
+        assert_eq!(ago(400 * 86_400), "13 months ago");
+    }
+}
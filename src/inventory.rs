@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::time::Duration;
+
+use crate::github;
+use crate::Result;
+
+/// Column giving the seconds a repository may take to be checked instead
+/// of --task-timeout, e.g. for huge monorepos
+const TIMEOUT_COLUMN: &str = "timeout";
+
+/// Column where `low` has a repository checked after the others
+const PRIORITY_COLUMN: &str = "priority";
+
+/// Repositories read from a CSV inventory, with the other columns of each
+/// row carried through to the results
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    /// Names of the columns other than the repository and those setting
+    /// how it's checked, in file order
+    pub columns: Vec<String>,
+    repos: Vec<String>,
+    rows: HashMap<String, Vec<String>>,
+    timeouts: HashMap<String, Duration>,
+    low_priority: HashSet<String>,
+}
+
+impl Inventory {
+    /// Reads a CSV file with a header row. The repository is taken from the
+    /// column named repo, or the first column when there is none.
+    pub fn read(path: &str) -> Result<Inventory> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read inventory {}: {}", path, e))?;
+        let mut records = parse(&data).into_iter();
+
+        let header = records.next().ok_or_else(|| format!("Inventory {} is empty", path))?;
+        let position = |column: &str| header.iter().position(|name| name.trim().eq_ignore_ascii_case(column));
+        let repo_column = position("repo").unwrap_or(0);
+        let (timeout_column, priority_column) = (position(TIMEOUT_COLUMN), position(PRIORITY_COLUMN));
+        let carried = |i: usize| i != repo_column && Some(i) != timeout_column && Some(i) != priority_column;
+        let columns = header
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| carried(*i))
+            .map(|(_, name)| name.trim().to_string())
+            .collect();
+
+        let mut inventory = Inventory { columns, ..Inventory::default() };
+        for mut record in records {
+            if record.iter().all(|field| field.trim().is_empty()) {
+                continue;
+            }
+            record.resize(header.len(), String::new());
+            let repo = record[repo_column].trim().to_string();
+            if let Some(timeout) = timeout_column.map(|i| record[i].trim()).filter(|timeout| !timeout.is_empty()) {
+                let secs: u64 = timeout.parse()
+                    .map_err(|_| format!("Invalid timeout {:?} of {} in inventory {}", timeout, repo, path))?;
+                inventory.timeouts.insert(repo.clone(), Duration::from_secs(secs));
+            }
+            if priority_column.is_some_and(|i| record[i].trim().eq_ignore_ascii_case("low")) {
+                inventory.low_priority.insert(repo.clone());
+            }
+            let values = record.into_iter().enumerate().filter(|(i, _)| carried(*i)).map(|(_, value)| value).collect();
+            if !inventory.rows.contains_key(&repo) {
+                inventory.repos.push(repo.clone());
+            }
+            inventory.rows.insert(repo, values);
+        }
+        Ok(inventory)
+    }
+
+    /// Lists the repositories in file order, those of low priority last
+    pub fn repos(&self) -> Vec<String> {
+        let (low, others): (Vec<&String>, Vec<&String>) = self.repos.iter().partition(|repo| self.low_priority.contains(*repo));
+        others.into_iter().chain(low).cloned().collect()
+    }
+
+    /// Keys the rows by the entries the repositories are checked as once
+    /// --ref and --path are applied, e.g. org/api@main for the row of
+    /// org/api with --ref main
+    pub fn with_defaults(&self, git_ref: Option<&str>, dir: Option<&str>) -> Inventory {
+        let entry = |repo: &String| github::with_defaults(repo.clone(), git_ref, dir);
+        Inventory {
+            columns: self.columns.clone(),
+            repos: self.repos.iter().map(entry).collect(),
+            rows: self.rows.iter().map(|(repo, row)| (entry(repo), row.clone())).collect(),
+            timeouts: self.timeouts.iter().map(|(repo, timeout)| (entry(repo), *timeout)).collect(),
+            low_priority: self.low_priority.iter().map(entry).collect(),
+        }
+    }
+
+    /// Seconds each repository given a timeout of its own may take to be
+    /// checked
+    pub fn timeouts(&self) -> HashMap<String, Duration> {
+        self.timeouts.clone()
+    }
+
+    /// Returns the other columns of a repository's row, in file order
+    pub fn values(&self, repo: &str) -> Option<&[String]> {
+        self.rows.get(repo).map(Vec::as_slice)
+    }
+
+    /// Returns the other columns of a repository's row by name
+    pub fn metadata(&self, repo: &str) -> Option<BTreeMap<String, String>> {
+        let values = self.values(repo)?;
+        Some(self.columns.iter().cloned().zip(values.iter().cloned()).collect())
+    }
+}
+
+/// Splits CSV data into records of fields, handling quoted fields with
+/// commas, line breaks and doubled quotes
+fn parse(data: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_timeouts_and_priorities_apart_from_the_carried_columns() {
+        let path = std::env::temp_dir().join(format!("check-versions-inventory-{}.csv", std::process::id()));
+        fs::write(&path, "repo,team,Timeout,priority\norg/monorepo,platform,600,low\norg/api,payments,,\norg/web,web,,normal\n").unwrap();
+        let inventory = Inventory::read(path.to_str().unwrap()).unwrap();
+        assert_eq!(inventory.columns, ["team"]);
+        assert_eq!(inventory.repos(), ["org/api", "org/web", "org/monorepo"]);
+        assert_eq!(inventory.values("org/monorepo"), Some(&["platform".to_string()][..]));
+        assert_eq!(inventory.timeouts(), HashMap::from([("org/monorepo".to_string(), Duration::from_secs(600))]));
+
+        // With --ref main the repositories are checked as org/api@main
+        let at_main = inventory.with_defaults(Some("main"), None);
+        assert_eq!(at_main.repos(), ["org/api@main", "org/web@main", "org/monorepo@main"]);
+        assert_eq!(at_main.metadata("org/api@main"), Some(BTreeMap::from([("team".to_string(), "payments".to_string())])));
+        assert_eq!(at_main.timeouts(), HashMap::from([("org/monorepo@main".to_string(), Duration::from_secs(600))]));
+
+        fs::write(&path, "repo,timeout\norg/monorepo,ten minutes\n").unwrap();
+        assert!(Inventory::read(path.to_str().unwrap()).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use std::env;
+
+use hyper::Method;
+use serde_json::json;
+
+use crate::config::JiraConfig;
+use crate::github::GitHub;
+use crate::report::Record;
+use crate::url::encode;
+use crate::Result;
+
+/// Environment variable holding the Jira API token
+const TOKEN_VAR: &str = "JIRA_TOKEN";
+
+/// Opens an issue per record with problems, or updates the one opened by an
+/// earlier run. Issues are found again by a label derived from the
+/// repository and package, so renaming the summary template doesn't
+/// duplicate them.
+pub async fn sync(github: &GitHub, jira: &JiraConfig, records: &[Record]) -> Result<()> {
+    let token = env::var(TOKEN_VAR).map_err(|_| format!("{} is not set", TOKEN_VAR))?;
+    let authorization = match &jira.user {
+        Some(user) => format!("Basic {}", base64(format!("{}:{}", user, token).as_bytes())),
+        None => format!("Bearer {}", token),
+    };
+    let api_url = format!("{}/rest/api/2", jira.url.trim_end_matches('/'));
+
+    for record in records {
+        let problems = record.problems();
+        if problems.is_empty() {
+            continue;
+        }
+
+        let problems: Vec<String> = problems.iter().map(|problem| format!("* {}", problem)).collect();
+        let render = |template: &str| {
+            template
+                .replace("{repo}", &record.repo)
+                .replace("{package}", &record.package)
+                .replace("{version}", record.version.as_deref().unwrap_or("not found"))
+                .replace("{problems}", &problems.join("\n"))
+        };
+        let key_label = label(&record.repo, &record.package);
+        let mut labels: Vec<String> = jira.labels.iter().map(|label| render(label)).collect();
+        labels.push(key_label.clone());
+
+        let jql = format!("project = \"{}\" AND labels = \"{}\" AND statusCategory != Done", jira.project, key_label);
+        let uri = format!("{}/search?jql={}&fields=key&maxResults=1", api_url, encode(&jql));
+        let found = github.send_json_authorized(Method::GET, &uri, None, &authorization).await?;
+
+        match found["issues"][0]["key"].as_str() {
+            Some(key) => {
+                let body = json!({
+                    "fields": { "summary": render(&jira.summary), "description": render(&jira.description) },
+                });
+                let uri = format!("{}/issue/{}", api_url, key);
+                github.send_json_authorized(Method::PUT, &uri, Some(&body), &authorization).await?;
+                eprintln!("Updated {} for {}", key, record.repo);
+            }
+            None => {
+                let body = json!({
+                    "fields": {
+                        "project": { "key": jira.project },
+                        "issuetype": { "name": jira.issue_type },
+                        "summary": render(&jira.summary),
+                        "description": render(&jira.description),
+                        "labels": labels,
+                    },
+                });
+                let created = github.send_json_authorized(Method::POST, &format!("{}/issue", api_url), Some(&body), &authorization).await?;
+                eprintln!("Created {} for {}", created["key"].as_str().unwrap_or_default(), record.repo);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Label identifying the issue of a repository and package; Jira labels
+/// can't contain spaces
+fn label(repo: &str, package: &str) -> String {
+    format!("check-versions:{}:{}", repo, package)
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64_like_rfc_4648() {
+        // Test vectors of RFC 4648, section 10
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (bytes, encoded) in vectors {
+            assert_eq!(base64(bytes.as_bytes()), encoded, "{:?}", bytes);
+        }
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64(b"me@example.com:api-token"), "bWVAZXhhbXBsZS5jb206YXBpLXRva2Vu");
+    }
+}
@@ -0,0 +1,54 @@
+//! Checks which versions of packages the lockfiles of repositories hosted
+//! on GitHub resolve. `VersionChecker` runs the checks and returns their
+//! results; the check-versions binary is a CLI over the same modules.
+
+#![deny(warnings)]
+#![warn(rust_2018_idioms)]
+
+mod app;
+mod backoff;
+pub mod cache;
+pub mod check;
+mod checker;
+pub mod config;
+mod credentials;
+pub mod discover;
+pub mod ecosystem;
+pub mod errors;
+pub mod explain;
+pub mod github;
+mod gitlab;
+mod gomod;
+pub mod history;
+mod inventory;
+mod jira;
+pub mod listen;
+mod local;
+mod lockfile;
+mod notify;
+mod picker;
+mod pnpmlock;
+mod policy;
+mod provider;
+mod progress;
+mod publish;
+mod quarantine;
+mod range;
+mod redact;
+mod registry;
+pub mod report;
+mod requirements;
+mod sign;
+pub mod site;
+mod tasks;
+pub mod telemetry;
+mod tracking;
+mod transport;
+mod url;
+mod version;
+mod workspaces;
+mod yarnlock;
+
+pub use checker::{RepoSource, VersionChecker, VersionReport};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -0,0 +1,331 @@
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::Args;
+use hyper::body::{Bytes, HttpBody};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::check::{self, CheckArgs};
+use crate::ecosystem::Ecosystem;
+use crate::github::{self, GitHub};
+use crate::policy::NamedPolicy;
+use crate::report::{Record, Report};
+use crate::telemetry::Tracer;
+use crate::Result;
+
+/// Environment variable holding the secret of the webhook
+pub const SECRET_VAR: &str = "GITHUB_WEBHOOK_SECRET";
+
+/// GitHub only lists the files of the first 20 commits of a push, so
+/// larger pushes are assumed to touch a manifest
+const LISTED_COMMITS: usize = 20;
+
+/// GitHub caps webhook payloads at 25 MB, so larger deliveries aren't
+/// read before their signature can be checked
+const MAX_PAYLOAD: usize = 25 * 1024 * 1024;
+
+/// Receive GitHub push webhooks and check the pushed repositories again
+/// when a manifest changed, keeping the --report file current without
+/// sweeping every repository
+#[derive(Args, Debug, Clone)]
+pub struct ListenArgs {
+    /// Address to receive the webhooks on
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+
+    /// Secret of the webhook, to verify the signature of its deliveries;
+    /// defaults to GITHUB_WEBHOOK_SECRET
+    #[clap(long)]
+    webhook_secret: Option<String>,
+
+    #[clap(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Deserialize, Debug)]
+struct PushEvent {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    repository: Repository,
+    #[serde(default)]
+    commits: Vec<Commit>,
+    /// Whether the push deleted the branch
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Repository {
+    full_name: String,
+    default_branch: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Commit {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+    #[serde(default)]
+    modified: Vec<String>,
+}
+
+/// Push to a branch of a repository changing one of its manifests
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Push {
+    /// Repository, prefixed with its host when it's on another GitHub
+    /// instance than the default one
+    repo: String,
+    branch: String,
+    default_branch: bool,
+}
+
+impl Push {
+    /// Reads a push event, none when it doesn't change any manifest
+    fn parse(body: &[u8], enterprise_host: Option<&str>) -> std::result::Result<Option<Push>, serde_json::Error> {
+        let event: PushEvent = serde_json::from_slice(body)?;
+        let branch = match event.git_ref.strip_prefix("refs/heads/") {
+            Some(branch) if !event.deleted => branch.to_string(),
+            _ => return Ok(None),
+        };
+        let touched = event.commits.len() >= LISTED_COMMITS || event.commits
+            .iter()
+            .flat_map(|commit| commit.added.iter().chain(&commit.removed).chain(&commit.modified))
+            .any(|path| is_manifest(path));
+        if !touched {
+            return Ok(None);
+        }
+
+        let repo = match enterprise_host {
+            Some(host) if host != github::default_host() => format!("{}/{}", host, event.repository.full_name),
+            _ => event.repository.full_name,
+        };
+        let default_branch = branch == event.repository.default_branch;
+        Ok(Some(Push { repo, branch, default_branch }))
+    }
+
+    /// Entries of the results to check again after the push: those of the
+    /// repository at the pushed branch, or at the default branch for
+    /// entries without a ref. A push to the default branch of a repository
+    /// without results checks it as a whole.
+    fn entries(&self, results: &[Record]) -> BTreeSet<String> {
+        let mut entries: BTreeSet<String> = results
+            .iter()
+            .map(|record| record.repo.as_str())
+            .filter(|entry| {
+                let (repo, git_ref, _) = github::split_entry(entry);
+                repo == self.repo && git_ref.map_or(self.default_branch, |git_ref| git_ref == self.branch)
+            })
+            .map(str::to_string)
+            .collect();
+        if entries.is_empty() && self.default_branch {
+            entries.insert(self.repo.clone());
+        }
+        entries
+    }
+}
+
+/// Whether a path is one of the manifests versions are read from, in any
+/// directory
+fn is_manifest(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    Ecosystem::ALL
+        .iter()
+        .any(|ecosystem| ecosystem.manifest() == name || ecosystem.fallback_manifests().contains(&name))
+}
+
+/// Whether the X-Hub-Signature-256 header of a delivery is the HMAC of its
+/// body with the secret
+fn verify(secret: &[u8], body: &[u8], signature: &str) -> Result<bool> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    let expected: String = signer.sign_to_vec()?.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let expected = format!("sha256={}", expected);
+    Ok(expected.len() == signature.len() && memcmp::eq(expected.as_bytes(), signature.as_bytes()))
+}
+
+/// Reads a body up to a size, none when it's larger
+async fn read_body(mut body: Body, limit: usize) -> std::result::Result<Option<Bytes>, hyper::Error> {
+    if body.size_hint().lower() > limit as u64 {
+        return Ok(None);
+    }
+    let mut read = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if read.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        read.extend_from_slice(&chunk);
+    }
+    Ok(Some(Bytes::from(read)))
+}
+
+pub async fn run(args: ListenArgs, github: GitHub, tracer: &Tracer) -> Result<()> {
+    let report = args.check.report_path().ok_or("listen needs --report to keep the results in")?.to_string();
+    let secret = args.webhook_secret.clone().or_else(|| env::var(SECRET_VAR).ok()).filter(|secret| !secret.is_empty());
+    let secret: Arc<[u8]> = secret.ok_or_else(|| format!("listen needs --webhook-secret or {}", SECRET_VAR))?.into_bytes().into();
+    let policies = check::policies(&github, &args.check)?;
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let make_service = make_service_fn(move |_| {
+        let (secret, sender) = (secret.clone(), sender.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| receive(request, secret.clone(), sender.clone())))
+        }
+    });
+    let server = Server::try_bind(&args.addr)?.serve(make_service);
+    eprintln!("Listening for push webhooks on {}, keeping {} current", args.addr, report);
+
+    let worker = recheck_pushes(receiver, &report, &args.check, github, &policies, tracer);
+    tokio::select! {
+        served = server => served?,
+        () = worker => {}
+    }
+    Ok(())
+}
+
+/// Checks the repositories of the pushes again as they're received, along
+/// with those pushed to in the meantime
+async fn recheck_pushes(
+    mut receiver: UnboundedReceiver<Push>,
+    report: &str,
+    cli: &CheckArgs,
+    github: GitHub,
+    policies: &[NamedPolicy],
+    tracer: &Tracer,
+) {
+    while let Some(push) = receiver.recv().await {
+        let mut pushes = vec![push];
+        while let Ok(push) = receiver.try_recv() {
+            pushes.push(push);
+        }
+
+        let results = if Path::new(report).exists() {
+            match Report::read(report) {
+                Ok(read) => read.results,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        let entries: BTreeSet<String> = pushes.iter().flat_map(|push| push.entries(&results)).collect();
+        if entries.is_empty() {
+            continue;
+        }
+        let entries: Vec<String> = entries.into_iter().collect();
+        eprintln!("Checking {} again after a push", entries.join(", "));
+        if let Err(e) = check::recheck(entries, cli, github.clone(), policies, tracer).await {
+            eprintln!("Unable to check the pushed repositories again: {}", e);
+        }
+    }
+}
+
+async fn receive(request: Request<Body>, secret: Arc<[u8]>, sender: UnboundedSender<Push>) -> std::result::Result<Response<Body>, Infallible> {
+    let respond = |status: StatusCode, message: &str| {
+        let mut response = Response::new(Body::from(message.to_string()));
+        *response.status_mut() = status;
+        Ok(response)
+    };
+    if request.method() != Method::POST {
+        return respond(StatusCode::METHOD_NOT_ALLOWED, "Webhooks are POSTed");
+    }
+
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let (event, signature, enterprise_host) = (header("X-GitHub-Event"), header("X-Hub-Signature-256"), header("X-GitHub-Enterprise-Host"));
+    let body: Bytes = match read_body(request.into_body(), MAX_PAYLOAD).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return respond(StatusCode::PAYLOAD_TOO_LARGE, "Payloads are at most 25 MB"),
+        Err(e) => return respond(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let verified = signature.is_some_and(|signature| verify(&secret, &body, &signature).unwrap_or(false));
+    if !verified {
+        return respond(StatusCode::UNAUTHORIZED, "Invalid signature");
+    }
+
+    match event.as_deref() {
+        Some("ping") => respond(StatusCode::OK, "pong"),
+        Some("push") => match Push::parse(&body, enterprise_host.as_deref()) {
+            Ok(Some(push)) => {
+                let _ = sender.send(push);
+                respond(StatusCode::ACCEPTED, "Checking again")
+            }
+            Ok(None) => respond(StatusCode::OK, "No manifest changed"),
+            Err(e) => respond(StatusCode::BAD_REQUEST, &format!("Invalid push event: {}", e)),
+        },
+        _ => respond(StatusCode::OK, "Only push events are handled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_signatures_of_deliveries() {
+        // Example of GitHub's webhook documentation
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify(b"It's a Secret to Everybody", b"Hello, World!", signature).unwrap());
+        assert!(!verify(b"It's a Secret to Everybody", b"Hello, World?", signature).unwrap());
+        assert!(!verify(b"another secret", b"Hello, World!", signature).unwrap());
+    }
+
+    #[tokio::test]
+    async fn reads_bodies_up_to_a_size() {
+        assert_eq!(read_body(Body::from("Hello, World!"), 13).await.unwrap(), Some(Bytes::from("Hello, World!")));
+        assert_eq!(read_body(Body::from("Hello, World!"), 12).await.unwrap(), None);
+
+        // Chunked bodies don't tell their size up front
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            for _ in 0..4 {
+                if sender.send_data(Bytes::from(vec![b'x'; 1024])).await.is_err() {
+                    break;
+                }
+            }
+        });
+        assert_eq!(read_body(body, 3000).await.unwrap(), None);
+    }
+
+    #[test]
+    fn checks_again_the_entries_of_pushes_changing_a_manifest() {
+        let event = |git_ref: &str, modified: &str| {
+            let body = serde_json::json!({
+                "ref": git_ref,
+                "repository": { "full_name": "org/api", "default_branch": "main" },
+                "commits": [{ "added": [], "removed": [], "modified": [modified] }],
+            });
+            serde_json::to_vec(&body).unwrap()
+        };
+        assert_eq!(Push::parse(&event("refs/heads/main", "README.md"), None).unwrap(), None);
+        assert_eq!(Push::parse(&event("refs/tags/v1.0.0", "package-lock.json"), None).unwrap(), None);
+        let push = Push::parse(&event("refs/heads/main", "packages/ui/yarn.lock"), None).unwrap().unwrap();
+        assert_eq!(push, Push { repo: "org/api".to_string(), branch: "main".to_string(), default_branch: true });
+        let enterprise = Push::parse(&event("refs/heads/main", "go.mod"), Some("ghe.example.com")).unwrap().unwrap();
+        assert_eq!(enterprise.repo, "ghe.example.com/org/api");
+
+        let results: Vec<Record> = ["org/api:packages/ui", "org/api@release/1.2", "org/web"]
+            .iter()
+            .map(|repo| Record::new(repo, "express"))
+            .collect();
+        assert_eq!(push.entries(&results), BTreeSet::from(["org/api:packages/ui".to_string()]));
+        let release = Push::parse(&event("refs/heads/release/1.2", "package-lock.json"), None).unwrap().unwrap();
+        assert_eq!(release.entries(&results), BTreeSet::from(["org/api@release/1.2".to_string()]));
+        assert_eq!(release.entries(&[]), BTreeSet::new());
+        assert_eq!(push.entries(&[]), BTreeSet::from(["org/api".to_string()]));
+    }
+}
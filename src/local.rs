@@ -0,0 +1,200 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ecosystem::Ecosystem;
+use crate::github;
+use crate::lockfile;
+
+/// Prefix of the repository entries of clones on disk, e.g.
+/// local:/home/me/work/api:packages/ui for the project in packages/ui of
+/// the clone in /home/me/work/api
+pub const PREFIX: &str = "local:";
+
+/// Directories never descended into, whatever the .gitignore files say
+const SKIPPED_DIRS: [&str; 2] = [".git", "node_modules"];
+
+/// Returns the directory of the clone of a local entry, none for other
+/// entries
+pub fn root(repo: &str) -> Option<PathBuf> {
+    let (root, _, _) = github::split_entry(repo.strip_prefix(PREFIX)?);
+    Some(PathBuf::from(root))
+}
+
+/// Manifests to read from a directory, in the order they're looked for:
+/// those of the ecosystem, or the lockfiles of every ecosystem before
+/// package.json when it's detected
+pub fn manifests(ecosystem: Option<Ecosystem>) -> Vec<(Ecosystem, &'static str)> {
+    let ecosystems = match ecosystem {
+        Some(ecosystem) => vec![ecosystem],
+        None => Ecosystem::ALL.to_vec(),
+    };
+    let mut manifests: Vec<(Ecosystem, &'static str)> = ecosystems
+        .iter()
+        .flat_map(|&ecosystem| std::iter::once(ecosystem.manifest()).chain(ecosystem.fallback_manifests().iter().copied()).map(move |manifest| (ecosystem, manifest)))
+        .collect();
+    // A package.json next to a go.mod is more likely tooling than the
+    // project
+    manifests.sort_by_key(|(_, manifest)| *manifest == lockfile::PACKAGE_JSON);
+    manifests
+}
+
+/// Line of a .gitignore file
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Directory of the .gitignore file relative to the clone, ending with
+    /// a slash unless it's the root
+    base: String,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Whether the pattern is matched against the path from the base
+    /// rather than the name of the file
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(base: &str, line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.trim_start_matches('/').to_string();
+        (!pattern.is_empty()).then(|| Rule { base: base.to_string(), pattern, negated, dir_only, anchored })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        let relative = match path.strip_prefix(&self.base) {
+            Some(relative) => relative,
+            None => return false,
+        };
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            lockfile::path_matches(&self.pattern, relative)
+        } else {
+            lockfile::glob_matches(&self.pattern, relative.rsplit('/').next().unwrap_or(relative))
+        }
+    }
+}
+
+/// Whether the last rule matching a path ignores it
+fn ignored(rules: &[Rule], path: &str, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// Finds the projects under a directory: every directory holding a
+/// lockfile, skipping those ignored by the .gitignore files of their
+/// clone, as entries prefixed with local: and named after the clone they
+/// belong to, or the directory itself when it isn't in one
+pub fn entries(dir: &str, ecosystem: Option<Ecosystem>) -> io::Result<Vec<String>> {
+    let dir = fs::canonicalize(dir)?;
+    let lockfiles: Vec<&str> = manifests(ecosystem)
+        .into_iter()
+        .map(|(_, manifest)| manifest)
+        .filter(|&manifest| manifest != lockfile::PACKAGE_JSON)
+        .collect();
+    let mut entries = Vec::new();
+    walk(&dir, &dir, "", &mut Vec::new(), &lockfiles, &mut entries)?;
+    Ok(entries)
+}
+
+fn walk(dir: &Path, root: &Path, relative: &str, rules: &mut Vec<Rule>, lockfiles: &[&str], entries: &mut Vec<String>) -> io::Result<()> {
+    // Nested clones are their own projects, with their own .gitignore
+    if !relative.is_empty() && dir.join(".git").exists() {
+        return walk(dir, dir, "", &mut Vec::new(), lockfiles, entries);
+    }
+
+    let kept = rules.len();
+    if let Ok(gitignore) = fs::read_to_string(dir.join(".gitignore")) {
+        let base = if relative.is_empty() { String::new() } else { format!("{}/", relative) };
+        rules.extend(gitignore.lines().filter_map(|line| Rule::parse(&base, line)));
+    }
+
+    let mut children: Vec<(String, bool)> = Vec::new();
+    for child in fs::read_dir(dir)? {
+        let child = child?;
+        // Symbolic links aren't followed, which could loop
+        let is_dir = child.file_type()?.is_dir();
+        children.push((child.file_name().to_string_lossy().into_owned(), is_dir));
+    }
+    children.sort();
+
+    let path_of = |name: &str| if relative.is_empty() { name.to_string() } else { format!("{}/{}", relative, name) };
+    let has_lockfile = children
+        .iter()
+        .any(|(name, is_dir)| !is_dir && lockfiles.contains(&name.as_str()) && !ignored(rules, &path_of(name), false));
+    if has_lockfile {
+        let entry = match relative {
+            "" => format!("{}{}", PREFIX, root.display()),
+            relative => format!("{}{}:{}", PREFIX, root.display(), relative),
+        };
+        entries.push(entry);
+    }
+    for (name, _) in children.iter().filter(|(name, is_dir)| *is_dir && !SKIPPED_DIRS.contains(&name.as_str())) {
+        let path = path_of(name);
+        if !ignored(rules, &path, true) {
+            walk(&dir.join(name), root, &path, rules, lockfiles, entries)?;
+        }
+    }
+
+    rules.truncate(kept);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_lockfiles_not_ignored_by_git() {
+        let work = std::env::temp_dir().join(format!("check-versions-local-{}", std::process::id()));
+        let file = |path: &str, contents: &str| {
+            let path = work.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        file("api/.git/HEAD", "ref: refs/heads/main\n");
+        file("api/.gitignore", "# build output\ndist/\n/vendor\n*.tmp.lock\n");
+        file("api/package-lock.json", "{}");
+        file("api/packages/ui/yarn.lock", "");
+        file("api/packages/ui/dist/package-lock.json", "{}");
+        file("api/vendor/go.mod", "module vendored\n");
+        file("api/node_modules/left-pad/package-lock.json", "{}");
+        file("api/tools/package.json", "{}");
+        file("api/services/.gitignore", "*\n!*/\n!go.mod\n");
+        file("api/services/billing/go.mod", "module billing\n");
+        file("web/.git/HEAD", "ref: refs/heads/main\n");
+        file("web/requirements.txt", "django==4.2\n");
+
+        let root = fs::canonicalize(&work).unwrap().display().to_string();
+        let entries = entries(work.to_str().unwrap(), None).unwrap();
+        assert_eq!(entries, [
+            format!("local:{}/api", root),
+            format!("local:{}/api:packages/ui", root),
+            format!("local:{}/api:services/billing", root),
+            format!("local:{}/web", root),
+        ]);
+        assert_eq!(super::root(&entries[1]).unwrap(), PathBuf::from(format!("{}/api", root)));
+        assert_eq!(github::repo_dir(&entries[1]), Some("packages/ui"));
+        assert_eq!(super::entries(work.to_str().unwrap(), Some(Ecosystem::Go)).unwrap(), [format!("local:{}/api:services/billing", root)]);
+        fs::remove_dir_all(&work).unwrap();
+
+        assert_eq!(manifests(Some(Ecosystem::Go)), [(Ecosystem::Go, "go.mod")]);
+        assert_eq!(manifests(None).last(), Some(&(Ecosystem::Npm, lockfile::PACKAGE_JSON)));
+    }
+}
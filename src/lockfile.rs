@@ -0,0 +1,1203 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::hash::Hash;
+use std::ops::Deref;
+use std::{fmt, io, str};
+
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Manifest read instead of a lockfile when a repository has none
+pub const PACKAGE_JSON: &str = "package.json";
+
+#[derive(Deserialize, Debug)]
+struct PackageJson {
+    name: Option<String>,
+    #[serde(default)]
+    dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default, rename = "devDependencies")]
+    dev_dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default, rename = "optionalDependencies")]
+    optional_dependencies: HashMap<String, serde_json::Value>,
+    #[serde(default, rename = "peerDependencies")]
+    peer_dependencies: HashMap<String, serde_json::Value>,
+}
+
+/// String of a lockfile borrowed from its body, so that multi-MB
+/// lockfiles parse without copying every key and version. Only strings
+/// with escapes are copied.
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+struct Text<'a>(#[serde(borrow)] Cow<'a, str>);
+
+impl Deref for Text<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Text<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl Borrow<str> for Text<'_> {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Packages<'a> {
+    /// Only present on the root package and workspaces
+    #[serde(borrow)]
+    name: Option<Text<'a>>,
+    #[serde(borrow)]
+    version: Option<Text<'a>>,
+    #[serde(borrow)]
+    resolved: Option<Text<'a>>,
+    #[serde(default)]
+    dev: bool,
+    #[serde(default)]
+    link: bool,
+    /// Declared ranges, only present on the root package
+    #[serde(borrow, default)]
+    dependencies: HashMap<Text<'a>, Text<'a>>,
+    #[serde(borrow, default, rename = "devDependencies")]
+    dev_dependencies: HashMap<Text<'a>, Text<'a>>,
+    #[serde(borrow, default, rename = "optionalDependencies")]
+    optional_dependencies: HashMap<Text<'a>, Text<'a>>,
+    #[serde(borrow, default, rename = "peerDependencies")]
+    peer_dependencies: HashMap<Text<'a>, Text<'a>>,
+}
+
+/// Entry of the lockfile v1 `dependencies` tree
+#[derive(Deserialize, Debug)]
+struct Dependency<'a> {
+    #[serde(borrow)]
+    version: Option<Text<'a>>,
+    #[serde(borrow)]
+    resolved: Option<Text<'a>>,
+    #[serde(default)]
+    dev: bool,
+    #[serde(borrow)]
+    dependencies: Option<HashMap<Text<'a>, Dependency<'a>>>,
+}
+
+impl Dependency<'_> {
+    /// Installed version, without the `npm:lodash@` written before the
+    /// versions of aliased packages, e.g. npm:lodash@3.10.1
+    fn installed_version(&self) -> Option<&str> {
+        let version = self.version.as_deref()?;
+        Some(match version.strip_prefix("npm:") {
+            Some(aliased) => aliased.rsplit_once('@').map_or(aliased, |(_, version)| version),
+            None => version,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageLockJson<'a> {
+    #[serde(borrow)]
+    packages: Option<HashMap<Text<'a>, Packages<'a>>>,
+    #[serde(rename = "lockfileVersion")]
+    lockfile_version: Option<i32>,
+    #[serde(borrow)]
+    dependencies: Option<HashMap<Text<'a>, Dependency<'a>>>,
+}
+
+/// Number of packages installed by a lockfile
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Stats {
+    pub total: usize,
+    pub prod: usize,
+    pub dev: usize,
+}
+
+/// How a project depends on a package
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    /// Declared in dependencies or optionalDependencies
+    Direct,
+    /// Declared in devDependencies
+    Dev,
+    /// Declared in peerDependencies
+    Peer,
+    /// Only installed as a dependency of other packages
+    Transitive,
+}
+
+impl DependencyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DependencyKind::Direct => "direct",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Peer => "peer",
+            DependencyKind::Transitive => "transitive",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            DependencyKind::Direct => "direct dependency",
+            DependencyKind::Dev => "dev dependency",
+            DependencyKind::Peer => "peer dependency",
+            DependencyKind::Transitive => "transitive dependency",
+        }
+    }
+}
+
+fn parse(body: &[u8]) -> Result<PackageLockJson<'_>, String> {
+    let body_str = str::from_utf8(body)
+        .map_err(|e| format!("Error converting body to UTF-8: {}", e))?;
+
+    serde_json::from_str(body_str)
+        .map_err(|e| format!("Error parsing JSON: {}", e))
+}
+
+/// A package installed by a lockfile
+struct Installed<'a> {
+    name: &'a str,
+    version: Option<&'a str>,
+    resolved: Option<&'a str>,
+    dev: bool,
+}
+
+/// Lists every package installed by a lockfile, including nested copies
+fn installed<'a>(package_lock_json: &'a PackageLockJson<'_>) -> Vec<Installed<'a>> {
+    fn walk_v1<'a>(dependencies: &'a HashMap<Text<'_>, Dependency<'_>>, installed: &mut Vec<Installed<'a>>) {
+        for (name, dependency) in dependencies {
+            installed.push(Installed {
+                name,
+                version: dependency.installed_version(),
+                resolved: dependency.resolved.as_deref(),
+                dev: dependency.dev,
+            });
+            if let Some(nested) = &dependency.dependencies {
+                walk_v1(nested, installed);
+            }
+        }
+    }
+
+    let mut installed = Vec::new();
+    match &package_lock_json.packages {
+        Some(packages) if package_lock_json.lockfile_version != Some(1) => {
+            for (path, package) in packages {
+                if package.link {
+                    continue;
+                }
+                if let Some((_, name)) = path.rsplit_once("node_modules/") {
+                    installed.push(Installed {
+                        name,
+                        version: package.version.as_deref(),
+                        resolved: package.resolved.as_deref(),
+                        dev: package.dev,
+                    });
+                }
+            }
+        }
+        _ => {
+            if let Some(dependencies) = &package_lock_json.dependencies {
+                walk_v1(dependencies, &mut installed);
+            }
+        }
+    }
+    installed
+}
+
+/// Counts the packages of a package-lock.json body, split into prod and
+/// dev dependencies
+pub fn stats(body: &[u8]) -> Result<Stats, String> {
+    let package_lock_json = parse(body)?;
+    let installed = installed(&package_lock_json);
+
+    let dev = installed.iter().filter(|package| package.dev).count();
+    Ok(Stats { total: installed.len(), prod: installed.len() - dev, dev })
+}
+
+/// Finds the packages installed at more than one version by a
+/// package-lock.json body, with their sorted versions
+pub fn duplicates(body: &[u8]) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let package_lock_json = parse(body)?;
+
+    let mut versions: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for package in installed(&package_lock_json) {
+        if let Some(version) = package.version {
+            versions.entry(package.name.to_string()).or_default().insert(version);
+        }
+    }
+
+    Ok(versions
+        .into_iter()
+        .filter(|(_, versions)| versions.len() > 1)
+        .map(|(name, versions)| (name, versions.into_iter().map(str::to_string).collect()))
+        .collect())
+}
+
+/// Reads the name of a package.json body, none when it has none or isn't
+/// valid
+pub fn package_name(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<PackageJson>(body).ok()?.name
+}
+
+/// Finds the range a package.json body declares for a package in its
+/// dependencies or devDependencies
+pub fn package_json_range(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let package_json: PackageJson = serde_json::from_slice(body)
+        .map_err(|e| format!("Error parsing JSON: {}", e))?;
+    let range = [&package_json.dependencies, &package_json.dev_dependencies]
+        .into_iter()
+        .find_map(|ranges| ranges.get(package_name))
+        .and_then(|range| range.as_str())
+        .map(str::to_string);
+    Ok(range)
+}
+
+/// Tells how a package.json body depends on a package, none when it
+/// doesn't declare it
+pub fn package_json_kind(body: &[u8], package_name: &str) -> Result<Option<DependencyKind>, String> {
+    let package_json: PackageJson = serde_json::from_slice(body)
+        .map_err(|e| format!("Error parsing JSON: {}", e))?;
+    Ok(declared_kind(
+        [&package_json.dependencies, &package_json.optional_dependencies, &package_json.peer_dependencies, &package_json.dev_dependencies],
+        package_name,
+    ))
+}
+
+/// Tells how the root of a package-lock.json body and its workspaces, or
+/// only the given workspace, depend on a package, from the dependencies
+/// the lockfile copies from their package.json. Packages declared nowhere
+/// but installed are transitive. Only lockfiles v2 and later copy them,
+/// so it's none for v1 lockfiles as for packages that aren't installed.
+pub fn dependency_kind(body: &[u8], workspace_dir: Option<&str>, package_name: &str) -> Result<Option<DependencyKind>, String> {
+    let package_lock_json = parse(body)?;
+    let packages = match &package_lock_json.packages {
+        Some(packages) if package_lock_json.lockfile_version != Some(1) => packages,
+        _ => return Ok(None),
+    };
+
+    // The root sorts first, so its declaration wins over the workspaces'
+    let mut declaring: Vec<&str> = match workspace_dir {
+        Some(dir) => vec![dir],
+        None => packages.keys().map(|path| &**path).filter(|path| !path.contains("node_modules/")).collect(),
+    };
+    declaring.sort_unstable();
+    let declared = declaring
+        .into_iter()
+        .filter_map(|path| packages.get(path))
+        .find_map(|package| {
+            let ranges = [&package.dependencies, &package.optional_dependencies, &package.peer_dependencies, &package.dev_dependencies];
+            declared_kind(ranges, package_name)
+        });
+    if declared.is_some() {
+        return Ok(declared);
+    }
+    let installed = installed(&package_lock_json).iter().any(|package| package.name == package_name);
+    Ok(installed.then_some(DependencyKind::Transitive))
+}
+
+/// Kind of the first of the dependencies, optionalDependencies,
+/// peerDependencies and devDependencies of a package declaring a package,
+/// as libraries often declare their peer dependencies as dev dependencies
+/// too
+fn declared_kind<K: Borrow<str> + Eq + Hash, V>(ranges: [&HashMap<K, V>; 4], package_name: &str) -> Option<DependencyKind> {
+    let kinds = [DependencyKind::Direct, DependencyKind::Direct, DependencyKind::Peer, DependencyKind::Dev];
+    kinds.into_iter().zip(ranges).find(|(_, ranges)| ranges.contains_key(package_name)).map(|(kind, _)| kind)
+}
+
+/// Finds the registry URLs a package-lock.json body resolves packages
+/// from that aren't on one of the approved hosts, for one package or for
+/// all of them. Git and local sources aren't registry URLs and are left
+/// out.
+pub fn unapproved_sources(body: &[u8], package_name: Option<&str>, approved: &[String]) -> Result<Vec<String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let sources: BTreeSet<&str> = installed(&package_lock_json)
+        .into_iter()
+        .filter(|package| package_name.is_none_or(|package_name| package.name == package_name))
+        .filter_map(|package| package.resolved)
+        .filter(|resolved| match registry_host(resolved) {
+            Some(host) => !approved.iter().any(|approved| approved == host || host.split(':').next() == Some(approved)),
+            None => false,
+        })
+        .collect();
+    Ok(sources.into_iter().map(str::to_string).collect())
+}
+
+/// Returns the host, with its port if any, of an HTTP(S) URL
+fn registry_host(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+    let authority = rest.split('/').next()?;
+    Some(authority.rsplit('@').next().unwrap_or(authority))
+}
+
+/// Finds the top-level packages whose name matches a glob such as
+/// `@internal/*` in a package-lock.json body, with their locked versions
+pub fn matching(body: &[u8], pattern: &str) -> Result<BTreeMap<String, String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let mut matching = BTreeMap::new();
+    match &package_lock_json.packages {
+        Some(packages) if package_lock_json.lockfile_version != Some(1) => {
+            for (path, package) in packages {
+                if let (Some(name), Some(version)) = (path.strip_prefix("node_modules/"), &package.version) {
+                    if !name.contains("node_modules/") && glob_matches(pattern, name) {
+                        matching.insert(name.to_string(), version.to_string());
+                    }
+                }
+            }
+        }
+        _ => {
+            for (name, dependency) in package_lock_json.dependencies.iter().flatten() {
+                if let (true, Some(version)) = (glob_matches(pattern, name), dependency.installed_version()) {
+                    matching.insert(name.to_string(), version.to_string());
+                }
+            }
+        }
+    }
+    Ok(matching)
+}
+
+/// Matches a name against a pattern where `*` stands for any run of
+/// characters
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let name = match name.strip_prefix(prefix) {
+                Some(name) => name,
+                None => return false,
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| glob_matches(rest, &name[i..]))
+        }
+    }
+}
+
+/// Matches a slash-separated path against a pattern where `**` stands for
+/// any number of directories and `*` for any run of characters within one,
+/// e.g. `services/**` or `**/examples/**`
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|skipped| segments_match(rest, &path[skipped..])),
+            Some((segment, rest)) => match path.split_first() {
+                Some((name, path)) => glob_matches(segment, name) && segments_match(rest, path),
+                None => false,
+            },
+        }
+    }
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_matches('/').split('/').collect();
+    segments_match(&pattern, &path)
+}
+
+/// Finds the range the root package declares for a package in a
+/// package-lock.json body. Only v2 and v3 lockfiles record it.
+pub fn declared_range(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let root = match package_lock_json.packages.as_ref().and_then(|packages| packages.get("")) {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+
+    let range = [&root.dependencies, &root.dev_dependencies, &root.optional_dependencies, &root.peer_dependencies]
+        .into_iter()
+        .find_map(|ranges| ranges.get(package_name))
+        .map(Text::to_string);
+    Ok(range)
+}
+
+/// Finds where a package was installed from when it's a git repository
+/// rather than a registry tarball, in a package-lock.json body
+pub fn git_source(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let (version, resolved) = match (&package_lock_json.packages, package_lock_json.lockfile_version) {
+        (Some(packages), version) if version != Some(1) => {
+            match packages.get(format!("node_modules/{}", package_name).as_str()) {
+                Some(package) => (&package.version, &package.resolved),
+                None => return Ok(None),
+            }
+        }
+        _ => match package_lock_json.dependencies.as_ref().and_then(|dependencies| dependencies.get(package_name)) {
+            Some(dependency) => (&dependency.version, &dependency.resolved),
+            None => return Ok(None),
+        },
+    };
+
+    // v1 lockfiles record git dependencies in version, with no resolved
+    let source = [resolved, version]
+        .into_iter()
+        .flatten()
+        .find(|source| is_git(source))
+        .map(Text::to_string);
+    Ok(source)
+}
+
+/// Finds whether a package is only installed as a dev dependency in a
+/// package-lock.json body, none when it isn't installed
+pub fn is_dev(body: &[u8], package_name: &str) -> Result<Option<bool>, String> {
+    let package_lock_json = parse(body)?;
+
+    let dev = match (&package_lock_json.packages, package_lock_json.lockfile_version) {
+        (Some(packages), version) if version != Some(1) => {
+            packages.get(format!("node_modules/{}", package_name).as_str()).map(|package| package.dev)
+        }
+        _ => package_lock_json.dependencies
+            .as_ref()
+            .and_then(|dependencies| dependencies.get(package_name))
+            .map(|dependency| dependency.dev),
+    };
+    Ok(dev)
+}
+
+/// Most chains listed per package by dependency_chains
+const MAX_CHAINS: usize = 10;
+
+/// Lists the chains of dependencies from the root of a package-lock.json
+/// body to each installed copy of a package, e.g. `app > express@4.18.2 >
+/// debug@2.6.9`, like `npm ls` does. Each copy is reached by its shortest
+/// chain, resolving dependencies from nested node_modules up to the root
+/// as Node does. Needs the `packages` section of lockfiles v2 and later.
+pub fn dependency_chains(body: &[u8], package_name: &str) -> Result<Vec<String>, String> {
+    let package_lock_json = parse(body)?;
+    let packages = match &package_lock_json.packages {
+        Some(packages) if package_lock_json.lockfile_version != Some(1) => packages,
+        _ => return Err("dependency chains need a lockfile v2 or later".to_string()),
+    };
+
+    // Breadth-first from the root, so each path is first reached by one of
+    // its shortest chains
+    // Workspaces are roots of their own, as the root needn't depend on them
+    let mut roots: Vec<&str> = packages.keys().map(|path| &**path).filter(|path| !path.contains("node_modules/")).collect();
+    roots.sort_unstable();
+    let mut parents: HashMap<&str, Option<&str>> = roots.iter().map(|root| (*root, None)).collect();
+    let mut queue: VecDeque<&str> = roots.into_iter().collect();
+    let mut targets = Vec::new();
+    while let Some(path) = queue.pop_front() {
+        let package = match packages.get(path) {
+            Some(package) => package,
+            None => continue,
+        };
+        let dependencies = [&package.dependencies, &package.dev_dependencies, &package.optional_dependencies, &package.peer_dependencies];
+        let mut names: Vec<&str> = dependencies.iter().flat_map(|ranges| ranges.keys().map(|name| &**name)).collect();
+        names.sort_unstable();
+        names.dedup();
+        for name in names {
+            let resolved = match resolve(packages, path, name) {
+                Some(resolved) => resolved,
+                None => continue,
+            };
+            if parents.contains_key(resolved) {
+                continue;
+            }
+            parents.insert(resolved, Some(path));
+            if name == package_name {
+                targets.push(resolved);
+            } else {
+                queue.push_back(resolved);
+            }
+        }
+    }
+
+    let mut chains: Vec<Vec<String>> = targets
+        .into_iter()
+        .map(|target| {
+            let mut chain = Vec::new();
+            let mut current = Some(target);
+            while let Some(path) = current {
+                chain.push(describe_package(packages, path));
+                current = parents.get(path).copied().flatten();
+            }
+            chain.reverse();
+            chain
+        })
+        .collect();
+    // Shortest chains first, as the direct dependencies are the first to
+    // look at
+    chains.sort_unstable_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    Ok(chains.into_iter().take(MAX_CHAINS).map(|chain| chain.join(" > ")).collect())
+}
+
+/// Path of the package a dependency of the package at a path resolves to:
+/// the nearest node_modules holding it, going up to the root, followed to
+/// its target when it's a link such as a workspace
+fn resolve<'a>(packages: &'a HashMap<Text<'_>, Packages<'_>>, from: &str, name: &str) -> Option<&'a str> {
+    let mut dir = from;
+    loop {
+        let candidate = if dir.is_empty() { format!("node_modules/{}", name) } else { format!("{}/node_modules/{}", dir, name) };
+        if let Some((path, package)) = packages.get_key_value(candidate.as_str()) {
+            return match (&package.resolved, package.link) {
+                (Some(target), true) => packages.get_key_value(&**target).map(|(path, _)| &**path),
+                _ => Some(&**path),
+            };
+        }
+        if dir.is_empty() {
+            return None;
+        }
+        dir = dir.rfind("node_modules/").map_or("", |index| dir[..index].trim_end_matches('/'));
+    }
+}
+
+/// Name and version of the package at a path of the lockfile
+fn describe_package(packages: &HashMap<Text<'_>, Packages<'_>>, path: &str) -> String {
+    let package = packages.get(path);
+    let name = package
+        .and_then(|package| package.name.as_deref())
+        .or_else(|| path.rsplit_once("node_modules/").map(|(_, name)| name))
+        .unwrap_or(if path.is_empty() { "root" } else { path });
+    match package.and_then(|package| package.version.as_deref()) {
+        Some(version) if !path.is_empty() => format!("{}@{}", name, version),
+        _ => name.to_string(),
+    }
+}
+
+fn is_git(source: &str) -> bool {
+    ["git+", "git:", "git://", "github:", "gitlab:", "bitbucket:"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+}
+
+/// Finds the locked version of a package in a package-lock.json body.
+/// Returns an error message when the body isn't a valid lockfile.
+pub fn find_version(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let package_lock_json = parse(body)?;
+
+    if let Some(lockfile_version) = package_lock_json.lockfile_version {
+        if lockfile_version == 1 {
+            if let Some(dependencies) = &package_lock_json.dependencies {
+                if let Some(package) = dependencies.get(package_name) {
+                    return Ok(package.installed_version().map(str::to_string));
+                }
+            }
+            return Ok(None);
+        }
+    }
+
+    if let Some(packages) = &package_lock_json.packages {
+        let node_modules_package_name = format!("node_modules/{}", package_name);
+        if let Some(package) = packages.get(node_modules_package_name.as_str()) {
+            return Ok(package.version.as_deref().map(str::to_string));
+        }
+    }
+
+    Ok(None)
+}
+
+/// How find_version resolved a package, for troubleshooting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub lockfile_version: Option<i32>,
+    /// Keys looked up in the lockfile, with the version found under each
+    pub probed: Vec<(String, Option<String>)>,
+    pub version: Option<String>,
+    pub reason: String,
+}
+
+/// Explains the result of find_version for a package-lock.json body: the
+/// lockfile version, the keys probed and why the version is what it is
+pub fn explain(body: &[u8], package_name: &str) -> Result<Explanation, String> {
+    let package_lock_json = parse(body)?;
+    let lockfile_version = package_lock_json.lockfile_version;
+    let mut nested: Vec<String> = installed(&package_lock_json)
+        .iter()
+        .filter(|package| package.name == package_name)
+        .filter_map(|package| package.version.map(str::to_string))
+        .collect();
+    nested.sort();
+    nested.dedup();
+    let elsewhere = if nested.is_empty() {
+        format!("{} isn't installed anywhere in the lockfile", package_name)
+    } else {
+        format!("only nested copies of {} are installed ({})", package_name, nested.join(", "))
+    };
+
+    if lockfile_version == Some(1) {
+        let key = format!("dependencies[{:?}]", package_name);
+        let dependency = package_lock_json.dependencies.as_ref().and_then(|dependencies| dependencies.get(package_name));
+        let version = dependency.and_then(|dependency| dependency.installed_version().map(str::to_string));
+        let reason = match dependency {
+            Some(_) if version.is_some() => format!("{} is a top-level dependency of the v1 lockfile", key),
+            Some(_) => format!("{} has no version", key),
+            None => format!("{} is missing: {}", key, elsewhere),
+        };
+        return Ok(Explanation { lockfile_version, probed: vec![(key, version.clone())], version, reason });
+    }
+
+    let key = format!("packages[\"node_modules/{}\"]", package_name);
+    let packages = match &package_lock_json.packages {
+        Some(packages) => packages,
+        None => {
+            let reason = "the lockfile has no packages section, which lockfiles other than v1 need".to_string();
+            return Ok(Explanation { lockfile_version, probed: Vec::new(), version: None, reason });
+        }
+    };
+    let package = packages.get(format!("node_modules/{}", package_name).as_str());
+    let version = package.and_then(|package| package.version.as_deref().map(str::to_string));
+    let reason = match package {
+        Some(_) if version.is_some() => format!("{} is the hoisted copy installed at the root", key),
+        Some(package) => match &package.resolved {
+            Some(resolved) if package.link => format!("{} links to {} and has no version of its own", key, resolved),
+            _ => format!("{} has no version", key),
+        },
+        None => format!("{} is missing: {}", key, elsewhere),
+    };
+    Ok(Explanation { lockfile_version, probed: vec![(key, version.clone())], version, reason })
+}
+
+/// Finds the version of a package used by a workspace of a
+/// package-lock.json body, given the workspace directory: its own nested
+/// copy, or else the hoisted one. None when the workspace doesn't depend on
+/// the package or the lockfile doesn't record workspaces (v1).
+pub fn find_workspace_version(body: &[u8], dir: &str, package_name: &str) -> Result<Option<String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let packages = match &package_lock_json.packages {
+        Some(packages) => packages,
+        None => return Ok(None),
+    };
+    let workspace = match packages.get(dir) {
+        Some(workspace) => workspace,
+        None => return Ok(None),
+    };
+    let depends = [&workspace.dependencies, &workspace.dev_dependencies, &workspace.optional_dependencies, &workspace.peer_dependencies]
+        .iter()
+        .any(|ranges| ranges.contains_key(package_name));
+    if !depends {
+        return Ok(None);
+    }
+
+    let nested = packages.get(format!("{}/node_modules/{}", dir, package_name).as_str());
+    let hoisted = packages.get(format!("node_modules/{}", package_name).as_str());
+    Ok(nested.or(hoisted).and_then(|package| package.version.as_deref().map(str::to_string)))
+}
+
+/// Reads the name a package-lock.json body records for a workspace
+/// directory
+pub fn workspace_name(body: &[u8], dir: &str) -> Option<String> {
+    parse(body).ok()?.packages?.remove(dir)?.name.map(|name| name.to_string())
+}
+
+/// Finds the versions of a package installed at the top level of the root
+/// and of each workspace by a package-lock.json body, keyed by workspace
+/// directory with an empty one for the root. Copies nested under other
+/// packages are left out.
+pub fn workspace_versions(body: &[u8], package_name: &str) -> Result<BTreeMap<String, String>, String> {
+    let package_lock_json = parse(body)?;
+
+    let mut versions = BTreeMap::new();
+    for (path, package) in package_lock_json.packages.iter().flatten() {
+        let dir = match path.strip_suffix(&format!("node_modules/{}", package_name)) {
+            Some(dir) => dir.trim_end_matches('/'),
+            None => continue,
+        };
+        if dir.contains("node_modules") || package.link {
+            continue;
+        }
+        if let Some(version) = &package.version {
+            versions.insert(dir.to_string(), version.to_string());
+        }
+    }
+    Ok(versions)
+}
+
+/// Finds the local path a package is installed from when it's linked from
+/// a workspace or a `file:` dependency rather than a registry, in a
+/// package-lock.json body
+pub fn local_source(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let package_lock_json = parse(body)?;
+    let local = |spec: &str| {
+        spec.strip_prefix("file:")
+            .or_else(|| spec.strip_prefix("workspace:"))
+            .map(str::to_string)
+    };
+
+    let source = match (&package_lock_json.packages, package_lock_json.lockfile_version) {
+        (Some(packages), version) if version != Some(1) => {
+            match packages.get(format!("node_modules/{}", package_name).as_str()) {
+                // Links resolve to the path of the linked package
+                Some(package) if package.link => package.resolved.as_deref().map(str::to_string),
+                Some(package) => package.resolved.as_deref().and_then(local),
+                None => None,
+            }
+        }
+        _ => package_lock_json.dependencies
+            .as_ref()
+            .and_then(|dependencies| dependencies.get(package_name))
+            .and_then(|dependency| dependency.version.as_deref())
+            .and_then(local),
+    };
+    Ok(source)
+}
+
+/// Reads a package-lock.json incrementally and keeps only what checking
+/// the given packages needs: the lockfile version, the root and workspace
+/// entries and the copies of the packages, nested ones included. The
+/// result is a much smaller package-lock.json the other functions read
+/// like the original.
+pub fn prune(reader: impl io::Read, package_names: &[String]) -> Result<Vec<u8>, String> {
+    let mut deserializer = serde_json::Deserializer::from_reader(io::BufReader::new(reader));
+    let pruned = LockfileSeed(package_names)
+        .deserialize(&mut deserializer)
+        .and_then(|pruned| deserializer.end().map(|_| pruned))
+        .map_err(|e| format!("Error parsing JSON: {}", e))?;
+    serde_json::to_vec(&pruned).map_err(|e| format!("Error writing JSON: {}", e))
+}
+
+struct LockfileSeed<'a>(&'a [String]);
+
+impl<'de> DeserializeSeed<'de> for LockfileSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LockfileSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a package-lock.json object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut pruned = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = match key.as_str() {
+                "lockfileVersion" => map.next_value()?,
+                "packages" => Value::Object(map.next_value_seed(PackagesSeed(self.0))?),
+                "dependencies" => Value::Object(map.next_value_seed(DependenciesSeed(self.0))?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+            };
+            pruned.insert(key, value);
+        }
+        Ok(pruned)
+    }
+}
+
+/// Keeps the entries of `packages` without node_modules in their path,
+/// i.e. the root and workspaces, and the copies of the packages
+struct PackagesSeed<'a>(&'a [String]);
+
+impl<'de> DeserializeSeed<'de> for PackagesSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PackagesSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a map of packages")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut pruned = Map::new();
+        while let Some(path) = map.next_key::<String>()? {
+            let wanted = match path.rsplit_once("node_modules/") {
+                Some((_, name)) => self.0.iter().any(|package_name| package_name == name),
+                None => true,
+            };
+            if wanted {
+                pruned.insert(path, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+/// Keeps the entries of a v1 `dependencies` tree that are one of the
+/// packages or have a copy of one nested under them
+struct DependenciesSeed<'a>(&'a [String]);
+
+impl<'de> DeserializeSeed<'de> for DependenciesSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DependenciesSeed<'_> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a map of dependencies")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut pruned = Map::new();
+        while let Some(name) = map.next_key::<String>()? {
+            let wanted = self.0.contains(&name);
+            if let Some(dependency) = map.next_value_seed(DependencySeed(self.0, wanted))? {
+                pruned.insert(name, Value::Object(dependency));
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+/// Keeps a v1 dependency, without its nested dependencies other than the
+/// wanted ones, when it's wanted itself or any of them is
+struct DependencySeed<'a>(&'a [String], bool);
+
+impl<'de> DeserializeSeed<'de> for DependencySeed<'_> {
+    type Value = Option<Map<String, Value>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DependencySeed<'_> {
+    type Value = Option<Map<String, Value>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a dependency")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let DependencySeed(package_names, wanted) = self;
+        let mut dependency = Map::new();
+        let mut nested_wanted = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" | "resolved" | "dev" => {
+                    dependency.insert(key, map.next_value()?);
+                }
+                "dependencies" => {
+                    let nested = map.next_value_seed(DependenciesSeed(package_names))?;
+                    nested_wanted = !nested.is_empty();
+                    dependency.insert(key, Value::Object(nested));
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok((wanted || nested_wanted).then_some(dependency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const V1: &[u8] = include_bytes!("../tests/fixtures/package-lock-v1.json");
+    const V2: &[u8] = include_bytes!("../tests/fixtures/package-lock-v2.json");
+    const V3: &[u8] = include_bytes!("../tests/fixtures/package-lock-v3.json");
+    const WORKSPACES: &[u8] = include_bytes!("../tests/fixtures/package-lock-workspaces.json");
+    /// Written by npm 10.8.2 for the dependencies it bundles
+    const NPM_CLI: &[u8] = include_bytes!("../tests/fixtures/package-lock-npm-cli.json");
+    const FIXTURES: [&[u8]; 5] = [V1, V2, V3, WORKSPACES, NPM_CLI];
+
+    #[test]
+    fn finds_versions_in_every_lockfile_version() {
+        assert_eq!(find_version(V1, "express").unwrap().as_deref(), Some("4.16.0"));
+        assert_eq!(find_version(V1, "qs").unwrap().as_deref(), Some("6.11.0"));
+        assert_eq!(find_version(V2, "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_version(V3, "express").unwrap().as_deref(), Some("4.19.2"));
+        assert_eq!(find_version(V3, "left-pad").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_scoped_and_aliased_packages_by_their_installed_name() {
+        assert_eq!(find_version(V3, "@babel/core").unwrap().as_deref(), Some("7.23.2"));
+        assert_eq!(find_version(V3, "lodash-old").unwrap().as_deref(), Some("3.10.1"));
+        assert_eq!(find_version(V3, "lodash").unwrap(), None);
+    }
+
+    #[test]
+    fn reads_scoped_and_aliased_packages_of_a_real_lockfile() {
+        let version = |body: &[u8], package_name: &str| find_version(body, package_name).unwrap();
+        assert_eq!(version(NPM_CLI, "@npmcli/arborist").as_deref(), Some("7.5.4"));
+        assert_eq!(version(NPM_CLI, "string-width-cjs").as_deref(), Some("4.2.3"));
+        assert_eq!(declared_range(NPM_CLI, "@sigstore/tuf").unwrap().as_deref(), Some("^2.3.4"));
+        assert_eq!(matching(NPM_CLI, "@sigstore/*").unwrap().len(), 6);
+        assert_eq!(duplicates(NPM_CLI).unwrap()["string-width"], ["4.2.3", "5.1.2"]);
+        assert_eq!(stats(NPM_CLI).unwrap().total, 201);
+        assert_eq!(
+            dependency_chains(NPM_CLI, "string-width-cjs").unwrap(),
+            ["npm > glob@10.4.2 > jackspeak@3.4.0 > @isaacs/cliui@8.0.2 > string-width@4.2.3"],
+        );
+
+        // Its v1 tree, read by npm 6, writes aliased versions as
+        // npm:string-width@4.2.3
+        let mut v1: Value = serde_json::from_slice(NPM_CLI).unwrap();
+        v1.as_object_mut().unwrap().remove("packages");
+        v1["lockfileVersion"] = 1.into();
+        let v1 = serde_json::to_vec(&v1).unwrap();
+        assert_eq!(version(&v1, "string-width-cjs").as_deref(), Some("4.2.3"));
+        assert_eq!(version(&v1, "@npmcli/arborist").as_deref(), Some("7.5.4"));
+        let expected = BTreeMap::from([
+            ("string-width-cjs".to_string(), "4.2.3".to_string()),
+            ("strip-ansi-cjs".to_string(), "6.0.1".to_string()),
+            ("wrap-ansi-cjs".to_string(), "7.0.0".to_string()),
+        ]);
+        assert_eq!(matching(&v1, "*-cjs").unwrap(), expected);
+
+        let packages = ["@npmcli/arborist".to_string(), "string-width-cjs".to_string()];
+        let pruned = prune(NPM_CLI, &packages).unwrap();
+        assert!(pruned.len() < NPM_CLI.len() / 10);
+        assert_eq!(version(&pruned, "@npmcli/arborist").as_deref(), Some("7.5.4"));
+        assert_eq!(version(&pruned, "string-width-cjs").as_deref(), Some("4.2.3"));
+        assert_eq!(version(&pruned, "semver"), None);
+    }
+
+    #[test]
+    fn reads_escaped_strings_that_cannot_be_borrowed() {
+        let body = br#"{"lockfileVersion": 3, "packages": {"node_modules/\u0065xpress": {"version": "4.\u0031.0"}}}"#;
+        assert_eq!(find_version(body, "express").unwrap().as_deref(), Some("4.1.0"));
+    }
+
+    #[test]
+    fn rejects_invalid_lockfiles() {
+        assert!(find_version(b"{", "express").is_err());
+        assert!(find_version(&[0xff, 0xfe], "express").is_err());
+    }
+
+    #[test]
+    fn lists_the_chains_pulling_a_package_in() {
+        let body = br#"{"lockfileVersion": 3, "packages": {
+            "": {"name": "app", "dependencies": {"express": "^4.18.0", "debug": "^4.3.0"}},
+            "packages/api": {"name": "api", "version": "1.0.0", "dependencies": {"morgan": "^1.10.0"}},
+            "node_modules/api": {"resolved": "packages/api", "link": true},
+            "node_modules/express": {"version": "4.18.2", "dependencies": {"debug": "2.6.9", "body-parser": "1.20.1"}},
+            "node_modules/express/node_modules/debug": {"version": "2.6.9"},
+            "node_modules/body-parser": {"version": "1.20.1", "dependencies": {"debug": "2.6.9"}},
+            "node_modules/body-parser/node_modules/debug": {"version": "2.6.9"},
+            "node_modules/morgan": {"version": "1.10.0", "dependencies": {"debug": "2.6.9"}},
+            "node_modules/debug": {"version": "4.3.4"}
+        }}"#;
+        assert_eq!(dependency_chains(body, "debug").unwrap(), [
+            "app > debug@4.3.4",
+            "app > express@4.18.2 > debug@2.6.9",
+            "app > express@4.18.2 > body-parser@1.20.1 > debug@2.6.9",
+        ]);
+        assert_eq!(dependency_chains(body, "morgan").unwrap(), ["api@1.0.0 > morgan@1.10.0"]);
+        assert!(dependency_chains(body, "lodash").unwrap().is_empty());
+        assert!(dependency_chains(V1, "express").is_err());
+    }
+
+    #[test]
+    fn counts_installed_packages() {
+        let v1 = stats(V1).unwrap();
+        assert_eq!((v1.total, v1.prod, v1.dev), (6, 5, 1));
+        let v3 = stats(V3).unwrap();
+        assert_eq!((v3.total, v3.prod, v3.dev), (6, 5, 1));
+    }
+
+    #[test]
+    fn finds_duplicates_including_nested_copies() {
+        let expected = BTreeMap::from([("qs".to_string(), vec!["6.11.0".to_string(), "6.5.1".to_string()])]);
+        assert_eq!(duplicates(V1).unwrap(), expected);
+        let expected = BTreeMap::from([("qs".to_string(), vec!["6.11.0".to_string(), "6.12.1".to_string()])]);
+        assert_eq!(duplicates(V3).unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_declared_ranges_from_the_root_package() {
+        assert_eq!(declared_range(V2, "express").unwrap().as_deref(), Some("^4.17.0"));
+        assert_eq!(declared_range(V2, "typescript").unwrap().as_deref(), Some("~4.9.0"));
+        assert_eq!(declared_range(V3, "lodash-old").unwrap().as_deref(), Some("npm:lodash@^3.10.0"));
+        assert_eq!(declared_range(V1, "express").unwrap(), None);
+    }
+
+    #[test]
+    fn matches_package_globs_at_the_top_level() {
+        let matching = matching(V3, "@babel/*").unwrap();
+        assert_eq!(matching, BTreeMap::from([("@babel/core".to_string(), "7.23.2".to_string())]));
+        assert_eq!(super::matching(V3, "qs").unwrap().get("qs").map(String::as_str), Some("6.12.1"));
+    }
+
+    #[test]
+    fn glob_stars_match_any_run_of_characters() {
+        assert!(glob_matches("@internal/*", "@internal/ui"));
+        assert!(glob_matches("*-plugin-*", "eslint-plugin-react"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("@internal/*", "@external/ui"));
+        assert!(!glob_matches("a*c", "abcd"));
+    }
+
+    #[test]
+    fn path_globs_match_directories() {
+        assert!(path_matches("services/**", "services/api/package-lock.json"));
+        assert!(path_matches("**/examples/**", "examples/package-lock.json"));
+        assert!(path_matches("**/examples/**", "packages/ui/examples/demo/package-lock.json"));
+        assert!(path_matches("packages/*/package-lock.json", "packages/ui/package-lock.json"));
+        assert!(!path_matches("packages/*/package-lock.json", "packages/ui/demo/package-lock.json"));
+        assert!(!path_matches("services/**", "tools/services/package-lock.json"));
+    }
+
+    #[test]
+    fn tells_git_local_and_registry_sources_apart() {
+        assert_eq!(git_source(V1, "left-pad").unwrap().as_deref(), Some("github:stevemao/left-pad#5ff9d7d"));
+        assert_eq!(git_source(V1, "express").unwrap(), None);
+        assert_eq!(local_source(V1, "shared").unwrap().as_deref(), Some("../shared"));
+        assert_eq!(local_source(WORKSPACES, "@acme/ui").unwrap().as_deref(), Some("packages/ui"));
+        assert_eq!(local_source(WORKSPACES, "express").unwrap(), None);
+    }
+
+    #[test]
+    fn tells_dev_dependencies_apart() {
+        assert_eq!(is_dev(V1, "jest").unwrap(), Some(true));
+        assert_eq!(is_dev(V1, "express").unwrap(), Some(false));
+        assert_eq!(is_dev(V3, "eslint").unwrap(), Some(true));
+        assert_eq!(is_dev(V3, "left-pad").unwrap(), None);
+    }
+
+    #[test]
+    fn tells_how_packages_are_depended_on() {
+        assert_eq!(dependency_kind(V3, None, "express").unwrap(), Some(DependencyKind::Direct));
+        assert_eq!(dependency_kind(V3, None, "eslint").unwrap(), Some(DependencyKind::Dev));
+        assert_eq!(dependency_kind(V3, None, "qs").unwrap(), Some(DependencyKind::Transitive));
+        assert_eq!(dependency_kind(V3, None, "left-pad").unwrap(), None);
+        assert_eq!(dependency_kind(V1, None, "express").unwrap(), None);
+        assert_eq!(dependency_kind(WORKSPACES, None, "express").unwrap(), Some(DependencyKind::Direct));
+        assert_eq!(dependency_kind(WORKSPACES, Some("packages/ui"), "express").unwrap(), Some(DependencyKind::Transitive));
+
+        let body = br#"{"dependencies": {"express": "^4.0.0"}, "devDependencies": {"react": "^18.0.0", "jest": "^29.0.0"}, "peerDependencies": {"react": ">=17"}}"#;
+        assert_eq!(package_json_kind(body, "express").unwrap(), Some(DependencyKind::Direct));
+        assert_eq!(package_json_kind(body, "react").unwrap(), Some(DependencyKind::Peer));
+        assert_eq!(package_json_kind(body, "jest").unwrap(), Some(DependencyKind::Dev));
+        assert_eq!(package_json_kind(body, "qs").unwrap(), None);
+    }
+
+    #[test]
+    fn flags_unapproved_registries() {
+        let approved = ["registry.npmjs.org".to_string()];
+        assert!(unapproved_sources(V3, None, &approved).unwrap().is_empty());
+        let sources = unapproved_sources(V3, Some("express"), &["npm.example.com".to_string()]).unwrap();
+        assert_eq!(sources, vec!["https://registry.npmjs.org/express/-/express-4.19.2.tgz".to_string()]);
+    }
+
+    #[test]
+    fn resolves_versions_per_workspace() {
+        assert_eq!(find_workspace_version(WORKSPACES, "packages/api", "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_workspace_version(WORKSPACES, "packages/legacy", "express").unwrap().as_deref(), Some("3.21.2"));
+        assert_eq!(find_workspace_version(WORKSPACES, "packages/ui", "express").unwrap(), None);
+        assert_eq!(workspace_name(WORKSPACES, "packages/api").as_deref(), Some("@acme/api"));
+
+        let versions = workspace_versions(WORKSPACES, "express").unwrap();
+        let expected = BTreeMap::from([
+            (String::new(), "4.18.2".to_string()),
+            ("packages/legacy".to_string(), "3.21.2".to_string()),
+        ]);
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn explains_where_versions_come_from() {
+        let explanation = explain(V1, "express").unwrap();
+        assert_eq!(explanation.lockfile_version, Some(1));
+        assert_eq!(explanation.probed, vec![("dependencies[\"express\"]".to_string(), Some("4.16.0".to_string()))]);
+
+        let explanation = explain(V3, "qs").unwrap();
+        assert_eq!(explanation.version.as_deref(), Some("6.12.1"));
+        let explanation = explain(WORKSPACES, "@acme/ui").unwrap();
+        assert_eq!(explanation.version, None);
+        assert!(explanation.reason.contains("links to packages/ui"), "{}", explanation.reason);
+        let explanation = explain(V3, "left-pad").unwrap();
+        assert!(explanation.reason.ends_with("left-pad isn't installed anywhere in the lockfile"), "{}", explanation.reason);
+    }
+
+    #[test]
+    fn reads_declared_ranges_from_package_json() {
+        let body = br#"{"name": "app", "dependencies": {"express": "^4.2.0"}, "devDependencies": {"jest": "~29.0.0"}}"#;
+        assert_eq!(package_json_range(body, "express").unwrap().as_deref(), Some("^4.2.0"));
+        assert_eq!(package_json_range(body, "jest").unwrap().as_deref(), Some("~29.0.0"));
+        assert_eq!(package_json_range(body, "qs").unwrap(), None);
+        assert!(package_json_range(b"[", "express").is_err());
+    }
+
+    #[test]
+    fn prunes_lockfiles_to_the_checked_packages() {
+        let packages = ["qs".to_string()];
+        for fixture in FIXTURES {
+            let pruned = prune(fixture, &packages).unwrap();
+            assert!(pruned.len() < fixture.len());
+            assert_eq!(find_version(&pruned, "qs").unwrap(), find_version(fixture, "qs").unwrap());
+            assert_eq!(find_version(&pruned, "jest").unwrap(), None);
+            assert_eq!(unapproved_sources(&pruned, Some("qs"), &[]).unwrap(), unapproved_sources(fixture, Some("qs"), &[]).unwrap());
+            assert_eq!(declared_range(&pruned, "qs").unwrap(), declared_range(fixture, "qs").unwrap());
+        }
+
+        let packages = ["express".to_string()];
+        let pruned = prune(WORKSPACES, &packages).unwrap();
+        assert_eq!(workspace_versions(&pruned, "express").unwrap(), workspace_versions(WORKSPACES, "express").unwrap());
+        assert_eq!(workspace_name(&pruned, "packages/api").as_deref(), Some("@acme/api"));
+        assert!(prune(&b"{\"packages\": {"[..], &packages).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn mutated_lockfiles_never_panic(
+            fixture in 0..FIXTURES.len(),
+            mutations in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in any::<prop::sample::Index>(),
+        ) {
+            let mut body = FIXTURES[fixture].to_vec();
+            for (index, byte) in mutations {
+                let i = index.index(body.len());
+                body[i] = byte;
+            }
+            body.truncate(truncate.index(body.len() + 1));
+
+            let _ = find_version(&body, "express");
+            let _ = stats(&body);
+            let _ = duplicates(&body);
+            let _ = declared_range(&body, "express");
+            let _ = matching(&body, "@acme/*");
+            let _ = git_source(&body, "express");
+            let _ = local_source(&body, "express");
+            let _ = find_workspace_version(&body, "packages/api", "express");
+            let _ = workspace_versions(&body, "express");
+            let _ = prune(body.as_slice(), &["express".to_string()]);
+        }
+
+        #[test]
+        fn finds_any_locked_package(
+            scope in prop::option::of("[a-z][a-z0-9-]{0,10}"),
+            name in "[a-z][a-z0-9._-]{0,20}",
+            version in (0u32..100, 0u32..100, 0u32..100),
+        ) {
+            let name = match scope {
+                Some(scope) => format!("@{}/{}", scope, name),
+                None => name,
+            };
+            let version = format!("{}.{}.{}", version.0, version.1, version.2);
+            let v3 = serde_json::json!({
+                "lockfileVersion": 3,
+                "packages": { "": {}, format!("node_modules/{}", name): { "version": version } },
+            });
+            let v1 = serde_json::json!({
+                "lockfileVersion": 1,
+                "dependencies": { name.clone(): { "version": version } },
+            });
+
+            for lockfile in [v3, v1] {
+                let body = serde_json::to_vec(&lockfile).unwrap();
+                prop_assert_eq!(find_version(&body, &name).unwrap(), Some(version.clone()));
+            }
+        }
+    }
+}
@@ -1,160 +1,176 @@
 #![deny(warnings)]
 #![warn(rust_2018_idioms)]
 
-use futures::prelude::*;
-use std::collections::HashMap;
 use std::env;
-use std::str;
-use std::fs;
-use tokio;
-
-use hyper::{body, Body, Client, Method, Request};
-use hyper_tls::HttpsConnector;
-use serde::Deserialize;
-use clap::Parser;
-
-#[derive(Deserialize, Debug)]
-struct PackageJson {
-    #[allow(unused)]
-    dependencies: HashMap<String, serde_json::Value>,
-}
-
-#[derive(Deserialize, Debug)]
-struct Packages {
-    #[allow(unused)]
-    version: Option<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct PackageLockJson {
-    #[allow(unused)]
-    packages: Option<HashMap<String, Packages>>,
-    #[allow(unused)]
-    #[serde(rename = "lockfileVersion")]
-    lockfile_version: Option<i32>,
-    #[allow(unused)]
-    dependencies: Option<HashMap<String, Packages>>,
-}
-
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use check_versions::cache::Cache;
+use check_versions::check::{self, CheckArgs, RetryArgs};
+use check_versions::config::{AppConfig, Config};
+use check_versions::discover::{self, DiscoverArgs};
+use check_versions::explain::{self, ExplainArgs};
+use check_versions::github::{self, GitHub};
+use check_versions::history::{self, CompareArgs};
+use check_versions::listen::{self, ListenArgs};
+use check_versions::report::{self, MergeArgs};
+use check_versions::site::{self, SiteArgs};
+use check_versions::telemetry::{self, Tracer};
+use check_versions::Result;
 
 /// Check versions of an npm package given list of repositories
 #[derive(Parser, Debug, Clone)]
 #[clap(version, about, long_about = None)]
+#[clap(subcommand_negates_reqs = true)]
 struct Cli {
-    /// Path of the file containing json list of repositories
-    #[clap(short, long)]
-    repos: String,
-
-    /// Package name to check versions on
-    #[clap(short, long)]
-    package: String
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    check: CheckArgs,
+
+    /// Path of the TOML configuration file; defaults to check-versions.toml
+    /// in ~/.config/check-versions, or in the current directory for its
+    /// [defaults] and [policy] only
+    #[clap(short, long, global = true)]
+    config: Option<String>,
+
+    /// REST API URL of the GitHub instance hosting the repositories not
+    /// prefixed with a host, e.g. https://github.example.com/api/v3 for a
+    /// GitHub Enterprise Server; defaults to GITHUB_API_URL, then
+    /// https://api.github.com
+    #[clap(long, global = true)]
+    api_url: Option<String>,
+
+    /// Base URL of an OTLP/HTTP collector to export spans of the run's
+    /// phases to, e.g. http://localhost:4318; defaults to
+    /// OTEL_EXPORTER_OTLP_ENDPOINT
+    #[clap(long, global = true)]
+    otlp_endpoint: Option<String>,
+
+    /// Token for the GitHub API of the repositories not prefixed with a
+    /// host; defaults to GITHUB_TOKENS, then GITHUB_TOKEN or GHP_TOKEN
+    /// (GHES_TOKEN for GitHub Enterprise Server), then the token of the gh
+    /// CLI, then unauthenticated requests which only read public
+    /// repositories. Given several times, or comma-separated in
+    /// GITHUB_TOKENS, the tokens take turns, each used while its rate
+    /// limit lasts.
+    #[clap(long, global = true)]
+    token: Vec<String>,
+
+    /// ID of a GitHub App to authenticate as instead of with a token, for
+    /// the GitHub API of the repositories not prefixed with a host;
+    /// defaults to the [app] section of the config file
+    #[clap(long, global = true, requires = "app-private-key")]
+    app_id: Option<u64>,
+
+    /// Path of the PEM private key of the GitHub App given with --app-id
+    #[clap(long, global = true, requires = "app-id")]
+    app_private_key: Option<String>,
+
+    /// Installation of the GitHub App to mint tokens of, needed when it's
+    /// installed on several accounts
+    #[clap(long, global = true, requires = "app-id")]
+    app_installation_id: Option<u64>,
+
+    /// Directory to cache GitHub responses in, revalidated with their ETag
+    /// on later runs; defaults to ~/.cache/check-versions
+    #[clap(long, global = true)]
+    cache_dir: Option<String>,
+
+    /// Send every GitHub request without reading or writing the cache
+    #[clap(long, global = true, conflicts_with = "cache-dir")]
+    no_cache: bool,
+
+    /// Print the JSON schema of the --format json rows, the --report file
+    /// and the --history snapshots, tagged with their schemaVersion, and
+    /// exit
+    #[clap(long)]
+    schema: bool,
 }
 
-const PARALLEL_REQUESTS: usize = 100;
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check versions of an npm package given list of repositories (default)
+    Check(CheckArgs),
+    Discover(DiscoverArgs),
+    Explain(ExplainArgs),
+    Listen(ListenArgs),
+    Merge(MergeArgs),
+    Report(CompareArgs),
+    Retry(RetryArgs),
+    Site(SiteArgs),
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    let package_name = cli.package.as_str();
-    let repos_path = cli.repos;
-
-    let data = fs::read_to_string(&repos_path)
-        .expect("Unable to read file");
-
-    let json: Vec<String> = serde_json::from_str(&data)
-        .expect("JSON does not have correct format.");
-
-    let uris = json.iter().map(|repo| {
-        let filename = "package-lock.json";
-        let uri = format!("https://api.github.com/repos/{repo}/contents/{filename}");
-        return uri;
-    });
-
-    let https = HttpsConnector::new();
-
-    let client = Client::builder()
-        .http2_only(true)
-        .build::<_, hyper::Body>(https);
-
-    let version_results = stream::iter(uris)
-        .map(move |uri| {
-            let request = Request::builder()
-                .method(Method::GET)
-                .uri(uri.clone())
-                .header("Authorization", format!("token {}", env::var("GHP_TOKEN").unwrap()))
-                .header("Accept", "application/vnd.github.raw")
-                .header("X-Github-Api-Version", "2022-11-28")
-                .header("User-Agent", "check-versions")
-                .body(Body::empty())
-                .unwrap();
-            let client = client.clone();
-            tokio::spawn(async move {
-                let res = client.request(request).await?;
-                if res.status() == 404 {
-                    println!("{:?}: {:?}", res.status(), uri.clone());
-                }
-                return body::to_bytes(res).await;
-            })
-        })
-        .buffered(PARALLEL_REQUESTS)
-        .map_ok(|body| {
-            let not_found = String::from("-------");
-
-            let body_bytes = body.expect("error: no body");
-
-            let body_str = match str::from_utf8(&body_bytes) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Error converting body to UTF-8: {}", e);
-                    return not_found.clone();
-                }
-            };
-
-            let package_lock_json: PackageLockJson = match serde_json::from_str(body_str) {
-                Ok(json) => json,
-                Err(e) => {
-                    eprintln!("Error parsing JSON: {}", e);
-                    return not_found.clone();
-                }
-            };
-
-            if let Some(lockfile_version) = package_lock_json.lockfile_version {
-                if lockfile_version == 1 {
-                    if let Some(dependencies) = &package_lock_json.dependencies {
-                        if let Some(package) = dependencies.get(package_name) {
-                            if let Some(version) = &package.version {
-                                return version.clone();
-                            }
-                        }
-                    }
-                    return not_found.clone();
-                }
-            }
-
-            if let Some(packages) = &package_lock_json.packages {
-                let node_modules_package_name = format!("node_modules/{}", package_name);
-                if let Some(package) = packages.get(&node_modules_package_name) {
-                    if let Some(version) = &package.version {
-                        return version.clone();
-                    }
-                }
-            }
-
-            return not_found.clone();
-        });
-
-    let versions: Vec<_> = version_results.collect().await;
-    for (i, version) in versions.iter().enumerate() {
-        match version {
-            Ok(version) => {
-                let repos: Vec<&str> = json[i].split('/').collect();
-                println!("{}\t: {}", version.as_str(), repos[1])
-            },
-            Err(e) => eprintln!("JoinError: {}", e),
-        }
+async fn main() {
+    // RUST_LOG=debug logs every request and response, with credentials
+    // redacted
+    pretty_env_logger::init();
+
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    if cli.schema {
+        print!("{}", report::SCHEMA);
+        return Ok(());
+    }
+    let config = Config::load(cli.config.as_deref())?;
+    let defaults = config.defaults.clone();
+    let api_url = cli.api_url.or_else(|| defaults.api_url.clone()).or_else(|| env::var(github::API_URL_VAR).ok());
+    if let Some(api_url) = api_url {
+        github::set_api_url(&api_url)?;
+    }
+    let app = match (cli.app_id, cli.app_private_key) {
+        (Some(app_id), Some(private_key)) => Some(AppConfig { app_id, private_key, installation_id: cli.app_installation_id }),
+        _ => config.app.clone(),
+    };
+    let mut github = GitHub::new(config).with_tokens(cli.token);
+    if let Some(app) = &app {
+        github = github.with_app(app)?;
     }
+    let cache_dir = cli.cache_dir.map(PathBuf::from).or_else(Cache::default_dir);
+    if let Some(dir) = cache_dir.filter(|_| !cli.no_cache) {
+        github = github.with_cache(Cache::new(dir));
+    }
+    let otlp_endpoint = cli.otlp_endpoint.or_else(|| env::var(telemetry::ENDPOINT_VAR).ok());
+    let tracer = Tracer::new(otlp_endpoint.is_some());
 
-    Ok(())
+    let result = match cli.command {
+        Some(Command::Check(mut args)) => {
+            args.apply_defaults(&defaults);
+            check::check(args, github.clone(), &tracer).await
+        }
+        Some(Command::Discover(args)) => discover::run(args, github.clone(), &tracer).await,
+        Some(Command::Explain(args)) => explain::run(args, github.clone()).await,
+        Some(Command::Listen(mut args)) => {
+            args.check.apply_defaults(&defaults);
+            listen::run(args, github.clone(), &tracer).await
+        }
+        Some(Command::Merge(args)) => report::merge(args),
+        Some(Command::Report(args)) => history::compare(args),
+        Some(Command::Retry(mut args)) => {
+            args.check.apply_defaults(&defaults);
+            check::retry(args, github.clone(), &tracer).await
+        }
+        Some(Command::Site(args)) => site::run(args),
+        None => {
+            let mut args = cli.check;
+            args.apply_defaults(&defaults);
+            check::check(args, github.clone(), &tracer).await
+        }
+    };
+
+    // Spans are exported even when the run failed, to see where it did
+    if let Some(endpoint) = &otlp_endpoint {
+        if let Err(e) = tracer.export(&github, endpoint).await {
+            eprintln!("Unable to export spans to {}: {}", endpoint, e);
+        }
+    }
+    result
 }
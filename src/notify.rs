@@ -0,0 +1,207 @@
+use clap::ArgEnum;
+use hyper::Method;
+use serde_json::{json, Value};
+
+use crate::github::GitHub;
+use crate::report::{Record, Report};
+use crate::Result;
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Repositories with problems listed in a notification at most
+const LISTED: usize = 10;
+
+/// Changes between runs that raise an alert
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertOn {
+    /// A repository without problems in the previous run has some now
+    Regression,
+}
+
+/// When to post to the webhook of --notify
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyOn {
+    /// After every run
+    Always,
+    /// Only when the results differ from the previous --history snapshot
+    Change,
+}
+
+/// Payload shape expected by the webhook of --notify
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyFormat {
+    /// Slack incoming webhook message
+    Slack,
+    /// Microsoft Teams message with an adaptive card
+    Teams,
+    /// Discord webhook message with an embed
+    Discord,
+}
+
+/// Posts a summary of the report to a webhook
+pub async fn notify(github: &GitHub, url: &str, format: NotifyFormat, package: &str, report: &Report) -> Result<()> {
+    github.send_json_anonymous(Method::POST, url, &payload(format, package, report)).await?;
+    Ok(())
+}
+
+/// Summary of the report in the payload shape of the webhook: totals,
+/// then the first problem of the first repositories with some
+fn payload(format: NotifyFormat, package: &str, report: &Report) -> Value {
+    let title = format!("check-versions: {}", package);
+    let totals = report.totals();
+    let facts = [
+        ("Repositories checked", totals.checked),
+        ("Using the package", totals.using),
+        ("With problems", totals.with_problems),
+        ("Failed to check", totals.failed),
+    ];
+    let problems: Vec<String> = report.results
+        .iter()
+        .filter_map(|record| Some(format!("{}: {}", record.repo, record.problems().first()?)))
+        .collect();
+    let mut listed = problems.iter().take(LISTED).cloned().collect::<Vec<_>>().join("\n");
+    if problems.len() > LISTED {
+        listed.push_str(&format!("\n…and {} more", problems.len() - LISTED));
+    }
+
+    match format {
+        NotifyFormat::Slack => {
+            let mut text = format!("*{}*\n", title);
+            for (name, count) in facts {
+                text.push_str(&format!("{}: {}\n", name, count));
+            }
+            if !listed.is_empty() {
+                text.push_str(&format!("```{}```", listed));
+            }
+            json!({ "text": text })
+        }
+        NotifyFormat::Teams => {
+            let facts: Vec<Value> = facts
+                .iter()
+                .map(|(name, count)| json!({ "title": name, "value": count.to_string() }))
+                .collect();
+            let mut body = vec![
+                json!({ "type": "TextBlock", "text": title, "weight": "Bolder", "size": "Medium" }),
+                json!({ "type": "FactSet", "facts": facts }),
+            ];
+            if !listed.is_empty() {
+                body.push(json!({ "type": "TextBlock", "text": listed, "wrap": true, "fontType": "Monospace" }));
+            }
+            json!({
+                "type": "message",
+                "attachments": [{
+                    "contentType": "application/vnd.microsoft.card.adaptive",
+                    "content": {
+                        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                        "type": "AdaptiveCard",
+                        "version": "1.4",
+                        "body": body,
+                    },
+                }],
+            })
+        }
+        NotifyFormat::Discord => {
+            let fields: Vec<Value> = facts
+                .iter()
+                .map(|(name, count)| json!({ "name": name, "value": count.to_string(), "inline": true }))
+                .collect();
+            // Red when something needs attention, green otherwise
+            let color = if totals.with_problems > 0 { 0xe01e5a } else { 0x2eb67d };
+            json!({
+                "embeds": [{
+                    "title": title,
+                    "description": if listed.is_empty() { String::new() } else { format!("```{}```", listed) },
+                    "color": color,
+                    "fields": fields,
+                }],
+            })
+        }
+    }
+}
+
+/// Triggers a PagerDuty event per regressed repository. Events are
+/// deduplicated per repository and package, so a repository still out of
+/// policy in later runs doesn't page again while its incident is open.
+pub async fn pagerduty(github: &GitHub, routing_key: &str, regressions: &[&Record]) -> Result<()> {
+    for record in regressions {
+        let problems = record.problems();
+        let payload = json!({
+            "routing_key": routing_key,
+            "event_action": "trigger",
+            "dedup_key": format!("check-versions:{}:{}", record.repo, record.package),
+            "payload": {
+                "summary": format!("{} fell out of policy for {}: {}", record.repo, record.package, problems[0]),
+                "source": record.repo,
+                "severity": "warning",
+                "custom_details": {
+                    "version": record.version,
+                    "problems": problems,
+                },
+            },
+        });
+        github.send_json_anonymous(Method::POST, PAGERDUTY_EVENTS_URL, &payload).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(failed: usize) -> Report {
+        let mut results = vec![Record::new("org/api", "express")];
+        results[0].version = Some("4.18.2".to_string());
+        for i in 0..failed {
+            let mut record = Record::new(&format!("org/repo-{:02}", i), "express");
+            record.error = Some("timed out".to_string());
+            results.push(record);
+        }
+        Report::new(results)
+    }
+
+    #[test]
+    fn shapes_slack_messages() {
+        assert_eq!(payload(NotifyFormat::Slack, "express", &report(1)), json!({
+            "text": "*check-versions: express*\nRepositories checked: 2\nUsing the package: 1\nWith problems: 1\nFailed to check: 1\n```org/repo-00: could not be checked: timed out```",
+        }));
+        let quiet = payload_text(NotifyFormat::Slack, &report(0));
+        assert!(!quiet.contains("```"));
+    }
+
+    #[test]
+    fn shapes_teams_cards() {
+        let message = payload(NotifyFormat::Teams, "express", &report(1));
+        let card = &message["attachments"][0];
+        assert_eq!(message["type"], "message");
+        assert_eq!(card["contentType"], "application/vnd.microsoft.card.adaptive");
+        let body = card["content"]["body"].as_array().unwrap();
+        assert_eq!(body[0]["text"], "check-versions: express");
+        assert_eq!(body[1]["facts"][0], json!({ "title": "Repositories checked", "value": "2" }));
+        assert_eq!(body[2]["text"], "org/repo-00: could not be checked: timed out");
+        assert_eq!(payload(NotifyFormat::Teams, "express", &report(0))["attachments"][0]["content"]["body"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn shapes_discord_embeds() {
+        let message = payload(NotifyFormat::Discord, "express", &report(1));
+        let embed = &message["embeds"][0];
+        assert_eq!(embed["title"], "check-versions: express");
+        assert_eq!(embed["color"], 0xe01e5a);
+        assert_eq!(embed["fields"][3], json!({ "name": "Failed to check", "value": "1", "inline": true }));
+        assert_eq!(embed["description"], "```org/repo-00: could not be checked: timed out```");
+        let healthy = payload(NotifyFormat::Discord, "express", &report(0));
+        assert_eq!((&healthy["embeds"][0]["color"], &healthy["embeds"][0]["description"]), (&json!(0x2eb67d), &json!("")));
+    }
+
+    #[test]
+    fn lists_the_first_problems_only() {
+        let text = payload_text(NotifyFormat::Slack, &report(LISTED + 3));
+        assert_eq!(text.matches("could not be checked").count(), LISTED);
+        assert!(text.contains("org/repo-09: could not be checked: timed out\n…and 3 more```"));
+        assert!(!text.contains("org/repo-10"));
+    }
+
+    fn payload_text(format: NotifyFormat, report: &Report) -> String {
+        payload(format, "express", report)["text"].as_str().unwrap().to_string()
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use crate::Result;
+
+/// Number of matches listed at once; narrowing the query shows the others
+const LISTED: usize = 20;
+
+const HELP: &str = "Type a query to fuzzy-filter the repositories, numbers or ranges (1 3-5) \
+to toggle listed ones, * to toggle every match, ! to show the selection, an empty line to \
+run with the selection or q to abort.";
+
+/// Lets the user pick repositories on the terminal, fuzzy-filtering them
+/// by a query. Reads from the terminal rather than stdin, which may be
+/// the piped list of repositories.
+pub fn pick(repos: Vec<String>) -> Result<Vec<String>> {
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("--interactive needs a terminal: {}", e))?;
+    let mut input = BufReader::new(tty.try_clone()?);
+    let mut output: File = tty;
+
+    let mut selected: BTreeSet<usize> = BTreeSet::new();
+    let mut query = String::new();
+    writeln!(output, "{} repositories. {}", repos.len(), HELP)?;
+    loop {
+        let matches = matching(&repos, &query);
+        for (i, &index) in matches.iter().take(LISTED).enumerate() {
+            let mark = if selected.contains(&index) { 'x' } else { ' ' };
+            writeln!(output, "{:>3} [{}] {}", i + 1, mark, repos[index])?;
+        }
+        if matches.len() > LISTED {
+            writeln!(output, "    ... {} more matches", matches.len() - LISTED)?;
+        }
+        write!(output, "{} selected, query {:?}> ", selected.len(), query)?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err("Interactive selection aborted".into());
+        }
+        let line = line.trim();
+        match line {
+            "" if selected.is_empty() => writeln!(output, "No repository selected yet")?,
+            "" => return Ok(selected.into_iter().map(|index| repos[index].clone()).collect()),
+            "q" => return Err("Interactive selection aborted".into()),
+            "?" => writeln!(output, "{}", HELP)?,
+            "!" => {
+                for &index in &selected {
+                    writeln!(output, "    {}", repos[index])?;
+                }
+            }
+            "*" => {
+                for &index in &matches {
+                    if !selected.remove(&index) {
+                        selected.insert(index);
+                    }
+                }
+            }
+            _ => match positions(line) {
+                Some(positions) => {
+                    for position in positions {
+                        match matches.get(position.wrapping_sub(1)).filter(|_| position <= LISTED) {
+                            Some(&index) => {
+                                if !selected.remove(&index) {
+                                    selected.insert(index);
+                                }
+                            }
+                            None => writeln!(output, "No listed repository {}", position)?,
+                        }
+                    }
+                }
+                None => query = line.to_string(),
+            },
+        }
+    }
+}
+
+/// Parses a toggle command such as `1 3-5`, none when the line is a query
+fn positions(line: &str) -> Option<Vec<usize>> {
+    let mut positions = Vec::new();
+    for part in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+                positions.extend(start..=end);
+            }
+            None => positions.push(part.parse().ok()?),
+        }
+    }
+    Some(positions)
+}
+
+/// Indexes of the repositories matching a query, best matches first
+fn matching(repos: &[String], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = repos
+        .iter()
+        .enumerate()
+        .filter_map(|(index, repo)| score(query, repo).map(|score| (score, index)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Scores how well a candidate matches a query whose characters must all
+/// appear in it in order, case-insensitively. Consecutive characters and
+/// characters starting a word score higher; none when it doesn't match.
+fn score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let wanted = wanted.to_lowercase().next()?;
+        let found = (next..candidate.len()).find(|&i| candidate[i].to_lowercase().next() == Some(wanted))?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(candidate[found - 1], '/' | '-' | '_' | '.') {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    // Shorter candidates are closer matches
+    Some(score * 100 - candidate.len() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_characters_in_order() {
+        assert!(score("acweb", "acme/website").is_some());
+        assert!(score("ACME", "acme/website").is_some());
+        assert!(score("webacme", "acme/website").is_none());
+        assert!(score("", "acme/website").is_some());
+    }
+
+    #[test]
+    fn ranks_consecutive_and_word_start_matches_first() {
+        let repos = ["acme/api-gateway", "acme/legacy-app", "acme/app"].map(String::from);
+        assert_eq!(matching(&repos, "app"), vec![2, 1]);
+        assert_eq!(matching(&repos, "gw"), vec![0]);
+    }
+
+    #[test]
+    fn parses_toggle_commands() {
+        assert_eq!(positions("1 3-5,7"), Some(vec![1, 3, 4, 5, 7]));
+        assert_eq!(positions("api"), None);
+        assert_eq!(positions("2-x"), None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::str;
+
+use semver::Version;
+
+pub const MANIFEST: &str = "pnpm-lock.yaml";
+
+/// Finds the version of a package in a pnpm-lock.yaml body from the keys of
+/// its `packages` section: `/name/1.0.0` up to lockfile version 5,
+/// `/name@1.0.0` in version 6 and `name@1.0.0` from version 9. When several
+/// versions are installed, the newest one is returned.
+pub fn find_version(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let body_str = str::from_utf8(body)
+        .map_err(|e| format!("Error converting body to UTF-8: {}", e))?;
+
+    let mut lockfile_version = None;
+    let mut in_packages = false;
+    let mut versions = Vec::new();
+    for line in body_str.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            if let Some(version) = line.strip_prefix("lockfileVersion:") {
+                let version = version.trim().trim_matches(|c| c == '\'' || c == '"');
+                let major = version.split('.').next().unwrap_or_default();
+                lockfile_version = Some(major.parse::<u32>().map_err(|_| format!("Unsupported lockfileVersion {:?}", version))?);
+            }
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if !in_packages {
+            continue;
+        }
+
+        // Keys of the packages are indented by two spaces
+        let key = match line.strip_prefix("  ") {
+            Some(key) if !key.starts_with(' ') => key,
+            _ => continue,
+        };
+        let key = key.trim_end().strip_suffix(':').unwrap_or(key).trim_matches(|c| c == '\'' || c == '"');
+        let lockfile_version = lockfile_version.ok_or("Error parsing pnpm-lock.yaml: no lockfileVersion before packages")?;
+        if let Some((name, version)) = parse_key(key, lockfile_version) {
+            if name == package_name {
+                versions.push(version);
+            }
+        }
+    }
+
+    versions.sort_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    });
+    versions.dedup();
+    Ok(versions.pop().map(str::to_string))
+}
+
+/// Splits a key of the packages section into the package name and version,
+/// dropping the peer dependencies suffix: `_react@18.2.0` up to lockfile
+/// version 5, `(react@18.2.0)` after
+fn parse_key(key: &str, lockfile_version: u32) -> Option<(&str, &str)> {
+    if lockfile_version <= 5 {
+        let (name, version) = key.strip_prefix('/')?.rsplit_once('/')?;
+        let version = version.split('_').next()?;
+        return Some((name, version));
+    }
+
+    let key = key.strip_prefix('/').unwrap_or(key);
+    let key = key.split('(').next()?;
+    let at = key.char_indices().skip(1).find(|&(_, c)| c == '@')?.0;
+    Some((&key[..at], &key[at + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const V5: &[u8] = include_bytes!("../tests/fixtures/pnpm-lock-v5.yaml");
+    const V6: &[u8] = include_bytes!("../tests/fixtures/pnpm-lock-v6.yaml");
+    const V9: &[u8] = include_bytes!("../tests/fixtures/pnpm-lock-v9.yaml");
+
+    #[test]
+    fn finds_versions_in_every_lockfile_version() {
+        assert_eq!(find_version(V5, "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_version(V6, "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_version(V9, "express").unwrap().as_deref(), Some("4.19.2"));
+        assert_eq!(find_version(V9, "qs").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_scoped_and_peer_resolved_packages() {
+        assert_eq!(find_version(V5, "@babel/core").unwrap().as_deref(), Some("7.23.2"));
+        assert_eq!(find_version(V6, "@babel/core").unwrap().as_deref(), Some("7.23.2"));
+        assert_eq!(find_version(V9, "@babel/core").unwrap().as_deref(), Some("7.24.0"));
+        assert_eq!(find_version(V5, "react-dom").unwrap().as_deref(), Some("18.2.0"));
+        assert_eq!(find_version(V6, "react-dom").unwrap().as_deref(), Some("18.2.0"));
+        assert_eq!(find_version(V5, "react").unwrap(), None);
+    }
+
+    #[test]
+    fn returns_the_newest_installed_version() {
+        assert_eq!(find_version(V6, "qs").unwrap().as_deref(), Some("6.11.0"));
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_pnpm_lock_never_panics(body in any::<Vec<u8>>(), name in "\\PC*") {
+            let _ = find_version(&body, &name);
+        }
+    }
+}
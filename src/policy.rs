@@ -0,0 +1,110 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use crate::config::{PolicyConfig, PolicyMode, Rule, Severity};
+use crate::errors::Error;
+use crate::report::Record;
+use crate::Result;
+
+/// Environment variable disabling colored output when set, see
+/// https://no-color.org
+const NO_COLOR_VAR: &str = "NO_COLOR";
+
+/// Policy evaluated against the results of a run, named after the file it
+/// was read from
+#[derive(Debug, Clone)]
+pub struct NamedPolicy {
+    pub name: String,
+    pub policy: PolicyConfig,
+}
+
+/// Reads a policy file given with --policy, which holds the keys of the
+/// [policy] section of the config file
+pub fn read(path: &str) -> Result<NamedPolicy> {
+    let data = fs::read_to_string(path)
+        .map_err(|source| Error::Read { path: path.to_string(), source })?;
+    let policy = toml::from_str(&data)
+        .map_err(|e| format!("{} is not a valid policy: {}", path, e))?;
+    let name = Path::new(path).file_stem().map_or_else(|| path.to_string(), |stem| stem.to_string_lossy().into_owned());
+    Ok(NamedPolicy { name, policy })
+}
+
+/// Rule of the kind of dependency the package of a record is, none when
+/// the record has no problems
+fn rule(policy: &PolicyConfig, record: &Record) -> Option<Rule> {
+    if record.problems().is_empty() {
+        None
+    } else if record.dev == Some(true) {
+        Some(policy.dev)
+    } else {
+        Some(policy.prod)
+    }
+}
+
+/// Severity of a record under its rule, with fail lowered to warn when the
+/// rule, or without a mode of its own the run, is in warn mode
+pub fn severity(policy: &PolicyConfig, record: &Record, mode: PolicyMode) -> Severity {
+    match rule(policy, record) {
+        Some(rule) if rule.mode().unwrap_or(mode) == PolicyMode::Warn => rule.severity().min(Severity::Warn),
+        Some(rule) => rule.severity(),
+        None => Severity::Ignore,
+    }
+}
+
+/// Whether a record only warns because its failing rule is in warn mode,
+/// and will fail once the rule is enforced
+pub fn deferred(policy: &PolicyConfig, record: &Record, mode: PolicyMode) -> bool {
+    rule(policy, record).is_some_and(|rule| rule.severity() == Severity::Fail)
+        && severity(policy, record, mode) == Severity::Warn
+}
+
+/// Whether rows printed to stdout may be colored
+pub fn colored() -> bool {
+    io::stdout().is_terminal() && env::var_os(NO_COLOR_VAR).is_none()
+}
+
+/// Colors a row by its severity with ANSI escapes: red to fail, yellow to
+/// warn
+pub fn paint(row: &str, severity: Severity) -> String {
+    match severity {
+        Severity::Fail => format!("\x1b[31m{}\x1b[0m", row),
+        Severity::Warn => format!("\x1b[33m{}\x1b[0m", row),
+        Severity::Ignore => row.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_problems_by_dependency_kind() {
+        let policy = PolicyConfig { prod: Rule::Severity(Severity::Fail), dev: Rule::Severity(Severity::Warn), migrations: Default::default() };
+        let mut record = Record::new("org/api", "express");
+        record.version = Some("4.17.1".to_string());
+        assert_eq!(severity(&policy, &record, PolicyMode::Enforce), Severity::Ignore);
+
+        record.latest = Some("4.18.2".to_string());
+        assert_eq!(severity(&policy, &record, PolicyMode::Enforce), Severity::Fail);
+        record.dev = Some(true);
+        assert_eq!(severity(&policy, &record, PolicyMode::Enforce), Severity::Warn);
+        assert!(!deferred(&policy, &record, PolicyMode::Warn));
+    }
+
+    #[test]
+    fn only_warns_on_rules_in_warn_mode() {
+        let policy: PolicyConfig = toml::from_str(r#"dev = { severity = "fail", mode = "warn" }"#).unwrap();
+        let mut record = Record::new("org/api", "express");
+        record.version = Some("4.17.1".to_string());
+        record.latest = Some("4.18.2".to_string());
+        assert_eq!(severity(&policy, &record, PolicyMode::Enforce), Severity::Fail);
+        assert_eq!(severity(&policy, &record, PolicyMode::Warn), Severity::Warn);
+        assert!(deferred(&policy, &record, PolicyMode::Warn));
+
+        record.dev = Some(true);
+        assert_eq!(severity(&policy, &record, PolicyMode::Enforce), Severity::Warn);
+        assert!(deferred(&policy, &record, PolicyMode::Enforce));
+    }
+}
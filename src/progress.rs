@@ -0,0 +1,97 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Width of the bar, in characters
+const WIDTH: usize = 24;
+
+/// Progress of a run drawn on the last line of stderr while results come
+/// in: repositories checked out of the total, failures so far and the
+/// requests left before the GitHub rate limit. Only drawn on a terminal,
+/// and never with --quiet.
+pub struct Progress {
+    enabled: bool,
+    /// Repositories to check, none when they're read as a stream
+    total: Option<usize>,
+    done: usize,
+    failed: usize,
+    /// Last repository counted, as --scan yields several results per
+    /// repository
+    last_repo: Option<String>,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn new(quiet: bool, total: Option<usize>) -> Progress {
+        Progress {
+            enabled: !quiet && io::stderr().is_terminal(),
+            total,
+            done: 0,
+            failed: 0,
+            last_repo: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Counts a result of a repository
+    pub fn advance(&mut self, repo: &str, failed: bool) {
+        if self.last_repo.as_deref() != Some(repo) {
+            self.done += 1;
+            self.last_repo = Some(repo.to_string());
+            if failed {
+                self.failed += 1;
+            }
+        }
+    }
+
+    /// Draws the bar over the previous one
+    pub fn draw(&self, rate_limit_remaining: Option<u64>) {
+        if self.enabled {
+            let mut stderr = io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K{}", line(self.done, self.total, self.failed, rate_limit_remaining, self.started.elapsed()));
+            let _ = stderr.flush();
+        }
+    }
+
+    /// Erases the bar, before printing anything else
+    pub fn clear(&self) {
+        if self.enabled {
+            let _ = write!(io::stderr(), "\r\x1b[2K");
+        }
+    }
+}
+
+/// Renders the progress, e.g. `[######------] 120/500 repositories, 3
+/// failed, 4211 requests left, 0:42`
+fn line(done: usize, total: Option<usize>, failed: usize, rate_limit_remaining: Option<u64>, elapsed: Duration) -> String {
+    let mut line = match total {
+        Some(total) => {
+            let filled = (done * WIDTH).checked_div(total).unwrap_or(WIDTH).min(WIDTH);
+            format!("[{}{}] {}/{} repositories", "#".repeat(filled), "-".repeat(WIDTH - filled), done, total)
+        }
+        None => format!("{} repositories", done),
+    };
+    if failed > 0 {
+        line.push_str(&format!(", {} failed", failed));
+    }
+    if let Some(remaining) = rate_limit_remaining {
+        line.push_str(&format!(", {} requests left", remaining));
+    }
+    let secs = elapsed.as_secs();
+    line.push_str(&format!(", {}:{:02}", secs / 60, secs % 60));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_progress_lines() {
+        assert_eq!(
+            line(120, Some(480), 3, Some(4211), Duration::from_secs(42)),
+            "[######------------------] 120/480 repositories, 3 failed, 4211 requests left, 0:42",
+        );
+        assert_eq!(line(0, Some(0), 0, None, Duration::from_secs(61)), "[########################] 0/0 repositories, 1:01");
+        assert_eq!(line(7, None, 0, None, Duration::ZERO), "7 repositories, 0:00");
+    }
+}
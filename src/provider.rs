@@ -0,0 +1,26 @@
+use crate::{gitlab, local};
+
+/// Service hosting a repository, told by the prefix of its entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// GitHub or GitHub Enterprise Server, for entries such as org/repo or
+    /// ghe.example.com/org/repo
+    GitHub,
+    /// GitLab, for entries such as gitlab:group/subgroup/project
+    GitLab,
+    /// Clone on disk found by --local, for entries such as
+    /// local:/home/me/work/api
+    Local,
+}
+
+impl Provider {
+    pub fn of(repo: &str) -> Provider {
+        if repo.starts_with(gitlab::PREFIX) {
+            Provider::GitLab
+        } else if repo.starts_with(local::PREFIX) {
+            Provider::Local
+        } else {
+            Provider::GitHub
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use futures::prelude::*;
+use hyper::Method;
+use serde_json::json;
+
+use crate::github::{self, GitHub};
+use crate::report::{Record, Report};
+use crate::Result;
+
+const PARALLEL_REQUESTS: usize = 10;
+
+/// Name of the check runs created by --publish-check-run
+const CHECK_RUN_NAME: &str = "check-versions";
+
+/// Summarizes a record in Markdown, for check runs and statuses
+fn summary(record: &Record) -> String {
+    let version = record.version.as_deref().unwrap_or("not found");
+    let mut summary = format!("**{}**: {}\n", record.package, version);
+    let problems = record.problems();
+    if !problems.is_empty() {
+        summary.push('\n');
+        for problem in problems {
+            summary.push_str(&format!("- {}\n", problem));
+        }
+    }
+    summary
+}
+
+/// Creates a completed check run on the inspected commit of every record,
+/// failing when problems were found. When several packages were checked,
+/// each gets its own check run named after it. Creating check runs requires
+/// a GitHub App installation token.
+pub async fn check_runs(github: &GitHub, records: &[Record], per_package: bool) -> Result<()> {
+    stream::iter(records.iter().filter(|record| record.sha.is_some()))
+        .map(|record| async move {
+            let (host, full_name) = github::parse_repo(&record.repo);
+            let conclusion = if !record.problems().is_empty() {
+                "failure"
+            } else if record.version.is_none() {
+                "neutral"
+            } else {
+                "success"
+            };
+            let name = if per_package {
+                format!("{}: {}", CHECK_RUN_NAME, record.package)
+            } else {
+                CHECK_RUN_NAME.to_string()
+            };
+            let body = json!({
+                "name": name,
+                "head_sha": record.sha,
+                "status": "completed",
+                "conclusion": conclusion,
+                "output": {
+                    "title": format!("{} {}", record.package, record.version.as_deref().unwrap_or("not found")),
+                    "summary": summary(record),
+                },
+            });
+            let uri = format!("{}/repos/{full_name}/check-runs", github::api_url(host));
+            if let Err(e) = github.send_json(Method::POST, &uri, &body).await {
+                eprintln!("Unable to publish check run on {}: {}", record.repo, e);
+            }
+        })
+        .buffer_unordered(PARALLEL_REQUESTS)
+        .collect::<()>()
+        .await;
+    Ok(())
+}
+
+/// Sets a commit status with the given context on the inspected commit of
+/// every record: failure when problems were found, success otherwise so
+/// that fixed repositories clear their earlier failure. When several packages
+/// were checked, the context is suffixed with the package of each status.
+pub async fn statuses(github: &GitHub, records: &[Record], context: &str, per_package: bool) -> Result<()> {
+    stream::iter(records.iter().filter(|record| record.sha.is_some()))
+        .map(|record| async move {
+            let (host, full_name) = github::parse_repo(&record.repo);
+            let problems = record.problems();
+            let (state, mut description) = match problems.first() {
+                Some(problem) if problems.len() > 1 => ("failure", format!("{} (+{} more)", problem, problems.len() - 1)),
+                Some(problem) => ("failure", problem.clone()),
+                None => ("success", format!("{} {}", record.package, record.version.as_deref().unwrap_or("not found"))),
+            };
+            // GitHub rejects descriptions longer than 140 characters
+            if description.chars().count() > 140 {
+                description = description.chars().take(139).chain(['…']).collect();
+            }
+            let context = if per_package {
+                format!("{}/{}", context, record.package)
+            } else {
+                context.to_string()
+            };
+            let body = json!({
+                "state": state,
+                "context": context,
+                "description": description,
+            });
+            let sha = record.sha.as_deref().unwrap_or_default();
+            let uri = format!("{}/repos/{full_name}/statuses/{sha}", github::api_url(host));
+            if let Err(e) = github.send_json(Method::POST, &uri, &body).await {
+                eprintln!("Unable to set commit status on {}: {}", record.repo, e);
+            }
+        })
+        .buffer_unordered(PARALLEL_REQUESTS)
+        .collect::<()>()
+        .await;
+    Ok(())
+}
+
+/// Creates or updates the digest issue of the package in a central
+/// repository with the rendered report, returning its URL. The issue is
+/// found again by its title, so every run of a package updates the same one.
+pub async fn digest_issue(github: &GitHub, repo: &str, package: &str, report: &Report) -> Result<String> {
+    let (host, full_name) = github::parse_repo(repo);
+    let title = format!("check-versions: {}", package);
+    let body = json!({
+        "title": title,
+        "body": report.to_markdown(),
+    });
+
+    let issues = github.open_issues(host, full_name).await?;
+    let issue = match issues.iter().find(|issue| issue.title == title) {
+        Some(issue) => {
+            let uri = format!("{}/repos/{full_name}/issues/{}", github::api_url(host), issue.number);
+            github.send_json(Method::PATCH, &uri, &body).await?
+        }
+        None => {
+            let uri = format!("{}/repos/{full_name}/issues", github::api_url(host));
+            github.send_json(Method::POST, &uri, &body).await?
+        }
+    };
+    Ok(issue["html_url"].as_str().unwrap_or_default().to_string())
+}
+
+/// Uploads the report as Markdown and JSON to a secret gist on github.com,
+/// returning its URL
+pub async fn gist(github: &GitHub, package: &str, report: &Report) -> Result<String> {
+    let body = json!({
+        "description": format!("check-versions report of {}", package),
+        "public": false,
+        "files": {
+            "report.md": { "content": report.to_markdown() },
+            "report.json": { "content": report.to_json()? },
+        },
+    });
+    let uri = format!("{}/gists", github::api_url(github::default_host()));
+    let gist = github.send_json(Method::POST, &uri, &body).await?;
+    Ok(gist["html_url"].as_str().unwrap_or_default().to_string())
+}
@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::Result;
+
+/// How long a quarantined repository is skipped before it's checked again
+const QUARANTINE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Repositories timing out run after run, kept in the --quarantine file so
+/// that those timing out too often are skipped for a while instead of
+/// holding up every run
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quarantine {
+    #[serde(flatten)]
+    repos: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    /// Runs in a row the repository timed out in
+    timeouts: u32,
+    /// Seconds since the Unix epoch at which the repository was
+    /// quarantined, none while it's still checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    since: Option<u64>,
+}
+
+impl Quarantine {
+    /// Reads a quarantine file, empty when there's none yet
+    pub fn read(path: &str) -> Result<Quarantine> {
+        if !Path::new(path).exists() {
+            return Ok(Quarantine::default());
+        }
+        let data = fs::read_to_string(path)
+            .map_err(|source| Error::Read { path: path.to_string(), source })?;
+        let quarantine = serde_json::from_str(&data)
+            .map_err(|source| Error::Json { path: path.to_string(), source })?;
+        Ok(quarantine)
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| format!("Unable to write quarantine file {}: {}", path, e))?;
+        Ok(())
+    }
+
+    /// Repositories skipped at a time, with the runs in a row they timed
+    /// out in
+    pub fn skipped(&self, now: u64) -> BTreeMap<String, u32> {
+        self.repos
+            .iter()
+            .filter(|(_, entry)| entry.since.is_some_and(|since| now < since + QUARANTINE_SECS))
+            .map(|(repo, entry)| (repo.clone(), entry.timeouts))
+            .collect()
+    }
+
+    /// Counts the timeouts of the repositories checked in a run, forgetting
+    /// those which didn't time out, and quarantines the repositories which
+    /// timed out in `after` runs in a row. Repositories released from
+    /// quarantine go back in as soon as they time out again.
+    pub fn update(&mut self, checked: &BTreeSet<String>, timed_out: &BTreeSet<String>, after: u32, now: u64) -> Vec<String> {
+        let mut quarantined = Vec::new();
+        for repo in checked {
+            if !timed_out.contains(repo) {
+                self.repos.remove(repo);
+                continue;
+            }
+            let entry = self.repos.entry(repo.clone()).or_insert(Entry { timeouts: 0, since: None });
+            entry.timeouts += 1;
+            if entry.timeouts >= after {
+                entry.since = Some(now);
+                quarantined.push(repo.clone());
+            }
+        }
+        quarantined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantines_repositories_timing_out_in_a_row() {
+        let set = |repos: &[&str]| repos.iter().map(|repo| repo.to_string()).collect::<BTreeSet<String>>();
+        let checked = set(&["org/monorepo", "org/api", "org/web"]);
+        let mut quarantine = Quarantine::default();
+
+        assert!(quarantine.update(&checked, &set(&["org/monorepo", "org/api"]), 2, 100).is_empty());
+        assert_eq!(quarantine.update(&checked, &set(&["org/monorepo"]), 2, 200), ["org/monorepo"]);
+        assert_eq!(quarantine.skipped(300), BTreeMap::from([("org/monorepo".to_string(), 2)]));
+        // org/api checked fine in between, so its count started over
+        assert!(quarantine.update(&set(&["org/api"]), &set(&["org/api"]), 2, 300).is_empty());
+        assert!(quarantine.skipped(200 + QUARANTINE_SECS).is_empty());
+        assert_eq!(quarantine.update(&checked, &set(&["org/monorepo"]), 2, 200 + QUARANTINE_SECS), ["org/monorepo"]);
+        assert!(quarantine.update(&checked, &set(&[]), 2, 300 + QUARANTINE_SECS).is_empty());
+        assert_eq!(quarantine, Quarantine::default());
+
+        let written: Quarantine = serde_json::from_str(r#"{"org/monorepo": {"timeouts": 3, "since": 100}}"#).unwrap();
+        assert_eq!(written.skipped(100).len(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+use semver::{Version, VersionReq};
+
+/// An npm semver range: alternatives separated by `||`, each a set of
+/// comparators that all have to match
+#[derive(Debug, Clone)]
+pub struct Range {
+    alternatives: Vec<VersionReq>,
+}
+
+impl Range {
+    /// Parses an npm range, returning none for specifiers that aren't
+    /// version ranges (dist-tags, URLs, `file:` paths...)
+    pub fn parse(range: &str) -> Option<Range> {
+        let alternatives = range
+            .split("||")
+            .map(|set| VersionReq::parse(&translate(set)?).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Range { alternatives })
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives.iter().any(|req| req.matches(version))
+    }
+}
+
+/// Translates a comparator set from npm's syntax to the semver crate's:
+/// comparators are separated by commas instead of spaces, bare versions
+/// are exact rather than caret requirements, and hyphen ranges are spelled
+/// out as bounds
+fn translate(set: &str) -> Option<String> {
+    let set = set.trim();
+    if set.is_empty() {
+        return Some("*".to_string());
+    }
+
+    if let Some((low, high)) = set.split_once(" - ") {
+        return Some(format!(">={}, <={}", strip_v(low.trim()), strip_v(high.trim())));
+    }
+
+    let mut comparators = Vec::new();
+    let mut pending_operator = String::new();
+    for token in set.split_whitespace() {
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            pending_operator.push_str(token);
+            continue;
+        }
+
+        let token = format!("{}{}", pending_operator, token);
+        pending_operator.clear();
+
+        let operator_end = token.find(|c: char| !"<>=~^".contains(c)).unwrap_or(token.len());
+        let (operator, version) = token.split_at(operator_end);
+        let version = strip_v(version);
+        if !version.starts_with(|c: char| c.is_ascii_digit() || "xX*".contains(c)) {
+            return None;
+        }
+
+        let is_wildcard = version.contains(['x', 'X', '*']);
+        let operator = if operator.is_empty() && !is_wildcard { "=" } else { operator };
+        comparators.push(format!("{}{}", operator, version));
+    }
+
+    Some(comparators.join(", "))
+}
+
+fn strip_v(version: &str) -> &str {
+    version.strip_prefix(['v', 'V']).unwrap_or(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_npm_comparator_sets() {
+        let cases = [
+            ("", Some("*")),
+            ("1.2.3 - 2.3.4", Some(">=1.2.3, <=2.3.4")),
+            ("v1.2.3 - v2.3", Some(">=1.2.3, <=2.3")),
+            ("1.2.3", Some("=1.2.3")),
+            ("v1.2.3", Some("=1.2.3")),
+            ("1.x", Some("1.x")),
+            ("1.2.*", Some("1.2.*")),
+            ("*", Some("*")),
+            (">= 1.2 < 2", Some(">=1.2, <2")),
+            (">=1.2.3 <2.0.0", Some(">=1.2.3, <2.0.0")),
+            ("^1.2.3", Some("^1.2.3")),
+            ("~ 1.2", Some("~1.2")),
+            ("latest", None),
+            ("file:../ui", None),
+        ];
+        for (set, translated) in cases {
+            assert_eq!(translate(set).as_deref(), translated, "{:?}", set);
+        }
+    }
+
+    #[test]
+    fn matches_versions_like_npm() {
+        let cases = [
+            ("1.2.3 - 2.3", "2.3.9", true),
+            ("1.2.3 - 2.3", "2.4.0", false),
+            ("1.2.3 - 2.3", "1.2.2", false),
+            ("1.2.3", "1.2.3", true),
+            ("1.2.3", "1.2.4", false),
+            ("1.x", "1.9.0", true),
+            ("1.x", "2.0.0", false),
+            (">= 1.2 < 2", "1.5.0", true),
+            (">= 1.2 < 2", "2.0.0", false),
+            ("^1.2.3 || ^3", "3.1.0", true),
+            ("^1.2.3 || ^3", "2.0.0", false),
+        ];
+        for (range, version, matches) in cases {
+            let parsed = Range::parse(range).unwrap();
+            assert_eq!(parsed.matches(&Version::parse(version).unwrap()), matches, "{} {}", range, version);
+        }
+        assert!(Range::parse("next || ^1.0.0").is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use log::{debug, warn};
+
+use semver::Version;
+use serde::Deserialize;
+
+use crate::github::GitHub;
+use crate::range::Range;
+use crate::Result;
+
+pub const REGISTRY_URL: &str = "https://registry.npmjs.org";
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Dist {
+    /// Size of the unpacked tarball in bytes, missing on old versions
+    #[serde(rename = "unpackedSize")]
+    pub unpacked_size: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Maintainer {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+/// Registry metadata of one published version
+#[derive(Deserialize, Debug)]
+pub struct Manifest {
+    #[serde(default)]
+    pub dist: Dist,
+    /// Funding as declared in package.json: a URL, an object with a URL, or
+    /// a list of either
+    #[serde(default)]
+    pub funding: Option<serde_json::Value>,
+}
+
+/// Registry metadata of a package
+#[derive(Deserialize, Debug)]
+pub struct Packument {
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub versions: HashMap<String, Manifest>,
+    #[serde(default)]
+    pub maintainers: Vec<Maintainer>,
+    /// Publish date of every version, along with `created` and `modified`
+    #[serde(default)]
+    pub time: HashMap<String, String>,
+}
+
+impl Packument {
+    pub fn latest(&self) -> Option<&str> {
+        self.dist_tags.get("latest").map(String::as_str)
+    }
+
+    /// Returns the funding URLs declared by the latest version
+    pub fn funding(&self) -> Vec<String> {
+        fn collect(funding: &serde_json::Value, urls: &mut Vec<String>) {
+            match funding {
+                serde_json::Value::String(url) => urls.push(url.clone()),
+                serde_json::Value::Object(object) => {
+                    if let Some(serde_json::Value::String(url)) = object.get("url") {
+                        urls.push(url.clone());
+                    }
+                }
+                serde_json::Value::Array(array) => array.iter().for_each(|funding| collect(funding, urls)),
+                _ => {}
+            }
+        }
+
+        let mut funding = Vec::new();
+        let manifest = self.latest().and_then(|latest| self.versions.get(latest));
+        if let Some(declared) = manifest.and_then(|manifest| manifest.funding.as_ref()) {
+            collect(declared, &mut funding);
+        }
+        funding
+    }
+
+    /// Returns the unpacked size of a published version
+    pub fn unpacked_size(&self, version: &str) -> Option<u64> {
+        self.versions.get(version)?.dist.unpacked_size
+    }
+
+    /// Returns when a version was published, as an ISO 8601 timestamp
+    pub fn published(&self, version: &str) -> Option<&str> {
+        self.time.get(version).map(String::as_str)
+    }
+
+    /// Returns the newest published version satisfying a range
+    pub fn newest_matching(&self, range: &Range) -> Option<Version> {
+        self.versions
+            .keys()
+            .filter_map(|version| Version::parse(version).ok())
+            .filter(|version| range.matches(version))
+            .max()
+    }
+}
+
+/// Registry lookups of a run: metadata is cached on disk for a while when
+/// a cache directory is set, concurrent lookups of the same package share
+/// one request, and at most `budget` requests are sent
+#[derive(Clone)]
+pub struct Registry {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    github: GitHub,
+    url: String,
+    cache_dir: Option<PathBuf>,
+    ttl: Duration,
+    budget: Option<usize>,
+    requests: AtomicUsize,
+    lookups: Mutex<HashMap<String, Lookup>>,
+}
+
+type Lookup = Shared<BoxFuture<'static, std::result::Result<Arc<Packument>, String>>>;
+
+impl Registry {
+    pub fn new(github: GitHub, url: &str, cache_dir: Option<&str>, ttl: Duration, budget: Option<usize>) -> Registry {
+        let inner = Inner {
+            github,
+            url: url.trim_end_matches('/').to_string(),
+            cache_dir: cache_dir.map(PathBuf::from),
+            ttl,
+            budget,
+            requests: AtomicUsize::new(0),
+            lookups: Mutex::new(HashMap::new()),
+        };
+        Registry { inner: Arc::new(inner) }
+    }
+
+    /// Fetches the metadata of a package, at most once per run
+    pub async fn packument(&self, name: &str) -> Result<Arc<Packument>> {
+        let lookup = self.inner.lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let inner = self.inner.clone();
+                let name = name.to_string();
+                async move { inner.packument(&name).await.map(Arc::new).map_err(|e| e.to_string()) }
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+        Ok(lookup.await?)
+    }
+}
+
+impl Inner {
+    async fn packument(&self, name: &str) -> Result<Packument> {
+        // Scoped packages keep their @ but have their slash encoded
+        let encoded = name.replace('/', "%2F");
+        let cached = self.cache_dir.as_ref().map(|dir| dir.join(format!("{}.json", encoded)));
+        if let Some(path) = &cached {
+            if let Some(body) = self.read_fresh(path) {
+                debug!("Using cached registry metadata {}", path.display());
+                return Ok(serde_json::from_slice(&body)?);
+            }
+        }
+
+        let requests = self.requests.fetch_add(1, Ordering::SeqCst);
+        if let Some(budget) = self.budget.filter(|&budget| requests >= budget) {
+            return Err(format!("the budget of {} registry requests is spent", budget).into());
+        }
+        let uri = format!("{}/{}", self.url, encoded);
+        let (status, body_bytes) = self.github.get_anonymous(&uri, "application/json").await?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, String::from_utf8_lossy(&body_bytes)).into());
+        }
+        let packument = serde_json::from_slice(&body_bytes)?;
+        if let Some(path) = &cached {
+            let written = fs::create_dir_all(path.parent().unwrap_or(path)).and_then(|_| fs::write(path, &body_bytes));
+            if let Err(e) = written {
+                warn!("Unable to cache registry metadata in {}: {}", path.display(), e);
+            }
+        }
+        Ok(packument)
+    }
+
+    /// Reads a cache file written less than the TTL ago
+    fn read_fresh(&self, path: &Path) -> Option<Vec<u8>> {
+        let age = fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()?;
+        if age > self.ttl {
+            return None;
+        }
+        fs::read(path).ok()
+    }
+}
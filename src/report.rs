@@ -0,0 +1,786 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::ecosystem::{Ecosystem, ManifestSummary};
+use crate::github::Access;
+use crate::history::{Snapshot, TimeArgs};
+use crate::lockfile::{self, DependencyKind, Stats};
+use crate::sign;
+use crate::version::{self, VersionStatus};
+use crate::Result;
+
+/// Merge report files, e.g. of shards run in parallel, into one report
+#[derive(Args, Debug, Clone)]
+pub struct MergeArgs {
+    /// Paths of the reports to merge; for a repository and package found in
+    /// several reports, a successful result wins over a failed one and later
+    /// reports win over earlier ones
+    #[clap(required = true)]
+    reports: Vec<String>,
+
+    /// Path to write the merged report to instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Path of a minisign secret key to sign the merged report with, as
+    /// with check --sign-report
+    #[clap(long, requires = "output")]
+    sign_report: Option<String>,
+}
+
+/// Result of checking a package in one repository
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    pub repo: String,
+    pub package: String,
+    /// Ecosystem whose manifest was read
+    #[serde(default)]
+    pub ecosystem: Option<Ecosystem>,
+    /// Locked version, none when the package or lockfile wasn't found or
+    /// the package is installed from a local path
+    pub version: Option<String>,
+    /// Why the repository couldn't be checked
+    pub error: Option<String>,
+    /// Commit the manifest was read at
+    #[serde(default)]
+    pub sha: Option<String>,
+    /// Path of the manifest in the repository
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Web URL of the manifest at the commit
+    #[serde(default)]
+    pub html_url: Option<String>,
+    /// Raw content URL of the manifest at the commit
+    #[serde(default)]
+    pub raw_url: Option<String>,
+    /// Package counts of the lockfile, with --stats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    /// Packages installed at several versions by the lockfile, with
+    /// --duplicates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<BTreeMap<String, Vec<String>>>,
+    /// Range the root package declares for the package, with
+    /// --latest-in-range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<String>,
+    /// Newest published version satisfying the declared range, with
+    /// --latest-in-range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_in_range: Option<String>,
+    /// Whether package.json was committed after the lockfile, with
+    /// --freshness
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_lockfile: Option<bool>,
+    /// Whether the version is semver, none when there's no version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_status: Option<VersionStatus>,
+    /// When the version was published, with --show-age
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released: Option<String>,
+    /// Latest published version, with --outdated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    /// Unpacked size in bytes of the locked version, with --show-size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Unpacked size in bytes of the latest version, with --show-size and
+    /// --outdated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_size: Option<u64>,
+    /// Locked versions of the packages matching the package glob, when
+    /// --package is a glob such as `@internal/*`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<BTreeMap<String, String>>,
+    /// Git repository the package was installed from instead of a
+    /// registry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_source: Option<String>,
+    /// Local path the package is linked or copied from instead of a
+    /// registry release, e.g. a workspace or a `file:` dependency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_path: Option<String>,
+    /// Registry URLs resolved from hosts other than the approved ones, with
+    /// --approved-registry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unapproved_sources: Option<Vec<String>>,
+    /// Name of the workspace package the manifest belongs to, with --scan
+    /// or --workspaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Directory of the workspace, with --workspaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_dir: Option<String>,
+    /// Template repository the repository was generated from, with
+    /// --template; empty when it wasn't generated from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Versions of the package installed by the root and workspaces of the
+    /// lockfile, keyed by workspace directory, when they disagree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hoist_conflict: Option<BTreeMap<String, String>>,
+    /// Whether the package is only installed as a dev dependency, read
+    /// from package-lock.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dev: Option<bool>,
+    /// Whether the package is declared as a dependency, dev dependency or
+    /// peer dependency, or only installed by other packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency_kind: Option<DependencyKind>,
+    /// Which of a deprecated package and its replacement the repository
+    /// installs, when the package is either of a migration of the policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migration: Option<Migration>,
+    /// Chains of dependencies from the root or a workspace to each
+    /// installed copy of the package, with --why
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency_chains: Option<Vec<String>>,
+    /// Note on the repository from the --annotations file, e.g. why it
+    /// can't be upgraded yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Manifest of each ecosystem found in the repository, with
+    /// --ecosystem-summary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecosystems: Option<Vec<ManifestSummary>>,
+    /// Other columns of the repository's row in a CSV inventory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<BTreeMap<String, String>>,
+    /// Why the manifest couldn't be read, telling a repository the token
+    /// can't access apart from one without the manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<Access>,
+    /// HTTP status of the last manifest request, none when no response was
+    /// received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Milliseconds spent fetching the repository's manifests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// How many requests for the repository were retried after a server
+    /// error, a dropped connection or rate limiting
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Progress of a repository migrating off a deprecated package
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub deprecated: String,
+    pub replacement: String,
+    pub status: MigrationStatus,
+}
+
+/// Which of the packages of a migration a repository installs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationStatus {
+    /// Still only the deprecated package
+    Deprecated,
+    /// Only the replacement, the migration is done
+    Replacement,
+    /// Both, the migration is under way
+    Both,
+    Neither,
+}
+
+impl Migration {
+    pub fn describe(&self) -> String {
+        match self.status {
+            MigrationStatus::Deprecated => format!("still on {}, superseded by {}", self.deprecated, self.replacement),
+            MigrationStatus::Replacement => format!("migrated from {} to {}", self.deprecated, self.replacement),
+            MigrationStatus::Both => format!("migrating, both {} and {}", self.deprecated, self.replacement),
+            MigrationStatus::Neither => format!("neither {} nor {}", self.deprecated, self.replacement),
+        }
+    }
+}
+
+impl Record {
+    pub fn new(repo: &str, package: &str) -> Record {
+        Record {
+            repo: repo.to_string(),
+            package: package.to_string(),
+            ecosystem: None,
+            version: None,
+            error: None,
+            sha: None,
+            path: None,
+            html_url: None,
+            raw_url: None,
+            stats: None,
+            duplicates: None,
+            range: None,
+            newest_in_range: None,
+            stale_lockfile: None,
+            version_status: None,
+            released: None,
+            latest: None,
+            size: None,
+            latest_size: None,
+            matches: None,
+            git_source: None,
+            unapproved_sources: None,
+            local_path: None,
+            workspace: None,
+            workspace_dir: None,
+            template: None,
+            hoist_conflict: None,
+            dev: None,
+            dependency_kind: None,
+            migration: None,
+            dependency_chains: None,
+            note: None,
+            ecosystems: None,
+            inventory: None,
+            access: None,
+            status: None,
+            duration_ms: None,
+            retries: 0,
+        }
+    }
+
+    /// Whether the repository has no lockfile, so only the range its
+    /// package.json declares is known
+    pub fn unlocked(&self) -> bool {
+        self.path.as_deref().is_some_and(|path| path.rsplit('/').next() == Some(lockfile::PACKAGE_JSON))
+    }
+
+    /// Whether two records are results for the same package in the same
+    /// manifest, telling apart the manifests and workspaces of a
+    /// repository checked with --scan or --workspaces
+    pub fn same_manifest(&self, other: &Record) -> bool {
+        self.repo == other.repo && self.package == other.package && self.path == other.path && self.workspace_dir == other.workspace_dir
+    }
+
+    /// Version to show for the record: the locked one, or the declared
+    /// range marked as such when the repository has no lockfile
+    pub fn shown_version(&self) -> Option<String> {
+        match (&self.version, &self.range) {
+            (Some(version), _) => Some(version.clone()),
+            (None, Some(range)) if self.unlocked() => Some(format!("{} (range)", range)),
+            _ => None,
+        }
+    }
+
+    /// Path of the manifest the version was read from, e.g. yarn.lock, none
+    /// when the repository couldn't be fetched
+    pub fn lockfile(&self) -> Option<&str> {
+        self.path.as_deref().or_else(|| self.ecosystem.map(Ecosystem::manifest))
+    }
+
+    /// Renders the record as a line of JSON for --format json
+    pub fn to_json_line(&self) -> Result<String> {
+        Ok(serde_json::to_string(&Versioned::new(&Row::from(self)))?)
+    }
+
+    /// Renders the record as a row of CSV for --format csv, under the
+    /// header of the style
+    pub fn to_csv_row(&self, style: CsvStyle) -> String {
+        let row = Row::from(self);
+        let version = row.version.map_or_else(String::new, |version| style.version(version));
+        let [repo, package, lockfile, error, note] = [row.repo, row.package, row.lockfile.unwrap_or_default(), row.error.unwrap_or_default(), row.note.unwrap_or_default()]
+            .map(|field| style.text(field));
+        let fields = [&repo, &package, &version, &lockfile, &error]
+            .map(|field| style.field(field));
+        let timing = [row.status.map(u64::from), row.duration_ms, Some(row.retries.into())]
+            .map(|field| field.map_or_else(String::new, |n| n.to_string()));
+        let note = style.field(&note);
+        let kind = row.kind.map_or("", DependencyKind::as_str).to_string();
+        fields.into_iter().chain(timing).chain([note, kind]).collect::<Vec<_>>().join(&style.delimiter.to_string())
+    }
+
+    /// Lists what keeps the repository from being compliant, empty when
+    /// nothing was flagged
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(error) = &self.error {
+            problems.push(format!("could not be checked: {}", error));
+        }
+        if let (Some(latest), Some(version)) = (&self.latest, &self.version) {
+            if latest != version {
+                problems.push(format!("{} {} is outdated, latest is {}", self.package, version, latest));
+            }
+        }
+        if let (Some(newest), Some(version)) = (&self.newest_in_range, &self.version) {
+            if newest != version {
+                problems.push(format!("{} {} can be updated to {} within its range", self.package, version, newest));
+            }
+        }
+        if let Some(git_source) = &self.git_source {
+            problems.push(format!("{} is installed from git: {}", self.package, git_source));
+        }
+        for source in self.unapproved_sources.iter().flatten() {
+            problems.push(format!("resolved from an unapproved registry: {}", source));
+        }
+        if let Some(conflict) = &self.hoist_conflict {
+            problems.push(format!("workspaces resolve different versions of {}: {}", self.package, describe_conflict(conflict)));
+        }
+        if self.stale_lockfile == Some(true) {
+            problems.push("package.json was committed after the lockfile".to_string());
+        }
+        if let (Some(VersionStatus::NonSemver), Some(version)) = (self.version_status, &self.version) {
+            problems.push(format!("{} {:?} isn't a semver version", self.package, version));
+        }
+        problems
+    }
+}
+
+/// Version and problems of a record, as compared between runs
+type Outcome<'a> = (Option<&'a str>, Vec<String>);
+
+/// Lists the versions of a hoist conflict with their workspace, e.g.
+/// `4.18.2 (root), 3.0.0 (packages/b)`
+pub fn describe_conflict(conflict: &BTreeMap<String, String>) -> String {
+    let versions: Vec<String> = conflict
+        .iter()
+        .map(|(dir, version)| format!("{} ({})", version, if dir.is_empty() { "root" } else { dir }))
+        .collect();
+    versions.join(", ")
+}
+
+/// Version of the schema of the structured outputs, tagged on every JSON
+/// row, report and history snapshot. Adding a field keeps it; removing or
+/// changing one bumps it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// JSON schema of the structured outputs, printed with --schema
+pub const SCHEMA: &str = include_str!("schema.json");
+
+/// Document of a structured output tagged with the version of its schema
+#[derive(Serialize)]
+pub(crate) struct Versioned<'a, T> {
+    #[serde(rename = "schemaVersion")]
+    schema_version: u32,
+    #[serde(flatten)]
+    document: &'a T,
+}
+
+impl<T> Versioned<'_, T> {
+    pub(crate) fn new(document: &T) -> Versioned<'_, T> {
+        Versioned { schema_version: SCHEMA_VERSION, document }
+    }
+}
+
+/// Columns of the rows printed with --format csv
+pub const CSV_HEADER: &str = "repo,package,version,lockfile,error,status,duration_ms,retries,note,kind";
+
+/// Summary of a record printed per line with --format json
+#[derive(Serialize, Debug)]
+struct Row<'a> {
+    repo: &'a str,
+    package: &'a str,
+    version: Option<&'a str>,
+    lockfile: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<DependencyKind>,
+    error: Option<&'a str>,
+    status: Option<u16>,
+    duration_ms: Option<u64>,
+    retries: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<&'a str>,
+}
+
+impl<'a> From<&'a Record> for Row<'a> {
+    fn from(record: &'a Record) -> Row<'a> {
+        Row {
+            repo: &record.repo,
+            package: &record.package,
+            version: record.version.as_deref(),
+            lockfile: record.lockfile(),
+            kind: record.dependency_kind,
+            error: record.error.as_deref(),
+            status: record.status,
+            duration_ms: record.duration_ms,
+            retries: record.retries,
+            note: record.note.as_deref(),
+        }
+    }
+}
+
+/// How rows are written with --format csv
+#[derive(Debug, Clone, Copy)]
+pub struct CsvStyle {
+    /// Separator of the fields, e.g. `;` for spreadsheets in locales
+    /// where the comma is the decimal separator
+    pub delimiter: char,
+    /// Whether to start with the byte order mark Excel needs to read UTF-8
+    /// and keep versions as text, which it would otherwise turn into
+    /// numbers such as 1.1 for 1.10 or dates such as 4.18.2002 for 4.18.2
+    pub excel: bool,
+}
+
+impl Default for CsvStyle {
+    fn default() -> CsvStyle {
+        CsvStyle { delimiter: ',', excel: false }
+    }
+}
+
+impl CsvStyle {
+    /// Header row of the columns, see CSV_HEADER
+    pub fn header(self) -> String {
+        let header = CSV_HEADER.replace(',', &self.delimiter.to_string());
+        if self.excel {
+            format!("\u{feff}{}", header)
+        } else {
+            header
+        }
+    }
+
+    /// Quotes a field when it holds the delimiter, quotes or line breaks
+    fn field(self, field: &str) -> String {
+        if field.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    /// Writes a version as a formula evaluating to the text for Excel, its
+    /// quotes doubled so that it can't end the string and add to the formula
+    fn version(self, version: &str) -> String {
+        if self.excel {
+            format!("=\"{}\"", version.replace('"', "\"\""))
+        } else {
+            version.to_string()
+        }
+    }
+
+    /// Keeps Excel from evaluating text read from repositories as a
+    /// formula, by starting those that would be one with a quote
+    fn text(self, text: &str) -> String {
+        if self.excel && text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+            format!("'{}", text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Counts of repositories of a report
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub checked: usize,
+    /// Repositories where the package was found
+    pub using: usize,
+    pub with_problems: usize,
+    pub failed: usize,
+}
+
+/// Results of a run, as written to --report
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Report {
+    pub results: Vec<Record>,
+}
+
+impl Report {
+    pub fn new(results: Vec<Record>) -> Report {
+        Report { results }
+    }
+
+    pub fn read(path: &str) -> Result<Report> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read report {}: {}", path, e))?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&Versioned::new(self))?)
+    }
+
+    /// Renders the results as a Markdown table, with a summary line
+    pub fn to_markdown(&self) -> String {
+        let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+
+        let totals = self.totals();
+        let mut markdown = format!(
+            "{} repositories checked, {} with problems\n\n| Repository | Package | Version | Problems |\n| --- | --- | --- | --- |\n",
+            totals.checked,
+            totals.with_problems,
+        );
+        for record in &self.results {
+            let repo = match &record.html_url {
+                Some(html_url) => format!("[{}]({})", cell(&record.repo), html_url),
+                None => cell(&record.repo),
+            };
+            let version = record.shown_version().unwrap_or_else(|| "not found".to_string());
+            let problems: Vec<String> = record.problems().iter().map(|problem| cell(problem)).collect();
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                repo,
+                cell(&record.package),
+                cell(&version),
+                problems.join("<br>"),
+            ));
+        }
+        markdown
+    }
+
+    pub fn totals(&self) -> Totals {
+        Totals {
+            checked: self.results.len(),
+            using: self.results.iter().filter(|record| record.version.is_some()).count(),
+            with_problems: self.results.iter().filter(|record| !record.problems().is_empty()).count(),
+            failed: self.results.iter().filter(|record| record.error.is_some()).count(),
+        }
+    }
+
+    /// Finds the results that have problems although the same manifest and
+    /// package had none in a previous snapshot
+    pub fn regressions(&self, previous: &Snapshot) -> Vec<&Record> {
+        self.results
+            .iter()
+            .filter(|record| {
+                !record.problems().is_empty()
+                    && previous.results.iter().any(|before| before.same_manifest(record) && before.problems().is_empty())
+            })
+            .collect()
+    }
+
+    /// Whether any repository has a different version or different
+    /// problems than in a previous snapshot, or was added or removed since
+    pub fn differs_from(&self, previous: &Snapshot) -> bool {
+        fn outcomes(records: &[Record]) -> BTreeMap<(&str, &str), Outcome<'_>> {
+            records
+                .iter()
+                .map(|record| ((record.repo.as_str(), record.package.as_str()), (record.version.as_deref(), record.problems())))
+                .collect()
+        }
+        outcomes(&self.results) != outcomes(&previous.results)
+    }
+
+    /// Renders a plain-text summary fitting 72 columns: totals, repositories
+    /// that had no problems in the previous snapshot but have some now, and
+    /// the repositories on the oldest versions
+    pub fn to_email_text(&self, package: &str, previous: Option<&Snapshot>, time: &TimeArgs) -> String {
+        const WIDTH: usize = 72;
+        const STRAGGLERS: usize = 10;
+        let line = |text: String| {
+            if text.chars().count() > WIDTH {
+                format!("{}...\n", text.chars().take(WIDTH - 3).collect::<String>())
+            } else {
+                format!("{}\n", text)
+            }
+        };
+
+        let title = format!("check-versions: {}", package);
+        let mut text = line(title.clone());
+        text.push_str(&line("=".repeat(title.chars().count().min(WIDTH))));
+
+        let totals = self.totals();
+        text.push('\n');
+        text.push_str(&line(format!("  {:<30}{:>6}", "Repositories checked", totals.checked)));
+        text.push_str(&line(format!("  {:<30}{:>6}", "Using the package", totals.using)));
+        text.push_str(&line(format!("  {:<30}{:>6}", "With problems", totals.with_problems)));
+        text.push_str(&line(format!("  {:<30}{:>6}", "Failed to check", totals.failed)));
+
+        if let Some(previous) = previous {
+            let regressions = self.regressions(previous);
+            text.push('\n');
+            text.push_str(&line(format!("Regressions since {} ({})", time.format(previous.timestamp), regressions.len())));
+            for record in regressions {
+                text.push_str(&line(format!("  {}: {}", record.repo, record.problems()[0])));
+            }
+        }
+
+        let mut stragglers: Vec<(semver::Version, &Record)> = self.results
+            .iter()
+            .filter_map(|record| {
+                let version = record.version.as_deref()?;
+                Some((version::parse(version)?, record))
+            })
+            .collect();
+        stragglers.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.repo.cmp(&b.1.repo)));
+        if !stragglers.is_empty() {
+            text.push('\n');
+            text.push_str(&line("Top stragglers".to_string()));
+            for (version, record) in stragglers.into_iter().take(STRAGGLERS) {
+                text.push_str(&line(format!("  {:<12}{}", version, record.repo)));
+            }
+        }
+        text
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        fs::write(path, self.to_json()?)
+            .map_err(|e| format!("Unable to write report {}: {}", path, e))?;
+        Ok(())
+    }
+
+    /// Replaces the results of the same manifest and package with the
+    /// given ones, appending results that weren't in the report yet
+    pub fn merge(&mut self, results: Vec<Record>) {
+        for record in results {
+            let existing = self.results
+                .iter_mut()
+                .find(|existing| existing.same_manifest(&record));
+            match existing {
+                Some(existing) => *existing = record,
+                None => self.results.push(record),
+            }
+        }
+    }
+
+    /// Adds the results of another report, keeping a successful result over
+    /// a failed one for the same manifest and package
+    pub fn combine(&mut self, other: Report) {
+        for record in other.results {
+            let existing = self.results
+                .iter_mut()
+                .find(|existing| existing.same_manifest(&record));
+            match existing {
+                Some(existing) if existing.error.is_none() && record.error.is_some() => {}
+                Some(existing) => *existing = record,
+                None => self.results.push(record),
+            }
+        }
+    }
+}
+
+pub fn merge(args: MergeArgs) -> Result<()> {
+    let mut merged = Report::default();
+    for path in &args.reports {
+        merged.combine(Report::read(path)?);
+    }
+
+    match &args.output {
+        Some(output) => {
+            merged.write(output)?;
+            if let Some(key) = &args.sign_report {
+                eprintln!("Signature: {}", sign::sign_report(output, key)?);
+            }
+            Ok(())
+        }
+        None => {
+            println!("{}", merged.to_json()?);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_reports_preferring_successes_then_later_results() {
+        let record = |repo: &str, version: Option<&str>, error: Option<&str>| {
+            let mut record = Record::new(repo, "express");
+            record.version = version.map(str::to_string);
+            record.error = error.map(str::to_string);
+            record
+        };
+        let mut combined = Report::new(vec![
+            record("org/api", Some("4.17.1"), None),
+            record("org/web", None, Some("502 Bad Gateway")),
+            record("org/cli", Some("4.17.1"), None),
+            record("org/docs", None, Some("timed out")),
+        ]);
+        combined.combine(Report::new(vec![
+            // A failure doesn't replace a success
+            record("org/api", None, Some("403 Forbidden")),
+            // A success replaces a failure
+            record("org/web", Some("4.18.2"), None),
+            // Otherwise the later report wins
+            record("org/cli", Some("4.18.2"), None),
+            record("org/docs", None, Some("404 Not Found")),
+            record("org/new", Some("4.18.2"), None),
+        ]));
+        let outcomes: Vec<(&str, Option<&str>, Option<&str>)> = combined.results
+            .iter()
+            .map(|record| (record.repo.as_str(), record.version.as_deref(), record.error.as_deref()))
+            .collect();
+        assert_eq!(outcomes, [
+            ("org/api", Some("4.17.1"), None),
+            ("org/web", Some("4.18.2"), None),
+            ("org/cli", Some("4.18.2"), None),
+            ("org/docs", None, Some("404 Not Found")),
+            ("org/new", Some("4.18.2"), None),
+        ]);
+    }
+
+    #[test]
+    fn merges_the_manifests_of_a_repository_apart() {
+        let record = |path: &str, version: &str| {
+            let mut record = Record::new("org/monorepo", "express");
+            record.path = Some(path.to_string());
+            record.version = Some(version.to_string());
+            record
+        };
+        let mut report = Report::new(vec![record("api/package-lock.json", "4.17.1"), record("web/package-lock.json", "4.17.1")]);
+        report.merge(vec![record("web/package-lock.json", "4.18.2"), record("cli/package-lock.json", "4.18.2")]);
+        let versions: Vec<(&str, &str)> = report.results
+            .iter()
+            .map(|record| (record.path.as_deref().unwrap(), record.version.as_deref().unwrap()))
+            .collect();
+        assert_eq!(versions, [("api/package-lock.json", "4.17.1"), ("web/package-lock.json", "4.18.2"), ("cli/package-lock.json", "4.18.2")]);
+
+        let mut workspace = record("package-lock.json", "4.18.2");
+        workspace.workspace_dir = Some("packages/ui".to_string());
+        report.combine(Report::new(vec![workspace]));
+        assert_eq!(report.results.len(), 4);
+    }
+
+    #[test]
+    fn renders_structured_rows() {
+        let mut record = Record::new("org/api", "express");
+        record.ecosystem = Some(Ecosystem::Npm);
+        record.version = Some("4.18.2".to_string());
+        record.status = Some(200);
+        record.duration_ms = Some(412);
+        assert_eq!(record.to_csv_row(CsvStyle::default()), "org/api,express,4.18.2,package-lock.json,,200,412,0,,");
+        let excel = CsvStyle { delimiter: ';', excel: true };
+        assert_eq!(record.to_csv_row(excel), "org/api;express;\"=\"\"4.18.2\"\"\";package-lock.json;;200;412;0;;");
+        assert_eq!(excel.header(), "\u{feff}repo;package;version;lockfile;error;status;duration_ms;retries;note;kind");
+        assert_eq!(
+            record.to_json_line().unwrap(),
+            r#"{"schemaVersion":2,"repo":"org/api","package":"express","version":"4.18.2","lockfile":"package-lock.json","error":null,"status":200,"duration_ms":412,"retries":0}"#,
+        );
+
+        record.dependency_kind = Some(DependencyKind::Dev);
+        assert!(record.to_csv_row(CsvStyle::default()).ends_with(",0,,dev"));
+        assert!(record.to_json_line().unwrap().contains(r#""lockfile":"package-lock.json","kind":"dev","error""#));
+
+        let mut failed = Record::new("org/web", "express");
+        failed.error = Some("Not Found, \"message\"\n".to_string());
+        failed.retries = 2;
+        failed.note = Some("blocked on Node 18 upgrade, see #12".to_string());
+        assert_eq!(failed.to_csv_row(CsvStyle::default()), "org/web,express,,,\"Not Found, \"\"message\"\"\n\",,,2,\"blocked on Node 18 upgrade, see #12\",");
+        assert!(failed.to_json_line().unwrap().ends_with(r#""retries":2,"note":"blocked on Node 18 upgrade, see #12"}"#));
+
+        let mut hostile = Record::new("org/web", "express");
+        hostile.version = Some("1\"&HYPERLINK(\"https://evil.example\")&\"".to_string());
+        hostile.error = Some("=cmd|' /C calc'!A0".to_string());
+        hostile.note = Some("@SUM(1+1)".to_string());
+        assert_eq!(
+            hostile.to_csv_row(CsvStyle { delimiter: ',', excel: true }),
+            "org/web,express,\"=\"\"1\"\"\"\"&HYPERLINK(\"\"\"\"https://evil.example\"\"\"\")&\"\"\"\"\"\"\",,'=cmd|' /C calc'!A0,,,0,'@SUM(1+1),",
+        );
+        assert!(hostile.to_csv_row(CsvStyle::default()).ends_with(",=cmd|' /C calc'!A0,,,0,@SUM(1+1),"));
+    }
+
+    #[test]
+    fn tags_structured_outputs_with_the_schema_version() {
+        let schema: serde_json::Value = serde_json::from_str(SCHEMA).unwrap();
+        assert_eq!(schema["$defs"]["schemaVersion"]["const"], SCHEMA_VERSION);
+
+        let mut record = Record::new("org/api", "express");
+        record.dependency_kind = Some(DependencyKind::Direct);
+        let report: serde_json::Value = serde_json::from_str(&Report::new(vec![record.clone()]).to_json().unwrap()).unwrap();
+        assert_eq!(report["schemaVersion"], SCHEMA_VERSION);
+        let properties = schema["$defs"]["record"]["properties"].as_object().unwrap();
+        for field in report["results"][0].as_object().unwrap().keys() {
+            assert!(properties.contains_key(field), "{} is missing from the schema", field);
+        }
+        // Reports written before the schema was versioned still read
+        let unversioned: Report = serde_json::from_str(r#"{"results": [{"repo": "org/api", "package": "express", "version": null, "error": null}]}"#).unwrap();
+        assert_eq!(unversioned.results.len(), 1);
+    }
+}
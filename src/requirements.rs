@@ -0,0 +1,72 @@
+use std::str;
+
+/// Compares distribution names as pip does: case-insensitively, with `-`,
+/// `_` and `.` being equivalent. Names are ASCII, so no line is copied to
+/// lowercase it.
+fn same_name(a: &str, b: &str) -> bool {
+    let canonical = |c: char| match c {
+        '_' | '.' => '-',
+        c => c.to_ascii_lowercase(),
+    };
+    a.chars().map(canonical).eq(b.chars().map(canonical))
+}
+
+/// Finds the pinned version of a distribution in a requirements.txt body.
+/// Requirements that aren't pinned with == are returned as their specifier,
+/// e.g. `>=2.0`, or `*` when they have none.
+pub fn find_version(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let body_str = str::from_utf8(body)
+        .map_err(|e| format!("Error converting body to UTF-8: {}", e))?;
+
+    for line in body_str.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with('-') {
+            continue;
+        }
+
+        let name_end = line
+            .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '.'))
+            .unwrap_or(line.len());
+        if !same_name(&line[..name_end], package_name) {
+            continue;
+        }
+
+        let mut specifier = line[name_end..].trim_start();
+        if specifier.starts_with('[') {
+            specifier = specifier.split_once(']').map_or("", |(_, rest)| rest).trim_start();
+        }
+        let specifier = specifier.trim();
+        return Ok(match specifier.strip_prefix("==") {
+            Some(version) => Some(version.trim().to_string()),
+            None if specifier.is_empty() => Some("*".to_string()),
+            None => Some(specifier.to_string()),
+        });
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const REQUIREMENTS: &[u8] = include_bytes!("../tests/fixtures/requirements.txt");
+
+    #[test]
+    fn finds_pins_and_specifiers_by_normalized_name() {
+        assert_eq!(find_version(REQUIREMENTS, "django").unwrap().as_deref(), Some("4.2.7"));
+        assert_eq!(find_version(REQUIREMENTS, "requests").unwrap().as_deref(), Some("2.31.0"));
+        assert_eq!(find_version(REQUIREMENTS, "python-dateutil").unwrap().as_deref(), Some(">=2.8"));
+        assert_eq!(find_version(REQUIREMENTS, "celery").unwrap().as_deref(), Some("*"));
+        assert_eq!(find_version(REQUIREMENTS, "flask").unwrap(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_requirements_never_panic(body in any::<Vec<u8>>(), name in "\\PC*") {
+            let _ = find_version(&body, &name);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use minisign::SecretKey;
+
+use crate::Result;
+
+/// Password of an encrypted --sign-report key; keys generated with
+/// `minisign -G -W` have none
+pub const PASSWORD_VAR: &str = "CHECK_VERSIONS_SIGNING_PASSWORD";
+
+/// Writes a detached minisign signature of a report next to it, as
+/// `<path>.minisig`, so it can be verified with
+/// `minisign -V -p <public key> -m <path>`. Returns the signature's path.
+pub fn sign_report(path: &str, key_path: &str) -> Result<String> {
+    // An empty password reads unencrypted keys instead of prompting for one
+    let password = env::var(PASSWORD_VAR).unwrap_or_default();
+    let key = SecretKey::from_file(key_path, Some(password))
+        .map_err(|e| format!("Unable to read signing key {}: {}", key_path, e))?;
+    let data = fs::read(path)
+        .map_err(|e| format!("Unable to read report {}: {}", path, e))?;
+
+    let file_name = Path::new(path).file_name().map_or_else(|| path.into(), |name| name.to_string_lossy());
+    let signature = signature(&data, &key, &file_name)?;
+    let signature_path = format!("{}.minisig", path);
+    fs::write(&signature_path, signature)
+        .map_err(|e| format!("Unable to write signature {}: {}", signature_path, e))?;
+    Ok(signature_path)
+}
+
+/// Signs data, with the file name and time of signing as the trusted
+/// comment like the minisign CLI
+fn signature(data: &[u8], key: &SecretKey, file_name: &str) -> Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let trusted_comment = format!("timestamp:{}\tfile:{}", timestamp, file_name);
+    let signature = minisign::sign(None, key, Cursor::new(data), Some(&trusted_comment), Some("check-versions report signature"))
+        .map_err(|e| format!("Unable to sign {}: {}", file_name, e))?;
+    Ok(signature.into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minisign::{KeyPair, SignatureBox};
+
+    #[test]
+    fn signs_reports_verifiably() {
+        let KeyPair { pk, sk } = KeyPair::generate_unencrypted_keypair().unwrap();
+        let report = br#"{"results":[]}"#;
+        let signature = SignatureBox::from_string(&signature(report, &sk, "report.json").unwrap()).unwrap();
+
+        assert!(signature.trusted_comment().unwrap().ends_with("\tfile:report.json"));
+        assert!(minisign::verify(&pk, &signature, Cursor::new(report), true, false, false).is_ok());
+        assert!(minisign::verify(&pk, &signature, Cursor::new(br#"{"results":[{}]}"#), true, false, false).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use clap::Args;
+
+use crate::history::{self, Snapshot, TimeArgs};
+use crate::report::Record;
+use crate::Result;
+
+/// Colors of the chart series, the last one for grouped versions
+const PALETTE: [&str; 8] = ["#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#edc948", "#b07aa1", "#bab0ac"];
+
+const CHART_HEIGHT: usize = 200;
+const BAR_WIDTH: usize = 24;
+const BAR_GAP: usize = 8;
+
+/// Render a static dashboard of the history, e.g. for GitHub Pages
+#[derive(Args, Debug, Clone)]
+pub struct SiteArgs {
+    /// History directory written by check --history
+    #[clap(long)]
+    history: String,
+
+    /// Directory to write the pages to
+    #[clap(long)]
+    out: String,
+
+    #[clap(flatten)]
+    time: TimeArgs,
+}
+
+pub fn run(args: SiteArgs) -> Result<()> {
+    let snapshots = history::load(&args.history)?;
+    let latest = snapshots.last().ok_or_else(|| format!("No snapshots in {}", args.history))?;
+
+    let packages: BTreeSet<&str> = snapshots
+        .iter()
+        .flat_map(|snapshot| &snapshot.results)
+        .map(|record| record.package.as_str())
+        .collect();
+
+    fs::create_dir_all(&args.out)
+        .map_err(|e| format!("Unable to create {}: {}", args.out, e))?;
+
+    let mut index = String::from("<h1>check-versions</h1>\n<table>\n<tr><th>Package</th><th>Repositories</th><th>Using it</th><th>With problems</th></tr>\n");
+    for package in &packages {
+        let records: Vec<&Record> = latest.results.iter().filter(|record| record.package == *package).collect();
+        index.push_str(&format!(
+            "<tr><td><a href=\"{}.html\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            slug(package),
+            escape(package),
+            records.len(),
+            records.iter().filter(|record| record.version.is_some()).count(),
+            records.iter().filter(|record| !record.problems().is_empty()).count(),
+        ));
+    }
+    index.push_str(&format!("</table>\n<p>Last run {}</p>\n", args.time.format(latest.timestamp)));
+    write(&args.out, "index.html", "check-versions", &index)?;
+
+    for package in &packages {
+        let mut page = format!("<p><a href=\"index.html\">All packages</a></p>\n<h1>{}</h1>\n", escape(package));
+        page.push_str(&chart(&snapshots, package, &args.time));
+
+        page.push_str("<table>\n<tr><th>Repository</th><th>Version</th><th>Problems</th><th>Note</th></tr>\n");
+        for record in latest.results.iter().filter(|record| record.package == *package) {
+            let repo = match &record.html_url {
+                Some(html_url) => format!("<a href=\"{}\">{}</a>", escape(html_url), escape(&record.repo)),
+                None => escape(&record.repo),
+            };
+            let problems: Vec<String> = record.problems().iter().map(|problem| escape(problem)).collect();
+            page.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                repo,
+                escape(&record.shown_version().unwrap_or_else(|| "not found".to_string())),
+                problems.join("<br>"),
+                escape(record.note.as_deref().unwrap_or_default()),
+            ));
+        }
+        page.push_str("</table>\n");
+        write(&args.out, &format!("{}.html", slug(package)), package, &page)?;
+    }
+
+    eprintln!("Wrote {} pages to {}", packages.len() + 1, args.out);
+    Ok(())
+}
+
+/// Renders a stacked bar chart of how many repositories lock each version
+/// of a package in every snapshot, as inline SVG with a legend
+fn chart(snapshots: &[Snapshot], package: &str, time: &TimeArgs) -> String {
+    let counts: Vec<BTreeMap<&str, usize>> = snapshots
+        .iter()
+        .map(|snapshot| {
+            let mut counts = BTreeMap::new();
+            for record in snapshot.results.iter().filter(|record| record.package == package) {
+                if let Some(version) = &record.version {
+                    *counts.entry(version.as_str()).or_default() += 1;
+                }
+            }
+            counts
+        })
+        .collect();
+
+    // The most used versions get their own color, the rest share the last
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for (version, count) in counts.iter().flatten() {
+        *totals.entry(version).or_default() += count;
+    }
+    let mut versions: Vec<&str> = totals.keys().copied().collect();
+    versions.sort_by(|a, b| totals[b].cmp(&totals[a]).then(a.cmp(b)));
+    let series = |version: &str| {
+        versions.iter().position(|v| *v == version).unwrap_or(0).min(PALETTE.len() - 1)
+    };
+
+    let max = counts.iter().map(|counts| counts.values().sum::<usize>()).max().unwrap_or(0).max(1);
+    let width = snapshots.len() * (BAR_WIDTH + BAR_GAP);
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" role=\"img\">\n", width, CHART_HEIGHT);
+    for (i, (snapshot, counts)) in snapshots.iter().zip(&counts).enumerate() {
+        let x = i * (BAR_WIDTH + BAR_GAP);
+        let mut y = CHART_HEIGHT;
+        for (version, count) in counts {
+            let height = count * CHART_HEIGHT / max;
+            y -= height;
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{} {}: {}</title></rect>\n",
+                x,
+                y,
+                BAR_WIDTH,
+                height,
+                PALETTE[series(version)],
+                time.format(snapshot.timestamp),
+                escape(version),
+                count,
+            ));
+        }
+    }
+    svg.push_str("</svg>\n<ul class=\"legend\">\n");
+
+    for (i, version) in versions.iter().enumerate().take(PALETTE.len()) {
+        let label = if i == PALETTE.len() - 1 && versions.len() > PALETTE.len() {
+            "other versions".to_string()
+        } else {
+            escape(version)
+        };
+        svg.push_str(&format!("<li><span style=\"color: {}\">■</span> {}</li>\n", PALETTE[i], label));
+    }
+    svg.push_str("</ul>\n");
+    svg
+}
+
+fn write(out: &str, name: &str, title: &str, body: &str) -> Result<()> {
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>body {{ font-family: sans-serif; }} td, th {{ padding: 2px 8px; text-align: left; }} .legend {{ list-style: none; padding: 0; }}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body,
+    );
+    let path = Path::new(out).join(name);
+    fs::write(&path, html)
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Turns a package name into a distinct file name, e.g. @scope/name into
+/// _40scope_2fname: characters other than letters, digits, `.` and `-`
+/// are written as `_` and their UTF-8 bytes in hex, so that no two
+/// packages share a page
+fn slug(package: &str) -> String {
+    let mut slug = String::with_capacity(package.len());
+    for c in package.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+            slug.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                slug.push_str(&format!("_{:02x}", byte));
+            }
+        }
+    }
+    slug
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_every_package_its_own_page() {
+        assert_eq!(slug("@acme/ui"), "_40acme_2fui");
+        assert_eq!(slug("acme-ui"), "acme-ui");
+        assert_eq!(slug("acme_ui"), "acme_5fui");
+        assert_eq!(slug("golang.org/x/net"), "golang.org_2fx_2fnet");
+    }
+
+    #[test]
+    fn renders_the_history_of_each_package() {
+        let dir = std::env::temp_dir().join(format!("check-versions-site-{}", std::process::id()));
+        let (history, out) = (dir.join("history"), dir.join("out"));
+        fs::create_dir_all(&history).unwrap();
+        let snapshot = |timestamp: u64, results: &[(&str, &str, Option<&str>)]| {
+            let results: Vec<Record> = results
+                .iter()
+                .map(|(repo, package, version)| {
+                    let mut record = Record::new(repo, package);
+                    record.version = version.map(str::to_string);
+                    record
+                })
+                .collect();
+            let snapshot = serde_json::json!({ "schemaVersion": 2, "timestamp": timestamp, "results": results });
+            fs::write(history.join(format!("{}.json", timestamp)), snapshot.to_string()).unwrap();
+        };
+        snapshot(1000, &[("org/api", "express", Some("4.17.1")), ("org/web", "express", Some("4.17.1")), ("org/api", "@acme/ui", Some("1.0.0"))]);
+        snapshot(2000, &[("org/api", "express", Some("4.18.2")), ("org/web", "express", Some("4.17.1")), ("org/cli", "express", None), ("org/web", "acme-ui", Some("2.0.0"))]);
+
+        let args = SiteArgs { history: history.display().to_string(), out: out.display().to_string(), time: TimeArgs::default() };
+        run(args).unwrap();
+
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        let rows: Vec<&str> = index.lines().filter(|line| line.starts_with("<tr><td>")).collect();
+        assert_eq!(rows, [
+            "<tr><td><a href=\"_40acme_2fui.html\">@acme/ui</a></td><td>0</td><td>0</td><td>0</td></tr>",
+            "<tr><td><a href=\"acme-ui.html\">acme-ui</a></td><td>1</td><td>1</td><td>0</td></tr>",
+            "<tr><td><a href=\"express.html\">express</a></td><td>3</td><td>2</td><td>0</td></tr>",
+        ]);
+
+        let page = fs::read_to_string(out.join("express.html")).unwrap();
+        let heights: Vec<&str> = page
+            .lines()
+            .filter(|line| line.starts_with("<rect"))
+            .filter_map(|line| line.split("height=\"").nth(1)?.split('"').next())
+            .collect();
+        // Two repositories on 4.17.1, then one on each version
+        assert_eq!(heights, ["200", "100", "100"]);
+        assert!(out.join("_40acme_2fui.html").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::any::Any;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::Duration;
+
+use tokio::task::{AbortHandle, JoinError};
+
+/// Why a worker task didn't produce its result
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TaskError {
+    #[error("worker panicked: {0}")]
+    Panicked(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+impl From<JoinError> for TaskError {
+    fn from(e: JoinError) -> TaskError {
+        match e.try_into_panic() {
+            Ok(payload) => TaskError::Panicked(panic_message(payload)),
+            Err(_) => TaskError::Cancelled,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    }
+}
+
+/// Set of spawned worker tasks which are aborted together, either when
+/// cancelled or when the last clone of the set is dropped, so that tasks
+/// never outlive the run that spawned them
+#[derive(Clone)]
+pub struct Tasks {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    handles: Mutex<Vec<AbortHandle>>,
+    cancelled: AtomicBool,
+    timeout: Option<Duration>,
+}
+
+impl Tasks {
+    /// Tasks running longer than `timeout` are aborted and reported as
+    /// timed out
+    pub fn new(timeout: Option<Duration>) -> Tasks {
+        let inner = Inner { handles: Mutex::new(Vec::new()), cancelled: AtomicBool::new(false), timeout };
+        Tasks { inner: Arc::new(inner) }
+    }
+
+    /// Cancel the set on Ctrl-C, letting the run finish with the results
+    /// collected so far
+    pub fn cancel_on_ctrl_c(&self) {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                if let Some(inner) = Weak::upgrade(&inner) {
+                    eprintln!("Interrupted, cancelling the remaining repositories");
+                    inner.cancel();
+                }
+            }
+        });
+    }
+
+    /// Spawn `future`, resolving to its output or to why it has none, with
+    /// a timeout of its own instead of the one of the set when given. Once
+    /// the set is cancelled nothing is spawned anymore.
+    pub fn spawn<F>(&self, future: F, timeout: Option<Duration>) -> impl Future<Output = Result<F::Output, TaskError>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let handle = if self.inner.cancelled.load(Ordering::SeqCst) {
+            None
+        } else {
+            let handle = tokio::spawn(future);
+            let mut handles = self.inner.handles.lock().unwrap_or_else(PoisonError::into_inner);
+            handles.retain(|handle| !handle.is_finished());
+            handles.push(handle.abort_handle());
+            Some(handle)
+        };
+        let timeout = timeout.or(self.inner.timeout);
+
+        async move {
+            let mut handle = handle.ok_or(TaskError::Cancelled)?;
+            match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, &mut handle).await {
+                    Ok(output) => Ok(output?),
+                    Err(_) => {
+                        handle.abort();
+                        Err(TaskError::TimedOut(timeout.as_secs()))
+                    }
+                },
+                None => Ok(handle.await?),
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for handle in self.handles.lock().unwrap_or_else(PoisonError::into_inner).drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_panics_with_their_message() {
+        let tasks = Tasks::new(None);
+        let result = tasks.spawn(async { panic!("boom {}", 1) }, None).await;
+        assert_eq!(result, Err::<(), _>(TaskError::Panicked("boom 1".to_string())));
+    }
+
+    #[tokio::test]
+    async fn aborts_tasks_running_past_the_timeout() {
+        let tasks = Tasks::new(Some(Duration::from_millis(10)));
+        let result = tasks.spawn(tokio::time::sleep(Duration::from_secs(60)), None).await;
+        assert_eq!(result, Err(TaskError::TimedOut(0)));
+        let result = Tasks::new(None).spawn(tokio::time::sleep(Duration::from_secs(60)), Some(Duration::from_millis(10))).await;
+        assert_eq!(result, Err(TaskError::TimedOut(0)));
+        assert_eq!(tasks.spawn(async {}, Some(Duration::from_secs(60))).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn cancels_running_and_future_tasks() {
+        let tasks = Tasks::new(None);
+        let running = tasks.spawn(tokio::time::sleep(Duration::from_secs(60)), None);
+        tokio::task::yield_now().await;
+        tasks.inner.cancel();
+        assert_eq!(running.await, Err(TaskError::Cancelled));
+        assert_eq!(tasks.spawn(async {}, None).await, Err(TaskError::Cancelled));
+    }
+}
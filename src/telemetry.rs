@@ -0,0 +1,149 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::Method;
+use serde_json::{json, Value};
+
+use crate::github::GitHub;
+use crate::Result;
+
+/// Environment variable holding the base URL of an OTLP/HTTP collector, as
+/// read by OpenTelemetry SDKs
+pub const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+const SERVICE_NAME: &str = "check-versions";
+
+/// Collects the spans of a run to export them to an OTLP collector at the
+/// end. A disabled tracer records nothing.
+#[derive(Clone, Default)]
+pub struct Tracer {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    trace_id: String,
+    spans: Mutex<Vec<Value>>,
+}
+
+/// A timed phase of the run, recorded when dropped
+pub struct Span {
+    tracer: Tracer,
+    id: String,
+    parent_id: Option<String>,
+    name: &'static str,
+    start: u128,
+    attributes: Vec<(&'static str, String)>,
+    error: Option<String>,
+}
+
+impl Tracer {
+    pub fn new(enabled: bool) -> Tracer {
+        let inner = enabled.then(|| Arc::new(Inner { trace_id: random_id(16), spans: Mutex::new(Vec::new()) }));
+        Tracer { inner }
+    }
+
+    /// Starts a span, nested in `parent` if any
+    pub fn span(&self, name: &'static str, parent: Option<&Span>) -> Span {
+        Span {
+            tracer: self.clone(),
+            id: if self.inner.is_some() { random_id(8) } else { String::new() },
+            parent_id: parent.map(|parent| parent.id.clone()),
+            name,
+            start: now(),
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Posts the recorded spans to the traces endpoint of an OTLP/HTTP
+    /// collector, given its base URL
+    pub async fn export(&self, github: &GitHub, endpoint: &str) -> Result<()> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return Ok(()),
+        };
+        let spans: Vec<Value> = inner.spans.lock().unwrap_or_else(PoisonError::into_inner).drain(..).collect();
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let payload = json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [attribute("service.name", SERVICE_NAME)] },
+                "scopeSpans": [{
+                    "scope": { "name": SERVICE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        });
+        let uri = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        github.send_json_anonymous(Method::POST, &uri, &payload).await?;
+        Ok(())
+    }
+}
+
+impl Span {
+    pub fn attribute(&mut self, key: &'static str, value: impl ToString) {
+        self.attributes.push((key, value.to_string()));
+    }
+
+    /// Marks the span as failed
+    pub fn fail(&mut self, message: impl ToString) {
+        self.error = Some(message.to_string());
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let inner = match &self.tracer.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+        let status = match &self.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        };
+        let attributes: Vec<Value> = self.attributes.iter().map(|(key, value)| attribute(key, value)).collect();
+        let mut span = json!({
+            "traceId": inner.trace_id,
+            "spanId": self.id,
+            "name": self.name,
+            // Internal
+            "kind": 1,
+            "startTimeUnixNano": self.start.to_string(),
+            "endTimeUnixNano": now().to_string(),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(parent_id) = &self.parent_id {
+            span["parentSpanId"] = json!(parent_id);
+        }
+        inner.spans.lock().unwrap_or_else(PoisonError::into_inner).push(span);
+    }
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn now() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos())
+}
+
+/// Returns a random identifier of `bytes` bytes in hex, as OTLP/JSON
+/// encodes trace and span IDs
+fn random_id(bytes: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut id = String::new();
+    while id.len() < bytes * 2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(now());
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id.truncate(bytes * 2);
+    id
+}
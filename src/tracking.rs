@@ -0,0 +1,190 @@
+use serde_json::json;
+
+use crate::github::{self, GitHub};
+use crate::Result;
+
+/// Project items fetched per GraphQL request
+const PAGE_SIZE: u64 = 100;
+
+const PROJECT_ITEMS_QUERY: &str = r#"
+query($owner: String!, $number: Int!, $first: Int!, $cursor: String) {
+  owner: repositoryOwner(login: $owner) {
+    ... on ProjectV2Owner {
+      projectV2(number: $number) {
+        items(first: $first, after: $cursor) {
+          pageInfo { hasNextPage endCursor }
+          nodes {
+            content {
+              ... on Issue { repository { nameWithOwner } }
+              ... on PullRequest { repository { nameWithOwner } }
+              ... on DraftIssue { title }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Upgrade-campaign artifact listing the repositories to check, given to
+/// --repos by its web URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tracker {
+    /// Issue or pull request whose task list has an item per repository,
+    /// e.g. https://github.com/org/infra/issues/42
+    Issue { host: String, full_name: String, number: u64 },
+    /// Project board with an issue, pull request or draft per repository,
+    /// e.g. https://github.com/orgs/org/projects/7
+    Project { host: String, owner: String, number: u64 },
+}
+
+impl Tracker {
+    /// Recognizes the web URL of an issue, pull request or project, none
+    /// for anything else such as a file path
+    pub fn parse(url: &str) -> Option<Tracker> {
+        let path = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+        let segments: Vec<&str> = path.split(['/', '?', '#']).collect();
+        match segments.as_slice() {
+            [host, "orgs" | "users", owner, "projects", number, ..] => Some(Tracker::Project {
+                host: host.to_string(),
+                owner: owner.to_string(),
+                number: number.parse().ok()?,
+            }),
+            [host, owner, repo, "issues" | "pull", number, ..] => Some(Tracker::Issue {
+                host: host.to_string(),
+                full_name: format!("{}/{}", owner, repo),
+                number: number.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Lists the repositories the tracker refers to, in its order and
+    /// without duplicates
+    pub async fn repos(&self, github: &GitHub) -> Result<Vec<String>> {
+        let repos = match self {
+            Tracker::Issue { host, full_name, number } => {
+                let issue = github.issue(host, full_name, *number).await?;
+                task_list_repos(issue.body.as_deref().unwrap_or_default(), host)
+            }
+            Tracker::Project { host, owner, number } => project_repos(github, host, owner, *number).await?,
+        };
+
+        let mut unique: Vec<String> = Vec::with_capacity(repos.len());
+        for repo in repos {
+            if !unique.contains(&repo) {
+                unique.push(repo);
+            }
+        }
+        Ok(unique)
+    }
+}
+
+/// Lists the repositories of the items of a project, following pagination.
+/// Draft items name the repository in their title.
+async fn project_repos(github: &GitHub, host: &str, owner: &str, number: u64) -> Result<Vec<String>> {
+    let mut repos = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let variables = json!({ "owner": owner, "number": number, "first": PAGE_SIZE, "cursor": cursor });
+        let data = github.graphql(host, PROJECT_ITEMS_QUERY, variables).await?;
+        let items = &data["owner"]["projectV2"]["items"];
+        if items.is_null() {
+            return Err(format!("Project {} of {} not found", number, owner).into());
+        }
+
+        for content in items["nodes"].as_array().into_iter().flatten().map(|node| &node["content"]) {
+            let repo = match (content["repository"]["nameWithOwner"].as_str(), content["title"].as_str()) {
+                (Some(full_name), _) => Some(github::qualified_name(host, full_name)),
+                (None, Some(title)) => repo_in(title, host),
+                (None, None) => None,
+            };
+            repos.extend(repo);
+        }
+
+        let page_info = &items["pageInfo"];
+        match page_info["endCursor"].as_str() {
+            Some(end_cursor) if page_info["hasNextPage"].as_bool() == Some(true) => cursor = Some(end_cursor.to_string()),
+            _ => break,
+        }
+    }
+
+    Ok(repos)
+}
+
+/// Lists the repositories named by the items of a Markdown task list,
+/// checked or not, e.g. `- [x] org/api` or `- [ ] https://github.com/org/web/pull/3`
+fn task_list_repos(markdown: &str, host: &str) -> Vec<String> {
+    markdown
+        .lines()
+        .filter_map(|line| {
+            let item = line.trim_start().strip_prefix(['-', '*', '+'])?.trim_start();
+            let item = ["[ ]", "[x]", "[X]"].iter().find_map(|checkbox| item.strip_prefix(checkbox))?;
+            repo_in(item, host)
+        })
+        .collect()
+}
+
+/// Finds the first repository named in text: a GitHub URL, or `org/repo`
+/// optionally followed by an issue number such as `org/repo#12`
+fn repo_in(text: &str, host: &str) -> Option<String> {
+    text.split(|c: char| c.is_whitespace() || "()[]<>`".contains(c))
+        .find_map(|token| {
+            let token = token.trim_end_matches([',', '.', ':', ';']);
+            match token.strip_prefix("https://").or_else(|| token.strip_prefix("http://")) {
+                Some(url) => {
+                    let mut segments = url.split('/');
+                    let (url_host, owner, repo) = (segments.next()?, segments.next()?, segments.next()?);
+                    let full_name = format!("{}/{}", owner, repo.trim_end_matches(".git"));
+                    (is_name(owner) && is_name(repo)).then(|| github::qualified_name(url_host, &full_name))
+                }
+                None => {
+                    let full_name = token.split('#').next()?;
+                    let (owner, repo) = full_name.split_once('/')?;
+                    (is_name(owner) && is_name(repo)).then(|| github::qualified_name(host, full_name))
+                }
+            }
+        })
+}
+
+/// Whether a path segment can be an owner or repository name
+fn is_name(segment: &str) -> bool {
+    !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_tracker_urls() {
+        assert_eq!(
+            Tracker::parse("https://github.com/org/infra/issues/42"),
+            Some(Tracker::Issue { host: "github.com".to_string(), full_name: "org/infra".to_string(), number: 42 }),
+        );
+        assert_eq!(
+            Tracker::parse("https://ghe.example.com/orgs/org/projects/7/views/2"),
+            Some(Tracker::Project { host: "ghe.example.com".to_string(), owner: "org".to_string(), number: 7 }),
+        );
+        assert_eq!(Tracker::parse("repos.json"), None);
+        assert_eq!(Tracker::parse("https://github.com/org/infra"), None);
+    }
+
+    #[test]
+    fn reads_repositories_from_task_lists() {
+        let markdown = "Upgrade express to 4.18.2\n\
+            - [x] org/api#12 (merged)\n\
+            - [ ] [web](https://github.com/org/web/pull/3)\n\
+            * [ ] `org/worker`, owned by platform\n\
+            - [ ] https://ghe.example.com/team/billing\n\
+            - not a task org/ignored\n\
+            - [ ] no repository here\n";
+        assert_eq!(
+            task_list_repos(markdown, "github.com"),
+            ["org/api", "org/web", "org/worker", "ghe.example.com/team/billing"],
+        );
+        assert_eq!(task_list_repos("- [ ] org/api", "ghe.example.com"), ["ghe.example.com/org/api"]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::vec;
+
+use futures::future::{self, BoxFuture};
+use futures::{FutureExt, TryFutureExt};
+use hyper::client::connect::dns::{GaiResolver, Name};
+use hyper::client::connect::{Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::Uri;
+use hyper_tls::{HttpsConnector, MaybeHttpsStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, UnixStream};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// DNS resolver answering configured hosts with a fixed address (like
+/// curl --resolve) and falling back to getaddrinfo for every other host
+#[derive(Clone)]
+pub struct Resolver {
+    overrides: Arc<HashMap<String, IpAddr>>,
+    gai: GaiResolver,
+}
+
+impl Resolver {
+    pub fn new(overrides: HashMap<String, IpAddr>) -> Resolver {
+        Resolver { overrides: Arc::new(overrides), gai: GaiResolver::new() }
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<Self::Response>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.gai.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        if let Some(ip) = self.overrides.get(name.as_str()) {
+            // The connector sets the port of the request on resolved addresses
+            let addrs = vec![SocketAddr::new(*ip, 0)];
+            return future::ready(Ok(addrs.into_iter())).boxed();
+        }
+
+        self.gai
+            .call(name)
+            .map(|addrs| addrs.map(|addrs| addrs.collect::<Vec<_>>().into_iter()))
+            .boxed()
+    }
+}
+
+/// Connector used by the HTTP client: TCP (with TLS) to the request's host,
+/// or a Unix domain socket of a local sidecar proxy such as Envoy, which
+/// then receives every request regardless of its host
+#[derive(Clone)]
+pub enum Connector {
+    Tcp(HttpsConnector<HttpConnector<Resolver>>),
+    Unix(Arc<PathBuf>),
+}
+
+impl Service<Uri> for Connector {
+    type Response = Stream;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Stream, Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        match self {
+            Connector::Tcp(https) => https.poll_ready(cx),
+            Connector::Unix(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        match self {
+            Connector::Tcp(https) => https.call(uri).map_ok(Stream::Tcp).boxed(),
+            Connector::Unix(path) => {
+                let path = path.clone();
+                async move { Ok(Stream::Unix(UnixStream::connect(path.as_path()).await?)) }.boxed()
+            }
+        }
+    }
+}
+
+pub enum Stream {
+    Tcp(MaybeHttpsStream<TcpStream>),
+    Unix(UnixStream),
+}
+
+impl Connection for Stream {
+    fn connected(&self) -> Connected {
+        match self {
+            Stream::Tcp(stream) => stream.connected(),
+            Stream::Unix(_) => Connected::new(),
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
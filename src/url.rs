@@ -0,0 +1,36 @@
+/// Percent-encodes a query parameter value or a single path segment
+pub fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Percent-encodes a path of several segments, keeping the slashes
+/// between them, e.g. a file of a repository or a ref such as release/1.2
+pub fn encode_path(path: &str) -> String {
+    path.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encodes_query_values() {
+        assert_eq!(
+            encode(r#"project = OPS AND labels = "check-versions" AND summary ~ "org/api""#),
+            "project%20%3D%20OPS%20AND%20labels%20%3D%20%22check-versions%22%20AND%20summary%20~%20%22org%2Fapi%22",
+        );
+        assert_eq!(encode("a_b.c~d-é"), "a_b.c~d-%C3%A9");
+    }
+
+    #[test]
+    fn keeps_the_slashes_of_paths() {
+        assert_eq!(encode_path("packages/ui/package-lock.json"), "packages/ui/package-lock.json");
+        assert_eq!(encode_path("release/1.2 #hotfix?"), "release/1.2%20%23hotfix%3F");
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// Whether a reported version is a semver version
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionStatus {
+    Semver,
+    /// Git SHAs, dist-tags such as latest, ranges and other strings that
+    /// can't be compared as versions
+    NonSemver,
+}
+
+/// Parses a version as semver, accepting a leading `v` or `=` as found in
+/// Go modules and some lockfiles
+pub fn parse(version: &str) -> Option<Version> {
+    let version = version.trim();
+    let version = version.strip_prefix('=').unwrap_or(version).trim_start();
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    Version::parse(version).ok()
+}
+
+/// Normalizes a reported version to its canonical semver form, or returns
+/// it unchanged when it isn't semver
+pub fn normalize(version: &str) -> (String, VersionStatus) {
+    match parse(version) {
+        Some(parsed) => (parsed.to_string(), VersionStatus::Semver),
+        None => (version.to_string(), VersionStatus::NonSemver),
+    }
+}
+
+/// Whether a version is lower than a minimum one, e.g. one with a security
+/// fix. None when the version isn't semver.
+pub fn below(version: &str, minimum: &Version) -> Option<bool> {
+    parse(version).map(|version| version < *minimum)
+}
+
+/// How far a version is from the latest published one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delta {
+    UpToDate,
+    /// Newer than latest, e.g. a pre-release of the next major
+    Ahead,
+    Major(u64),
+    Minor(u64),
+    Patch(u64),
+    /// Pre-release of the latest version
+    Prerelease,
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Delta::UpToDate => write!(f, "up to date"),
+            Delta::Ahead => write!(f, "ahead of latest"),
+            Delta::Major(behind) => write!(f, "{} major behind", behind),
+            Delta::Minor(behind) => write!(f, "{} minor behind", behind),
+            Delta::Patch(behind) => write!(f, "{} patch behind", behind),
+            Delta::Prerelease => write!(f, "pre-release of latest"),
+        }
+    }
+}
+
+/// Compares a version with the latest published one, counting the
+/// releases it's behind at the most significant level that differs. None
+/// when either isn't semver.
+pub fn delta(version: &str, latest: &str) -> Option<Delta> {
+    let (version, latest) = (parse(version)?, parse(latest)?);
+    let delta = if version == latest {
+        Delta::UpToDate
+    } else if version > latest {
+        Delta::Ahead
+    } else if version.major < latest.major {
+        Delta::Major(latest.major - version.major)
+    } else if version.minor < latest.minor {
+        Delta::Minor(latest.minor - version.minor)
+    } else if version.patch < latest.patch {
+        Delta::Patch(latest.patch - version.patch)
+    } else {
+        Delta::Prerelease
+    };
+    Some(delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_prefixes_from_semver_versions() {
+        assert_eq!(normalize("4.18.2"), ("4.18.2".to_string(), VersionStatus::Semver));
+        assert_eq!(normalize("v1.9.1"), ("1.9.1".to_string(), VersionStatus::Semver));
+        assert_eq!(normalize("=2.0.0-rc.1"), ("2.0.0-rc.1".to_string(), VersionStatus::Semver));
+        assert_eq!(normalize("v0.0.0-20231010-abcdef123456").1, VersionStatus::Semver);
+    }
+
+    #[test]
+    fn counts_releases_behind_latest() {
+        assert_eq!(delta("1.2.3", "2.0.1"), Some(Delta::Major(1)));
+        assert_eq!(delta("2.0.1", "2.3.0"), Some(Delta::Minor(3)));
+        assert_eq!(delta("v2.3.0", "2.3.4"), Some(Delta::Patch(4)));
+        assert_eq!(delta("2.3.4", "2.3.4"), Some(Delta::UpToDate));
+        assert_eq!(delta("3.0.0-beta.1", "2.3.4"), Some(Delta::Ahead));
+        assert_eq!(delta("2.3.4-rc.1", "2.3.4"), Some(Delta::Prerelease));
+        assert_eq!(delta("5ff9d7d", "2.3.4"), None);
+        assert_eq!(Delta::Major(1).to_string(), "1 major behind");
+    }
+
+    #[test]
+    fn compares_with_a_minimum_version() {
+        let minimum = Version::parse("4.18.2").unwrap();
+        assert_eq!(below("4.17.3", &minimum), Some(true));
+        assert_eq!(below("4.18.2-rc.1", &minimum), Some(true));
+        assert_eq!(below("v4.18.2", &minimum), Some(false));
+        assert_eq!(below("5.0.0", &minimum), Some(false));
+        assert_eq!(below("latest", &minimum), None);
+    }
+
+    #[test]
+    fn flags_other_strings() {
+        for version in ["latest", "5ff9d7d", ">=2.8", "*", "4.2", ""] {
+            assert_eq!(normalize(version), (version.to_string(), VersionStatus::NonSemver), "{}", version);
+        }
+    }
+}
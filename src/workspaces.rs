@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+use crate::github::GitHub;
+use crate::lockfile;
+use crate::Result;
+
+/// Lists the directories of the sub-projects of a monorepo from its
+/// workspace configuration: the workspaces of the root package.json (also
+/// used by Turborepo), the packages of lerna.json and the projects of
+/// nx.json or workspace.json. Globs are expanded by listing the directory
+/// they apply to, so only a wildcard in the last segment is supported.
+pub async fn discover(github: &GitHub, host: &str, full_name: &str, git_ref: &str) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    let mut dirs = BTreeSet::new();
+
+    for file in ["package.json", "lerna.json", "nx.json", "workspace.json"] {
+        let body = match github.file(host, full_name, file, git_ref).await? {
+            Some(body) => body,
+            None => continue,
+        };
+        let json: Value = match serde_json::from_slice(&body) {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("Ignoring invalid {} of {}: {}", file, full_name, e);
+                continue;
+            }
+        };
+
+        // package.json workspaces are a list, or an object with a list of
+        // packages (Yarn)
+        for list in [&json["workspaces"], &json["workspaces"]["packages"], &json["packages"]] {
+            patterns.extend(list.as_array().into_iter().flatten().filter_map(Value::as_str).map(str::to_string));
+        }
+        // Nx projects map names to their root, or to an object holding it
+        for project in json["projects"].as_object().into_iter().flat_map(|projects| projects.values()) {
+            if let Some(root) = project.as_str().or_else(|| project["root"].as_str()) {
+                dirs.insert(root.trim_end_matches('/').to_string());
+            }
+        }
+    }
+
+    for pattern in patterns {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/').replace("**", "*");
+        let (parent, last) = pattern.rsplit_once('/').unwrap_or(("", &pattern));
+        if parent.contains('*') {
+            eprintln!("Ignoring workspace glob {} of {}: only the last segment may hold a wildcard", pattern, full_name);
+        } else if last.contains('*') {
+            for entry in github.directory(host, full_name, parent, git_ref).await? {
+                if entry.kind == "dir" && lockfile::glob_matches(last, &entry.name) {
+                    dirs.insert(entry.path);
+                }
+            }
+        } else {
+            dirs.insert(pattern);
+        }
+    }
+
+    Ok(dirs.into_iter().collect())
+}
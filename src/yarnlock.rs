@@ -0,0 +1,101 @@
+use std::str;
+
+use semver::Version;
+
+pub const MANIFEST: &str = "yarn.lock";
+
+/// Finds the version of a package in a yarn.lock body, either the v1 format
+/// or the YAML one of Yarn 2+ (Berry). When several versions are locked for
+/// different ranges, the newest one is returned.
+pub fn find_version(body: &[u8], package_name: &str) -> Result<Option<String>, String> {
+    let body_str = str::from_utf8(body)
+        .map_err(|e| format!("Error converting body to UTF-8: {}", e))?;
+
+    let mut in_entry = false;
+    let mut versions = Vec::new();
+    for line in body_str.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            // Entry header listing the descriptors it resolves, e.g.
+            // `"express@^4.17.1", "express@^4.18.0":`
+            let descriptors = line
+                .strip_suffix(':')
+                .ok_or_else(|| format!("Error parsing yarn.lock: unexpected line {:?}", line))?;
+            // Berry describes the lockfile itself in a __metadata entry
+            in_entry = descriptors != "__metadata"
+                && descriptors
+                .split(',')
+                .any(|descriptor| descriptor_name(descriptor.trim().trim_matches('"')) == package_name);
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+
+        // Fields of the entry are indented by two spaces: `version "1.0.0"`
+        // in v1 and `version: 1.0.0` in Berry
+        let field = match line.strip_prefix("  ") {
+            Some(field) if !field.starts_with(' ') => field,
+            _ => continue,
+        };
+        if let Some(version) = field.strip_prefix("version") {
+            let version = version.trim_start_matches(':').trim().trim_matches('"');
+            versions.push(version);
+        }
+    }
+
+    versions.sort_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    });
+    Ok(versions.pop().map(str::to_string))
+}
+
+/// Returns the package name of a descriptor such as `express@^4.17.1`,
+/// `@babel/core@npm:^7.23.0` or `alias@npm:lodash@^3`
+fn descriptor_name(descriptor: &str) -> &str {
+    match descriptor.char_indices().skip(1).find(|&(_, c)| c == '@') {
+        Some((i, _)) => &descriptor[..i],
+        None => descriptor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const V1: &[u8] = include_bytes!("../tests/fixtures/yarn-v1.lock");
+    const BERRY: &[u8] = include_bytes!("../tests/fixtures/yarn-berry.lock");
+
+    #[test]
+    fn finds_versions_in_v1_lockfiles() {
+        assert_eq!(find_version(V1, "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_version(V1, "@babel/core").unwrap().as_deref(), Some("7.23.2"));
+        assert_eq!(find_version(V1, "lodash-old").unwrap().as_deref(), Some("3.10.1"));
+        assert_eq!(find_version(V1, "lodash").unwrap(), None);
+        assert_eq!(find_version(V1, "@babel/code-frame").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_versions_in_berry_lockfiles() {
+        assert_eq!(find_version(BERRY, "express").unwrap().as_deref(), Some("4.18.2"));
+        assert_eq!(find_version(BERRY, "@babel/core").unwrap().as_deref(), Some("7.23.2"));
+        assert_eq!(find_version(BERRY, "qs").unwrap().as_deref(), Some("6.11.0"));
+        assert_eq!(find_version(BERRY, "__metadata").unwrap().as_deref(), None);
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert!(find_version(include_bytes!("../tests/fixtures/package-lock-v3.json"), "express").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_yarn_lock_never_panics(body in any::<Vec<u8>>(), name in "\\PC*") {
+            let _ = find_version(&body, &name);
+        }
+    }
+}
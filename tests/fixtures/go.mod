@@ -0,0 +1,10 @@
+module github.com/acme/service
+
+go 1.21
+
+require github.com/google/uuid v1.3.0
+
+require (
+	github.com/gin-gonic/gin v1.9.1 // indirect
+	golang.org/x/net v0.17.0
+)